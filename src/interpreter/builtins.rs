@@ -0,0 +1,143 @@
+use super::{Int, Interpreter, Key, Value};
+use crate::{syntax::ast::Expr, Result, Spanned};
+use std::io::Write;
+
+/// A function that is implemented by the interpreter itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Print,
+    Len,
+    Push,
+    Pop,
+    Insert,
+    Remove,
+    Contains,
+    Keys,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "len" => Some(Builtin::Len),
+            "push" => Some(Builtin::Push),
+            "pop" => Some(Builtin::Pop),
+            "insert" => Some(Builtin::Insert),
+            "remove" => Some(Builtin::Remove),
+            "contains" => Some(Builtin::Contains),
+            "keys" => Some(Builtin::Keys),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+            Builtin::Len => "len",
+            Builtin::Push => "push",
+            Builtin::Pop => "pop",
+            Builtin::Insert => "insert",
+            Builtin::Remove => "remove",
+            Builtin::Contains => "contains",
+            Builtin::Keys => "keys",
+        }
+    }
+
+    /// Returns the builtin that can be called as a method on `val`,
+    /// e.g. `"abc".len()` or `[1].push(2)`.
+    pub fn method(val: &Value, name: &str) -> Option<Self> {
+        match (val, Builtin::from_name(name)?) {
+            (Value::String(_), builtin @ Builtin::Len) => Some(builtin),
+            (Value::Array(_), builtin @ (Builtin::Len | Builtin::Push | Builtin::Pop)) => {
+                Some(builtin)
+            }
+            (
+                Value::Map(_),
+                builtin @ (Builtin::Len
+                | Builtin::Insert
+                | Builtin::Remove
+                | Builtin::Contains
+                | Builtin::Keys),
+            ) => Some(builtin),
+            _ => None,
+        }
+    }
+
+    /// The number of arguments, including the receiver of a method call.
+    pub fn arity(self) -> usize {
+        match self {
+            Builtin::Print | Builtin::Len | Builtin::Pop | Builtin::Keys => 1,
+            Builtin::Push | Builtin::Remove | Builtin::Contains => 2,
+            Builtin::Insert => 3,
+        }
+    }
+}
+
+impl Interpreter {
+    /// Calls a builtin, where `call` is the whole call expression
+    /// and the receiver of a method call is passed as the first argument.
+    pub(super) fn call_builtin(
+        &mut self,
+        builtin: Builtin,
+        call: &Expr,
+        args: Vec<Spanned<Value>>,
+    ) -> Result<Value> {
+        if args.len() != builtin.arity() {
+            return Err(self.arity_error(call.span(), builtin.arity(), args.len()));
+        }
+
+        match builtin {
+            Builtin::Print => {
+                writeln!(self.output.0, "{}", *args[0]).map_err(|err| {
+                    let label = self.new_label(err.to_string(), call.span());
+                    self.new_error("failed to print", label)
+                })?;
+                Ok(Value::Unit)
+            }
+            Builtin::Len => match args[0].data() {
+                Value::Array(elems) => Ok(Value::Int(Int::new(elems.borrow().len() as i128, None))),
+                Value::String(x) => Ok(Value::Int(Int::new(x.chars().count() as i128, None))),
+                Value::Map(entries) => {
+                    Ok(Value::Int(Int::new(entries.borrow().len() as i128, None)))
+                }
+                val => Err(self.type_error(val, args[0].span(), "array', 'map' or 'string")),
+            },
+            Builtin::Push => {
+                let elems = typecheck!(self, args[0].data(), Array, args[0].span(), "array")?;
+                elems.borrow_mut().push(args[1].data().clone());
+                Ok(Value::Unit)
+            }
+            Builtin::Pop => {
+                let elems = typecheck!(self, args[0].data(), Array, args[0].span(), "array")?;
+                let val = elems.borrow_mut().pop();
+                val.ok_or_else(|| {
+                    let label = self.new_label("this array is empty", args[0].span());
+                    self.new_error("cannot pop from an empty array", label)
+                })
+            }
+            Builtin::Insert => {
+                let entries = typecheck!(self, args[0].data(), Map, args[0].span(), "map")?;
+                let key = self.map_key(&args[1])?;
+                entries.borrow_mut().insert(key, args[2].data().clone());
+                Ok(Value::Unit)
+            }
+            Builtin::Remove => {
+                let entries = typecheck!(self, args[0].data(), Map, args[0].span(), "map")?;
+                let key = self.map_key(&args[1])?;
+                let val = entries.borrow_mut().remove(&key);
+                val.ok_or_else(|| self.missing_key_error(&key, args[1].span()))
+            }
+            Builtin::Contains => {
+                let entries = typecheck!(self, args[0].data(), Map, args[0].span(), "map")?;
+                let key = self.map_key(&args[1])?;
+                let found = entries.borrow().contains_key(&key);
+                Ok(Value::Bool(found))
+            }
+            Builtin::Keys => {
+                let entries = typecheck!(self, args[0].data(), Map, args[0].span(), "map")?;
+                let keys = entries.borrow().keys().map(Key::to_value).collect();
+                Ok(Value::array(keys))
+            }
+        }
+    }
+}
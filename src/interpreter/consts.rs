@@ -0,0 +1,145 @@
+//! The const evaluator, which evaluates `const` items after every module was
+//! declared and before `main` is called.
+//!
+//! Constants are evaluated by the interpreter itself, so they follow the same
+//! rules as the rest of the program, e.g. for overflows. They can only contain
+//! expressions that don't depend on anything that happens at runtime.
+
+use super::{Interpreter, Value};
+use crate::{
+    diagnostic::{Diagnostic, FileId, Label},
+    syntax::{
+        ast::{Expr, ExprKind, FormatPart, Identifier, Type},
+        visit::ExprVisitor,
+    },
+    Result, Span,
+};
+
+/// A `const` item and its value, which is `None` until the constant was evaluated.
+#[derive(Debug)]
+pub struct ConstDef {
+    pub name: Identifier,
+    pub public: bool,
+    pub ty: Type,
+    pub val: Expr,
+    pub value: Option<Value>,
+    /// Whether the constant is being evaluated right now, which is how cycles are detected.
+    pub evaluating: bool,
+}
+
+/// Returns the first part of `expr` that can't be evaluated before the program runs.
+/// `is_const` decides whether a variable or path refers to another constant.
+pub fn find_non_const<'e>(
+    expr: &'e Expr,
+    is_const: &mut dyn FnMut(&Expr) -> bool,
+) -> Option<&'e Expr> {
+    match expr.data() {
+        ExprKind::Literal(_) => None,
+        ExprKind::Variable(_) | ExprKind::Path(_) if is_const(expr) => None,
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+            ..
+        } => find_non_const(left, is_const).or_else(|| find_non_const(right, is_const)),
+        ExprKind::Unary { expr, .. } | ExprKind::Cast { expr, .. } | ExprKind::Grouping(expr) => {
+            find_non_const(expr, is_const)
+        }
+        ExprKind::Tuple(elems) => elems.iter().find_map(|elem| find_non_const(elem, is_const)),
+        ExprKind::FormatString(parts) => parts.iter().find_map(|part| match part {
+            FormatPart::Expr(expr) => find_non_const(expr, is_const),
+            FormatPart::Literal(_) => None,
+        }),
+        _ => Some(expr),
+    }
+}
+
+/// The error for an expression in a constant that was returned by [`find_non_const`].
+pub fn non_const_error(file: FileId, span: Span) -> Diagnostic {
+    Diagnostic::error()
+        .with_message("non-constant expression in a `const` item")
+        .with_labels(vec![Label::primary(file, span)
+            .with_message("this can't be evaluated before the program runs")])
+        .with_notes(vec![
+            "constants can only contain literals, operators, casts, tuples, ranges, format strings and other constants"
+                .to_string(),
+        ])
+}
+
+impl Interpreter {
+    /// Returns the value of the constant `name` of the module `file`, which is
+    /// evaluated the first time it's used. Returns `None` if there is no such constant.
+    pub(super) fn const_value(&mut self, file: FileId, name: &str) -> Result<Option<Value>> {
+        let def = match self.const_def(file, name) {
+            Some(def) => def,
+            None => return Ok(None),
+        };
+        if let Some(value) = &def.value {
+            return Ok(Some(value.clone()));
+        }
+        if def.evaluating {
+            let label = Label::primary(file, def.name.span())
+                .with_message(format!("`{}` depends on itself", name));
+            return Err(Diagnostic::error()
+                .with_message(format!(
+                    "cycle detected when evaluating constant `{}`",
+                    name
+                ))
+                .with_labels(vec![label]));
+        }
+
+        def.evaluating = true;
+        let (val, ty) = (def.val.clone(), def.ty.clone());
+        let result = self.eval_const(file, &val, &ty);
+        if let Some(def) = self.const_def(file, name) {
+            def.evaluating = false;
+            if let Ok(value) = &result {
+                def.value = Some(value.clone());
+            }
+        }
+        result.map(Some)
+    }
+
+    pub(super) fn const_def(&mut self, file: FileId, name: &str) -> Option<&mut ConstDef> {
+        self.modules.get_mut(&file)?.consts.get_mut(name)
+    }
+
+    /// Evaluates the value of a constant in the module `file`, where only other
+    /// constants are in scope.
+    fn eval_const(&mut self, file: FileId, val: &Expr, ty: &Type) -> Result<Value> {
+        let prev_file = std::mem::replace(&mut self.file, file);
+        let prev_env = std::mem::replace(&mut self.env, self.globals.child());
+        let result = self
+            .check_const(val)
+            .and_then(|()| match self.visit_expr(val) {
+                Ok(value) => self.coerce(value, Some(ty), val.span()),
+                Err(unwind) => Err(self.unwind_error(unwind)),
+            });
+        self.env = prev_env;
+        self.file = prev_file;
+        result
+    }
+
+    fn check_const(&self, val: &Expr) -> Result<()> {
+        let namespace = match self.modules.get(&self.file) {
+            Some(namespace) => namespace,
+            None => return Ok(()),
+        };
+        let mut is_const = |expr: &Expr| match expr.data() {
+            ExprKind::Variable(name) => namespace.consts.contains_key(name.data()),
+            ExprKind::Path(path) => path
+                .module
+                .as_ref()
+                .and_then(|module| namespace.imports.get(module.data()))
+                .and_then(|file| self.modules.get(file))
+                .is_some_and(|module| {
+                    path.variant.is_none() && module.consts.contains_key(path.name.data())
+                }),
+            _ => false,
+        };
+        match find_non_const(val, &mut is_const) {
+            Some(expr) => Err(non_const_error(self.file, expr.span())),
+            None => Ok(()),
+        }
+    }
+}
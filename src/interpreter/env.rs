@@ -0,0 +1,62 @@
+use super::Value;
+use crate::{diagnostic::FileId, syntax::ast::Type, Span};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// A variable that was introduced by a `let` statement.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub value: Value,
+    pub mutable: bool,
+    /// The declared type of the variable, which assigned values are converted to.
+    pub ty: Option<Type>,
+    /// The file and span of the name in the `let` statement
+    /// that introduced this binding.
+    pub file: FileId,
+    pub span: Span,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    vars: HashMap<String, Binding>,
+    parent: Option<Env>,
+}
+
+/// A chain of scopes, where each scope can shadow the variables of its parent.
+///
+/// Cloning an `Env` is cheap and the clone will refer to the same scopes.
+#[derive(Debug, Clone, Default)]
+pub struct Env(Rc<RefCell<Scope>>);
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty scope whose parent is `self`.
+    pub fn child(&self) -> Self {
+        Self(Rc::new(RefCell::new(Scope {
+            vars: HashMap::new(),
+            parent: Some(self.clone()),
+        })))
+    }
+
+    /// Defines a new variable in the innermost scope, shadowing
+    /// any variable with the same name.
+    pub fn define(&self, name: impl Into<String>, binding: Binding) {
+        self.0.borrow_mut().vars.insert(name.into(), binding);
+    }
+
+    /// Searches for the binding with the given name, starting at the innermost scope,
+    /// and applies `f` to it.
+    pub fn lookup<R>(&self, name: &str, f: impl FnOnce(&mut Binding) -> R) -> Option<R> {
+        let mut scope = self.0.borrow_mut();
+        match scope.vars.get_mut(name) {
+            Some(binding) => Some(f(binding)),
+            None => scope.parent.as_ref()?.lookup(name, f),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.lookup(name, |binding| binding.value.clone())
+    }
+}
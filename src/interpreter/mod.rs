@@ -1,14 +1,3 @@
-use crate::syntax::{
-    ast::{BinaryOperation, Expr, Identifier, Literal, UnaryOperation},
-    visit::ExprVisitor,
-};
-use crate::{
-    diagnostic::{Diagnostic, FileId, Label},
-    Result, Span,
-};
-use std::fmt;
-
-#[allow(unused)]
 macro_rules! typecheck {
     ($self:ident, $val:expr, $ty:ident, $span:expr, $expect:expr) => {{
         if let Value::$ty(x) = $val {
@@ -19,31 +8,851 @@ macro_rules! typecheck {
     }};
 }
 
+mod builtins;
+mod consts;
+mod env;
+mod value;
+
+pub use builtins::Builtin;
+pub use consts::{find_non_const, non_const_error, ConstDef};
+pub use env::{Binding, Env};
+pub use value::{EnumValue, Function, Int, Key, StructValue, Value};
+
+use crate::syntax::{
+    ast::{
+        BinaryOperation, Block, ClosureArg, ClosureBody, DefArgument, Expr, ExprKind, FieldInit,
+        FormatPart, Identifier, IntTy, Item, ItemKind, LetPattern, LetPatternKind, Literal,
+        MatchArm, Path, Pattern, PatternKind, Stmt, StructField, Type, TypeKind, UnaryOperation,
+        Variant,
+    },
+    visit::{ExprVisitor, ItemVisitor, StmtVisitor},
+};
+use crate::{
+    diagnostic::{already_defined, Diagnostic, FileId, Label},
+    loader::{Module, Program},
+    Result, Span, Spanned,
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fmt,
+    io::Write,
+    rc::Rc,
+};
+
+/// How many function calls can be nested, before the program is stopped with an
+/// error instead of overflowing the stack of the interpreter.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// The names of the fields of a declared struct, in declaration order.
+#[derive(Debug)]
+pub struct StructDef {
+    pub name: Identifier,
+    pub public: bool,
+    pub fields: Vec<StructField>,
+}
+
+/// The variants of a declared enum and how many fields each of them has.
+#[derive(Debug)]
+pub struct EnumDef {
+    pub name: Identifier,
+    pub public: bool,
+    pub variants: Vec<(Identifier, usize)>,
+}
+
+/// Stops the evaluation of the surrounding code, either because of an error or
+/// because of a statement that changes the control flow, which is caught by the
+/// surrounding loop or function.
+///
+/// The error is boxed to keep `Exec` small, because every level of a recursive
+/// evaluation keeps a few of them on the stack.
 #[derive(Debug)]
-pub enum Value {
-    Int(i64),
-    String(String),
-    Bool(bool),
+pub enum Unwind {
+    Error(Box<Diagnostic>),
+    Break(Span),
+    Continue(Span),
+    Return(Value),
+}
+
+impl From<Diagnostic> for Unwind {
+    fn from(err: Diagnostic) -> Self {
+        Unwind::Error(Box::new(err))
+    }
 }
 
-impl fmt::Display for Value {
+/// The result of evaluating an expression or executing a statement.
+pub type Exec<T> = std::result::Result<T, Unwind>;
+
+/// Where the output of `print` goes.
+pub struct Output(Box<dyn Write>);
+
+impl fmt::Debug for Output {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Int(_) => write!(f, "int"),
-            Value::String(_) => write!(f, "string"),
-            Value::Bool(_) => write!(f, "bool"),
-        }
+        f.write_str("Output")
     }
 }
 
+/// The items that were declared in a single module.
+#[derive(Debug, Default)]
+struct Namespace {
+    functions: HashMap<String, Rc<Function>>,
+    structs: HashMap<String, Rc<StructDef>>,
+    enums: HashMap<String, Rc<EnumDef>>,
+    consts: HashMap<String, ConstDef>,
+    /// The methods of every type, that were declared in `impl` blocks.
+    methods: HashMap<String, HashMap<String, Rc<Function>>>,
+    imports: HashMap<String, FileId>,
+}
+
 #[derive(Debug)]
 pub struct Interpreter {
+    /// The module the code that is currently executed belongs to.
     file: FileId,
+    globals: Env,
+    env: Env,
+    modules: HashMap<FileId, Namespace>,
+    output: Output,
+    /// The number of function calls that haven't returned yet.
+    calls: usize,
 }
 
 impl Interpreter {
     pub fn new(file: FileId) -> Self {
-        Self { file }
+        let globals = Env::new();
+        Self {
+            file,
+            env: globals.clone(),
+            globals,
+            modules: HashMap::new(),
+            output: Output(Box::new(std::io::stdout())),
+            calls: 0,
+        }
+    }
+
+    /// Redirects the output of the program, which is stdout by default.
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Output(Box::new(output));
+        self
+    }
+
+    /// Declares the items of every module, evaluates their constants and calls
+    /// the `main` function of the root module.
+    pub fn run(&mut self, program: &Program) -> Result<()> {
+        for module in &program.modules {
+            self.declare(module)?;
+        }
+        for module in &program.modules {
+            for item in &module.items {
+                if let ItemKind::Const { name, .. } = item.data() {
+                    self.const_value(module.file, name.data())?;
+                }
+            }
+        }
+
+        self.file = program.root;
+        let main = self
+            .namespace()
+            .functions
+            .get("main")
+            .cloned()
+            .ok_or_else(|| {
+                Diagnostic::error().with_message("`main` function not found in this program")
+            })?;
+        let span = main.span;
+        self.call_function(main, span, None, vec![])?;
+        Ok(())
+    }
+
+    /// Executes a single statement outside of a function, like the REPL does.
+    pub fn execute(&mut self, stmt: &Stmt) -> Result<()> {
+        match self.visit_stmt(stmt) {
+            Ok(()) => Ok(()),
+            Err(unwind) => Err(self.unwind_error(unwind)),
+        }
+    }
+
+    fn declare(&mut self, module: &Module) -> Result<()> {
+        self.file = module.file;
+        self.namespace().imports = module.imports.clone();
+        for item in &module.items {
+            self.visit_item(item)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the namespace of the module that is currently executed.
+    fn namespace(&mut self) -> &mut Namespace {
+        self.modules.entry(self.file).or_default()
+    }
+
+    /// Fails if a struct or enum of the current module already has the name `name`.
+    fn check_type_name(&mut self, name: &Identifier) -> Result<()> {
+        let file = self.file;
+        let namespace = self.namespace();
+        let prev = namespace
+            .structs
+            .get(name.data())
+            .map(|def| &def.name)
+            .or_else(|| namespace.enums.get(name.data()).map(|def| &def.name));
+        match prev {
+            Some(prev) => Err(already_defined(file, name, prev)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the module that `path` refers to.
+    fn resolve(&mut self, path: &Path) -> Result<FileId> {
+        let module = match &path.module {
+            Some(module) => module,
+            None => return Ok(self.file),
+        };
+        match self.namespace().imports.get(module.data()) {
+            Some(file) => Ok(*file),
+            None => {
+                let label = self.new_label("not found in this scope", module.span());
+                Err(self.new_error(format!("cannot find module `{}`", module.data()), label))
+            }
+        }
+    }
+
+    /// Returns the enum and the index of the variant that `path` refers to,
+    /// or `None` if it doesn't refer to a variant.
+    fn resolve_variant(&mut self, path: &Path) -> Result<Option<(FileId, Rc<EnumDef>, usize)>> {
+        let (file, name, variant) = match (&path.module, &path.variant) {
+            (Some(_), Some(variant)) => (self.resolve(path)?, &path.name, variant),
+            (Some(module), None) if !self.namespace().imports.contains_key(module.data()) => {
+                (self.file, module, &path.name)
+            }
+            _ => return Ok(None),
+        };
+        let def = self
+            .modules
+            .get(&file)
+            .and_then(|namespace| namespace.enums.get(name.data()))
+            .cloned();
+        let def = match def {
+            Some(def) => def,
+            // `a::b` may also refer to a missing module, which is reported by `resolve`.
+            None if path.variant.is_none() => return Ok(None),
+            None => {
+                let label = self.new_label("not found in this module", name.span());
+                return Err(self.new_error(format!("cannot find enum `{}`", name.data()), label));
+            }
+        };
+        self.check_visibility("enum", path.span(), file, &def.name, def.public)?;
+
+        match def
+            .variants
+            .iter()
+            .position(|(name, _)| name.data() == variant.data())
+        {
+            Some(idx) => Ok(Some((file, def, idx))),
+            None => {
+                let label = self.new_label("variant not found", variant.span());
+                Err(self.new_error(
+                    format!(
+                        "no variant named `{}` in enum `{}`",
+                        variant.data(),
+                        def.name.data()
+                    ),
+                    label,
+                ))
+            }
+        }
+    }
+
+    /// Creates a value of the variant `idx` of the enum `def`.
+    fn construct_variant(
+        &self,
+        span: Span,
+        (file, def, idx): (FileId, Rc<EnumDef>, usize),
+        fields: Vec<Value>,
+    ) -> Result<Value> {
+        let (variant, arity) = &def.variants[idx];
+        if fields.len() != *arity {
+            return Err(self.arity_error(span, *arity, fields.len()));
+        }
+        Ok(Value::Enum(Rc::new(EnumValue {
+            name: def.name.data().clone(),
+            file,
+            variant: variant.data().clone(),
+            fields,
+        })))
+    }
+
+    /// Executes every statement inside the block in a new scope, and evaluates to the
+    /// expression at the end of the block, or to unit if there is none.
+    fn eval_block(&mut self, block: &Block) -> Exec<Value> {
+        let env = self.env.child();
+        let prev = std::mem::replace(&mut self.env, env);
+        let result = self.eval_block_inner(block);
+        self.env = prev;
+        result
+    }
+
+    fn eval_block_inner(&mut self, block: &Block) -> Exec<Value> {
+        for stmt in &block.stmts {
+            self.visit_stmt(stmt)?;
+        }
+        match &block.expr {
+            Some(expr) => self.visit_expr(expr),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    /// Executes the body of a loop, and returns whether the loop should stop.
+    fn execute_loop_body(&mut self, block: &Block) -> Exec<bool> {
+        match self.eval_block(block) {
+            Ok(_) | Err(Unwind::Continue(_)) => Ok(false),
+            Err(Unwind::Break(_)) => Ok(true),
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    fn eval_args(&mut self, args: &[Expr]) -> Exec<Vec<Spanned<Value>>> {
+        args.iter()
+            .map(|arg| Ok(arg.span().span(self.visit_expr(arg)?)))
+            .collect()
+    }
+
+    /// Evaluates the body of the first arm that matches the value of `scrutinee`
+    /// in a new scope that contains the bindings of the pattern.
+    fn eval_match(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Exec<Value> {
+        let val = self.visit_expr(scrutinee)?;
+        for arm in arms {
+            let mut bindings = vec![];
+            if !self.match_pattern(&arm.pattern, &val, &mut bindings)? {
+                continue;
+            }
+
+            let env = self.env.child();
+            for (name, value) in bindings {
+                let binding = Binding {
+                    value,
+                    mutable: false,
+                    ty: None,
+                    file: self.file,
+                    span: name.span(),
+                };
+                env.define(name.data().clone(), binding);
+            }
+            let prev = std::mem::replace(&mut self.env, env);
+            let result = self.visit_expr(&arm.body);
+            self.env = prev;
+            return result;
+        }
+
+        let label = self.new_label(format!("`{}` is not covered", val), scrutinee.span());
+        Err(self
+            .new_error("no match arm matches the value", label)
+            .into())
+    }
+
+    /// Evaluates the block of the branch that was taken, where a missing
+    /// `else` evaluates to unit.
+    fn eval_if(&mut self, cond: &Expr, then: &Block, otherwise: Option<&Block>) -> Exec<Value> {
+        match (self.condition(cond)?, otherwise) {
+            (true, Some(_)) => self.eval_block(then),
+            // Without an `else`, the `if` never has a value.
+            (true, None) => self.eval_block(then).map(|_| Value::Unit),
+            (false, Some(otherwise)) => self.eval_block(otherwise),
+            (false, None) => Ok(Value::Unit),
+        }
+    }
+
+    /// Checks if the value matches the pattern and collects the values
+    /// of all bindings in the pattern.
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        val: &Value,
+        bindings: &mut Vec<(Identifier, Value)>,
+    ) -> Result<bool> {
+        match (pattern.data(), val) {
+            (PatternKind::Wildcard, _) => Ok(true),
+            (PatternKind::Binding(name), val) => {
+                bindings.push((name.clone(), val.clone()));
+                Ok(true)
+            }
+            (PatternKind::Literal(Literal::Int(x)), Value::Int(y)) => Ok(*x == y.val),
+            (PatternKind::Literal(Literal::String(x)), Value::String(y)) => Ok(x == y),
+            (PatternKind::Literal(Literal::Bool(x)), Value::Bool(y)) => Ok(x == y),
+            (PatternKind::Struct { name, fields }, Value::Struct(s)) => {
+                let file = self.resolve(name)?;
+                let s = s.borrow();
+                if s.file != file || &s.name != name.name.data() {
+                    return Ok(false);
+                }
+                for field in fields {
+                    let val = s
+                        .field(&field.name)
+                        .ok_or_else(|| self.no_field_error(&s, &field.name))?;
+                    if !self.match_pattern(&field.pattern, val, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (PatternKind::Variant { name, args }, val) => {
+                let (file, def, idx) = match self.resolve_variant(name)? {
+                    Some(variant) => variant,
+                    None => {
+                        let label = self.new_label("not a variant", name.span());
+                        return Err(self
+                            .new_error(format!("expected enum variant, found `{}`", name), label));
+                    }
+                };
+                let (variant, arity) = &def.variants[idx];
+                if args.len() != *arity {
+                    return Err(self.arity_error(pattern.span(), *arity, args.len()));
+                }
+                let val = match val {
+                    Value::Enum(val) => val,
+                    _ => return Ok(false),
+                };
+                if val.file != file
+                    || &val.name != def.name.data()
+                    || &val.variant != variant.data()
+                {
+                    return Ok(false);
+                }
+                for (arg, field) in args.iter().zip(&val.fields) {
+                    if !self.match_pattern(arg, field, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Calls a function, builtin or the constructor of an enum variant.
+    fn call(&mut self, expr: &Expr, callee: &Expr, args: &[Expr]) -> Exec<Value> {
+        if let ExprKind::Path(path) = callee.data() {
+            if let Some(variant) = self.resolve_variant(path)? {
+                let fields = args
+                    .iter()
+                    .map(|arg| self.visit_expr(arg))
+                    .collect::<Exec<Vec<_>>>()?;
+                return Ok(self.construct_variant(expr.span(), variant, fields)?);
+            }
+        }
+
+        let val = self.visit_expr(callee)?;
+        let args = self.eval_args(args)?;
+
+        let result = match val {
+            Value::Function(function) => self.call_function(function, expr.span(), None, args),
+            Value::Builtin(builtin) => self.call_builtin(builtin, expr, args),
+            val => Err(self.type_error(&val, callee.span(), "function")),
+        };
+        Ok(result?)
+    }
+
+    /// Calls a method on the value of `receiver`, which is either declared
+    /// in an `impl` block or a builtin method.
+    fn call_method(
+        &mut self,
+        expr: &Expr,
+        receiver: &Expr,
+        name: &Identifier,
+        args: &[Expr],
+    ) -> Exec<Value> {
+        let val = self.visit_expr(receiver)?;
+        let mut args = self.eval_args(args)?;
+
+        let ty = match &val {
+            Value::Struct(s) => {
+                let s = s.borrow();
+                Some((s.file, s.name.clone()))
+            }
+            Value::Enum(e) => Some((e.file, e.name.clone())),
+            _ => None,
+        };
+        let method = ty.as_ref().and_then(|(file, ty)| {
+            self.modules
+                .get(file)
+                .and_then(|namespace| namespace.methods.get(ty))
+                .and_then(|methods| methods.get(name.data()))
+                .cloned()
+        });
+        if let Some(method) = method {
+            self.check_function_visibility("method", name.span(), &method)?;
+            return Ok(self.call_function(method, expr.span(), Some(val), args)?);
+        }
+
+        match Builtin::method(&val, name) {
+            Some(builtin) => {
+                args.insert(0, receiver.span().span(val));
+                Ok(self.call_builtin(builtin, expr, args)?)
+            }
+            None => {
+                let ty = match ty {
+                    Some((_, ty)) => ty,
+                    None => val.type_name().to_string(),
+                };
+                let label = self.new_label("method not found", name.span());
+                Err(self
+                    .new_error(
+                        format!("no method named `{}` found for `{}`", name.data(), ty),
+                        label,
+                    )
+                    .into())
+            }
+        }
+    }
+
+    /// Calls a function, where `receiver` is the value of `self` if the function is a method.
+    fn call_function(
+        &mut self,
+        function: Rc<Function>,
+        span: Span,
+        receiver: Option<Value>,
+        args: Vec<Spanned<Value>>,
+    ) -> Result<Value> {
+        if args.len() != function.args.len() {
+            return Err(self.arity_error(span, function.args.len(), args.len()));
+        }
+        if self.calls == MAX_CALL_DEPTH {
+            let label = self.new_label(
+                format!(
+                    "this call is nested more than {} calls deep",
+                    MAX_CALL_DEPTH
+                ),
+                span,
+            );
+            return Err(self
+                .new_error("maximum call depth exceeded", label)
+                .with_notes(vec![
+                    "help: check for recursion that never stops".to_string()
+                ]));
+        }
+
+        let env = function.env.child();
+        if let Some(value) = receiver {
+            let binding = Binding {
+                value,
+                mutable: false,
+                ty: None,
+                file: self.file,
+                span: function.span,
+            };
+            env.define("self", binding);
+        }
+        for ((arg, ty), value) in function.args.iter().zip(args) {
+            let value = self.coerce(value.data().clone(), ty.as_ref(), value.span())?;
+            let binding = Binding {
+                value,
+                mutable: false,
+                ty: ty.clone(),
+                file: self.file,
+                span: arg.span(),
+            };
+            env.define(arg.data().clone(), binding);
+        }
+
+        let prev = std::mem::replace(&mut self.env, env);
+        let prev_file = std::mem::replace(&mut self.file, function.file);
+        self.calls += 1;
+        let result = match self.eval_block_inner(&function.body) {
+            Ok(val) | Err(Unwind::Return(val)) => Ok(val),
+            Err(unwind) => Err(self.unwind_error(unwind)),
+        };
+        self.calls -= 1;
+        self.env = prev;
+        self.file = prev_file;
+        self.coerce(result?, function.ret.as_ref(), span)
+    }
+
+    /// Defines the variables of `pattern`, destructuring `value` if it's a tuple pattern.
+    /// `ty` is the declared type of `value`, if there is one.
+    fn bind(
+        &mut self,
+        pattern: &LetPattern,
+        value: Value,
+        mutable: bool,
+        ty: Option<&Type>,
+    ) -> Result<()> {
+        match pattern.data() {
+            LetPatternKind::Wildcard => Ok(()),
+            LetPatternKind::Name(name) => {
+                let binding = Binding {
+                    value,
+                    mutable,
+                    ty: ty.cloned(),
+                    file: self.file,
+                    span: name.span(),
+                };
+                self.env.define(name.data().clone(), binding);
+                Ok(())
+            }
+            LetPatternKind::Tuple(patterns) => {
+                let elems = typecheck!(self, &value, Tuple, pattern.span(), "tuple")?;
+                if elems.len() != patterns.len() {
+                    let plural = |n: usize| if n == 1 { "" } else { "s" };
+                    let label = self.new_label(
+                        format!(
+                            "expected a tuple with {} element{}, found one with {} element{}",
+                            patterns.len(),
+                            plural(patterns.len()),
+                            elems.len(),
+                            plural(elems.len())
+                        ),
+                        pattern.span(),
+                    );
+                    return Err(self.new_error("mismatched types", label));
+                }
+                let tys = match ty.map(Spanned::data) {
+                    Some(TypeKind::Tuple(tys)) if tys.len() == patterns.len() => Some(tys),
+                    _ => None,
+                };
+                for (idx, (pattern, elem)) in patterns.iter().zip(elems.iter()).enumerate() {
+                    let ty = tys.map(|tys| &tys[idx]);
+                    self.bind(pattern, elem.clone(), mutable, ty)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Gives integers without a type the integer type of the argument
+    /// or return value they're passed as.
+    fn coerce(&self, val: Value, ty: Option<&Type>, span: Span) -> Result<Value> {
+        match (val, ty.map(Spanned::data)) {
+            (Value::Int(int), Some(TypeKind::Int(ty))) => {
+                Ok(Value::Int(self.convert_int(int, *ty, span)?))
+            }
+            (Value::Tuple(elems), Some(TypeKind::Tuple(tys))) if elems.len() == tys.len() => {
+                let elems = elems
+                    .iter()
+                    .zip(tys)
+                    .map(|(elem, ty)| self.coerce(elem.clone(), Some(ty), span))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Value::Tuple(Rc::new(elems)))
+            }
+            (val, _) => Ok(val),
+        }
+    }
+
+    /// Converts a value that is assigned to a place to the type of that place,
+    /// which is either its declared type `ty` or the integer type of its `old` value.
+    fn coerce_assigned(
+        &self,
+        val: Value,
+        old: Option<&Value>,
+        ty: Option<&Type>,
+        span: Span,
+    ) -> Result<Value> {
+        match (ty, old, val) {
+            (Some(ty), _, val) => self.coerce(val, Some(ty), span),
+            (None, Some(Value::Int(Int { ty: Some(ty), .. })), Value::Int(int)) => {
+                Ok(Value::Int(self.convert_int(int, *ty, span)?))
+            }
+            (None, _, val) => Ok(val),
+        }
+    }
+
+    /// Converts an integer without a type to `ty`, which fails if it doesn't fit
+    /// into the range of `ty` or if the integer already has a different type.
+    fn convert_int(&self, int: Int, ty: IntTy, span: Span) -> Result<Int> {
+        match int.ty {
+            Some(found) if found == ty => Ok(int),
+            Some(found) => {
+                let label = self.new_label(format!("expected `{}`, found `{}`", ty, found), span);
+                Err(self.new_error("mismatched types", label))
+            }
+            None if ty.contains(int.val) => Ok(Int::new(int.val, Some(ty))),
+            None => {
+                let label =
+                    self.new_label(format!("`{}` doesn't fit into `{}`", int.val, ty), span);
+                Err(self.new_error("integer out of range", label))
+            }
+        }
+    }
+
+    /// Gives both integers the same type, if one of them has a type.
+    fn unify_ints(&self, left: Spanned<Int>, right: Spanned<Int>) -> Result<(Int, Int)> {
+        match (left.ty, right.ty) {
+            (Some(ty), _) => Ok((*left, self.convert_int(*right, ty, right.span())?)),
+            (None, Some(ty)) => Ok((self.convert_int(*left, ty, left.span())?, *right)),
+            (None, None) => Ok((*left, *right)),
+        }
+    }
+
+    /// Checks the left operand of `op` and returns the result if it doesn't depend
+    /// on the right operand, which then isn't evaluated, e.g. for `false && x`.
+    fn short_circuit(
+        &self,
+        op: &BinaryOperation,
+        left: &Value,
+        left_span: Span,
+    ) -> Result<Option<Value>> {
+        match (op, left) {
+            (BinaryOperation::And, _) | (BinaryOperation::Or, _) => {
+                let left = *typecheck!(self, left, Bool, left_span, "bool")?;
+                if left == (op == &BinaryOperation::Or) {
+                    Ok(Some(Value::Bool(left)))
+                } else {
+                    Ok(None)
+                }
+            }
+            (BinaryOperation::BitAnd, Value::Bool(_))
+            | (BinaryOperation::BitOr, Value::Bool(_))
+            | (BinaryOperation::BitXor, Value::Bool(_)) => Ok(None),
+            _ => {
+                typecheck!(self, left, Int, left_span, "int")?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Applies `op` to its evaluated operands, after
+    /// [`short_circuit`](Self::short_circuit) checked the left one.
+    ///
+    /// This is separate from [`visit_binary`](ExprVisitor::visit_binary), which recurses
+    /// into the operands, so long chains like `1 + 2 + 3 + …` take less stack.
+    fn binary_op(
+        &self,
+        expr: &Expr,
+        op: &BinaryOperation,
+        left: Spanned<Value>,
+        right: Spanned<Value>,
+    ) -> Result<Value> {
+        if let Value::Bool(left) = *left {
+            let right = *typecheck!(self, &*right, Bool, right.span(), "bool")?;
+            return Ok(Value::Bool(match op {
+                BinaryOperation::BitAnd => left & right,
+                BinaryOperation::BitOr => left | right,
+                BinaryOperation::BitXor => left ^ right,
+                // `&&` and `||` whose result is decided by the right operand.
+                _ => right,
+            }));
+        }
+        let left_int = *typecheck!(self, &*left, Int, left.span(), "int")?;
+        let right_int = *typecheck!(self, &*right, Int, right.span(), "int")?;
+        let (left_int, right_int) =
+            self.unify_ints(left.span().span(left_int), right.span().span(right_int))?;
+        let left_span = left.span();
+        let (ty, left, right) = (left_int.ty, left_int.val, right_int.val);
+
+        match op {
+            BinaryOperation::Plus => self.int_result(expr, "add", left.checked_add(right), ty),
+            BinaryOperation::Minus => {
+                self.int_result(expr, "subtract", left.checked_sub(right), ty)
+            }
+            BinaryOperation::Mul => self.int_result(expr, "multiply", left.checked_mul(right), ty),
+            BinaryOperation::Div if right == 0 => {
+                let label = self.new_label("the divisor is zero", expr.span());
+                Err(self.new_error("attempt to divide by zero", label))
+            }
+            BinaryOperation::Div => self.int_result(expr, "divide", left.checked_div(right), ty),
+            BinaryOperation::Rem if right == 0 => {
+                let label = self.new_label("the divisor is zero", expr.span());
+                Err(self.new_error(
+                    "attempt to calculate the remainder with a divisor of zero",
+                    label,
+                ))
+            }
+            BinaryOperation::Rem => {
+                self.int_result(expr, "calculate the remainder", left.checked_rem(right), ty)
+            }
+            BinaryOperation::Pow if right < 0 => {
+                let label = self.new_label("the exponent is negative", expr.span());
+                Err(self.new_error("attempt to raise to a negative power", label))
+            }
+            BinaryOperation::Pow => {
+                let val = u32::try_from(right)
+                    .ok()
+                    .and_then(|exp| left.checked_pow(exp));
+                self.int_result(expr, "raise to a power", val, ty)
+            }
+            BinaryOperation::BitAnd => Ok(Value::Int(Int::new(left & right, ty))),
+            BinaryOperation::BitOr => Ok(Value::Int(Int::new(left | right, ty))),
+            BinaryOperation::BitXor => Ok(Value::Int(Int::new(left ^ right, ty))),
+            // Bits that are shifted out of the type are an overflow too,
+            // so `x << n` is the same as `x * 2 ** n`.
+            BinaryOperation::Shl => {
+                let val = u32::try_from(right)
+                    .ok()
+                    .and_then(|amount| 2i128.checked_pow(amount))
+                    .and_then(|factor| left.checked_mul(factor));
+                self.int_result(expr, "shift left", val, ty)
+            }
+            BinaryOperation::Shr => {
+                let val = u32::try_from(right)
+                    .ok()
+                    .map(|amount| left >> amount.min(127));
+                self.int_result(expr, "shift right", val, ty)
+            }
+            // Their left operand was already checked to be a boolean.
+            BinaryOperation::And | BinaryOperation::Or => {
+                Err(self.type_error(&Value::Int(left_int), left_span, "bool"))
+            }
+            BinaryOperation::NotEqual => Ok(Value::Bool(left != right)),
+            BinaryOperation::EqualEqual => Ok(Value::Bool(left == right)),
+            BinaryOperation::Less => Ok(Value::Bool(left < right)),
+            BinaryOperation::LessEqual => Ok(Value::Bool(left <= right)),
+            BinaryOperation::Greater => Ok(Value::Bool(left > right)),
+            BinaryOperation::GreaterEqual => Ok(Value::Bool(left >= right)),
+        }
+    }
+
+    /// Checks that the result of an arithmetic operation, which is `None` if it
+    /// overflowed an `i128`, fits into the type of the operands.
+    fn int_result(
+        &self,
+        expr: &Expr,
+        verb: &str,
+        val: Option<i128>,
+        ty: Option<IntTy>,
+    ) -> Result<Value> {
+        let bounds = Int::new(0, ty).bounds();
+        match val.filter(|val| bounds.contains(*val)) {
+            Some(val) => Ok(Value::Int(Int::new(val, ty))),
+            None => {
+                let label = self.new_label(
+                    format!("the result doesn't fit into `{}`", bounds),
+                    expr.span(),
+                );
+                Err(self.new_error(format!("attempt to {} with overflow", verb), label))
+            }
+        }
+    }
+
+    /// Creates a function declared using `def`, which can only see global
+    /// variables and its own arguments.
+    fn function(
+        &self,
+        name: &Identifier,
+        public: bool,
+        args: &[DefArgument],
+        ret: Option<&Type>,
+        body: &Block,
+    ) -> Function {
+        Function {
+            name: Some(name.clone()),
+            public,
+            args: args
+                .iter()
+                .map(|arg| (arg.name.clone(), Some(arg.ty.clone())))
+                .collect(),
+            ret: ret.cloned(),
+            body: body.clone(),
+            env: self.globals.clone(),
+            file: self.file,
+            span: name.span(),
+        }
+    }
+
+    fn struct_value(&self, val: &Value, span: Span) -> Result<Rc<RefCell<StructValue>>> {
+        typecheck!(self, val, Struct, span, "struct").cloned()
+    }
+
+    fn condition(&mut self, cond: &Expr) -> Exec<bool> {
+        let val = self.visit_expr(cond)?;
+        Ok(*typecheck!(self, &val, Bool, cond.span(), "bool")?)
     }
 
     // Error utilities
@@ -59,24 +868,494 @@ impl Interpreter {
     }
 
     fn type_error(&self, val: &Value, span: Span, expected: &str) -> Diagnostic {
-        let label = self.new_label(format!("expected '{}' found '{}'", expected, val), span);
+        let label = self.new_label(
+            format!("expected '{}' found '{}'", expected, val.type_name()),
+            span,
+        );
         self.new_error("invalid type", label)
     }
+
+    fn arity_error(&self, span: Span, expected: usize, found: usize) -> Diagnostic {
+        let label = self.new_label(
+            format!(
+                "expected {} argument{}, found {}",
+                expected,
+                if expected == 1 { "" } else { "s" },
+                found
+            ),
+            span,
+        );
+        self.new_error("wrong number of arguments", label)
+    }
+
+    /// The error for the error nodes of a syntax tree that still contains syntax errors.
+    fn syntax_error(&self, span: Span) -> Diagnostic {
+        let label = self.new_label("this contains a syntax error", span);
+        self.new_error("cannot run code with syntax errors", label)
+    }
+
+    /// Evaluates `index` and checks that it's a valid index into a collection of length `len`.
+    fn index_of(&mut self, index: &Expr, len: impl FnOnce() -> usize) -> Exec<usize> {
+        let val = self.visit_expr(index)?;
+        let idx = typecheck!(self, &val, Int, index.span(), "int")?.val;
+        let len = len();
+        if idx >= 0 && idx < len as i128 {
+            return Ok(idx as usize);
+        }
+
+        let label = self.new_label(
+            format!("the length is {} but the index is {}", len, idx),
+            index.span(),
+        );
+        Err(self.new_error("index out of bounds", label).into())
+    }
+
+    /// Returns the key for `val`, or an error if it can't be used as the key of a map.
+    fn map_key(&self, val: &Spanned<Value>) -> Result<Key> {
+        Key::from_value(val.data())
+            .ok_or_else(|| self.type_error(val.data(), val.span(), "int', 'string' or 'bool"))
+    }
+
+    fn missing_key_error(&self, key: &Key, span: Span) -> Diagnostic {
+        let key = match key {
+            Key::String(s) => format!("{:?}", s),
+            key => key.to_value().to_string(),
+        };
+        let label = self.new_label(format!("the map has no key {}", key), span);
+        self.new_error("key not found", label)
+    }
+
+    fn outside_loop_error(&self, keyword: &str, span: Span) -> Diagnostic {
+        let label = self.new_label(format!("cannot `{}` outside of a loop", keyword), span);
+        self.new_error(format!("`{}` outside of a loop", keyword), label)
+    }
+
+    /// Turns a control flow statement that wasn't caught by a loop or function into an error.
+    fn unwind_error(&self, unwind: Unwind) -> Diagnostic {
+        match unwind {
+            Unwind::Error(err) => *err,
+            Unwind::Break(span) => self.outside_loop_error("break", span),
+            Unwind::Continue(span) => self.outside_loop_error("continue", span),
+            Unwind::Return(_) => Diagnostic::error().with_message("`return` outside of a function"),
+        }
+    }
+
+    fn no_field_error(&self, val: &StructValue, name: &Identifier) -> Diagnostic {
+        let label = self.new_label("unknown field", name.span());
+        self.new_error(
+            format!("no field `{}` on struct `{}`", name.data(), val.name),
+            label,
+        )
+    }
+
+    fn unresolved_error(&self, name: &Identifier) -> Diagnostic {
+        let label = self.new_label("not found in this scope", name.span());
+        self.new_error(format!("cannot find value `{}`", name.data()), label)
+    }
+
+    /// Returns an error if the item `def`, that was declared in `file`, is private
+    /// and used at `span` outside of its module.
+    fn check_visibility(
+        &self,
+        kind: &str,
+        span: Span,
+        file: FileId,
+        def: &Identifier,
+        public: bool,
+    ) -> Result<()> {
+        if public || file == self.file {
+            return Ok(());
+        }
+        let keyword = match kind {
+            "struct" | "enum" => kind,
+            "constant" => "const",
+            _ => "def",
+        };
+        Err(Diagnostic::error()
+            .with_message(format!("{} `{}` is private", kind, def.data()))
+            .with_labels(vec![
+                self.new_label(format!("private {}", kind), span),
+                Label::secondary(file, def.span())
+                    .with_message(format!("`{}` is declared here", def.data())),
+            ])
+            .with_notes(vec![format!(
+                "help: consider making it public: `pub {} {}`",
+                keyword,
+                def.data()
+            )]))
+    }
+
+    fn check_function_visibility(&self, kind: &str, span: Span, function: &Function) -> Result<()> {
+        match &function.name {
+            Some(def) => self.check_visibility(kind, span, function.file, def, function.public),
+            None => Ok(()),
+        }
+    }
+
+    fn immutable_error(
+        &self,
+        name: &Identifier,
+        span: Span,
+        binding: (FileId, Span),
+    ) -> Diagnostic {
+        let (file, def) = binding;
+        let hint = Label::secondary(file, def).with_message(format!(
+            "first assignment to `{0}`, help: make this binding mutable: `mut {0}`",
+            name.data()
+        ));
+        Diagnostic::error()
+            .with_message(format!(
+                "cannot assign twice to immutable variable `{}`",
+                name.data()
+            ))
+            .with_labels(vec![
+                self.new_label("cannot assign twice to immutable variable", span),
+                hint,
+            ])
+    }
 }
 
 impl ExprVisitor for Interpreter {
-    type Output = Result<Value>;
+    type Output = Exec<Value>;
 
     fn visit_literal(&mut self, _expr: &Expr, literal: &Literal) -> Self::Output {
         Ok(match literal {
-            Literal::Int(x) => Value::Int(*x),
+            Literal::Int(x) => Value::Int(Int::new(*x, None)),
             Literal::String(x) => Value::String(x.clone()),
             Literal::Bool(x) => Value::Bool(*x),
         })
     }
 
-    fn visit_call(&mut self, expr: &Expr, name: &Identifier, args: &Vec<Expr>) -> Self::Output {
-        todo!()
+    fn visit_call(&mut self, expr: &Expr, callee: &Expr, args: &[Expr]) -> Self::Output {
+        self.call(expr, callee, args)
+    }
+
+    fn visit_closure(
+        &mut self,
+        expr: &Expr,
+        args: &[ClosureArg],
+        body: &ClosureBody,
+    ) -> Self::Output {
+        let body = match body {
+            ClosureBody::Block(block) => block.clone(),
+            ClosureBody::Expr(val) => Block {
+                stmts: vec![],
+                expr: Some(val.clone()),
+            },
+        };
+        let function = Function {
+            name: None,
+            public: false,
+            args: args
+                .iter()
+                .map(|arg| (arg.name.clone(), arg.ty.clone()))
+                .collect(),
+            ret: None,
+            body,
+            env: self.env.clone(),
+            file: self.file,
+            span: expr.span(),
+        };
+        Ok(Value::Function(Rc::new(function)))
+    }
+
+    fn visit_array(&mut self, _expr: &Expr, elems: &[Expr]) -> Self::Output {
+        let elems = elems
+            .iter()
+            .map(|elem| self.visit_expr(elem))
+            .collect::<Exec<Vec<_>>>()?;
+        Ok(Value::array(elems))
+    }
+
+    fn visit_format_string(&mut self, _expr: &Expr, parts: &[FormatPart]) -> Self::Output {
+        let mut result = String::new();
+        for part in parts {
+            match part {
+                FormatPart::Literal(s) => result.push_str(s),
+                FormatPart::Expr(expr) => result.push_str(&self.visit_expr(expr)?.to_string()),
+            }
+        }
+        Ok(Value::String(result))
+    }
+
+    fn visit_tuple(&mut self, _expr: &Expr, elems: &[Expr]) -> Self::Output {
+        if elems.is_empty() {
+            return Ok(Value::Unit);
+        }
+        let elems = elems
+            .iter()
+            .map(|elem| self.visit_expr(elem))
+            .collect::<Exec<Vec<_>>>()?;
+        Ok(Value::Tuple(Rc::new(elems)))
+    }
+
+    fn visit_map(&mut self, _expr: &Expr, entries: &[(Expr, Expr)]) -> Self::Output {
+        let mut map = BTreeMap::new();
+        for (key, val) in entries {
+            let key = key.span().span(self.visit_expr(key)?);
+            let key = self.map_key(&key)?;
+            map.insert(key, self.visit_expr(val)?);
+        }
+        Ok(Value::map(map))
+    }
+
+    fn visit_index(&mut self, _expr: &Expr, array: &Expr, index: &Expr) -> Self::Output {
+        let val = self.visit_expr(array)?;
+        if let Value::Map(entries) = &val {
+            let key = index.span().span(self.visit_expr(index)?);
+            let key = self.map_key(&key)?;
+            let val = entries.borrow().get(&key).cloned();
+            return val.ok_or_else(|| self.missing_key_error(&key, index.span()).into());
+        }
+        let elems = typecheck!(self, &val, Array, array.span(), "array")?;
+        let idx = self.index_of(index, || elems.borrow().len())?;
+        let elem = elems.borrow()[idx].clone();
+        Ok(elem)
+    }
+
+    fn visit_struct_literal(
+        &mut self,
+        expr: &Expr,
+        name: &Path,
+        fields: &[FieldInit],
+    ) -> Self::Output {
+        let file = self.resolve(name)?;
+        let def = self
+            .modules
+            .get(&file)
+            .and_then(|namespace| namespace.structs.get(name.name.data()))
+            .cloned()
+            .ok_or_else(|| {
+                let label = self.new_label("not found in this scope", name.span());
+                self.new_error(format!("cannot find struct `{}`", name), label)
+            })?;
+        self.check_visibility("struct", name.span(), file, &def.name, def.public)?;
+
+        let mut vals = Vec::with_capacity(fields.len());
+        for field in fields {
+            if !def
+                .fields
+                .iter()
+                .any(|def_field| def_field.name.data() == field.name.data())
+            {
+                let label = self.new_label("unknown field", field.name.span());
+                return Err(self
+                    .new_error(
+                        format!(
+                            "struct `{}` has no field named `{}`",
+                            def.name.data(),
+                            field.name.data()
+                        ),
+                        label,
+                    )
+                    .into());
+            }
+            vals.push((
+                field.name.data(),
+                field.val.span(),
+                self.visit_expr(&field.val)?,
+            ));
+        }
+
+        let fields = def
+            .fields
+            .iter()
+            .map(|field| {
+                let name = field.name.data();
+                let val = vals.iter().find(|(val_name, ..)| *val_name == name);
+                match val {
+                    Some((_, span, val)) => {
+                        let val = self.coerce(val.clone(), Some(&field.ty), *span)?;
+                        Ok((name.clone(), val))
+                    }
+                    None => {
+                        let label =
+                            self.new_label(format!("missing field `{}`", name), expr.span());
+                        Err(self.new_error(
+                            format!(
+                                "missing field `{}` in initializer of `{}`",
+                                name,
+                                def.name.data()
+                            ),
+                            label,
+                        ))
+                    }
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Value::Struct(Rc::new(RefCell::new(StructValue {
+            name: def.name.data().clone(),
+            file,
+            fields,
+        }))))
+    }
+
+    fn visit_field(&mut self, _expr: &Expr, target: &Expr, name: &Identifier) -> Self::Output {
+        let val = self.visit_expr(target)?;
+        let val = self.struct_value(&val, target.span())?;
+        let val = val.borrow();
+        val.field(name)
+            .cloned()
+            .ok_or_else(|| self.no_field_error(&val, name).into())
+    }
+
+    fn visit_method_call(
+        &mut self,
+        expr: &Expr,
+        receiver: &Expr,
+        name: &Identifier,
+        args: &[Expr],
+    ) -> Self::Output {
+        self.call_method(expr, receiver, name, args)
+    }
+
+    fn visit_cast(&mut self, _expr: &Expr, val: &Expr, ty: &Type) -> Self::Output {
+        let int_ty = match ty.data() {
+            TypeKind::Int(int_ty) => *int_ty,
+            _ => {
+                let label = self.new_label(format!("cannot cast to `{}`", ty), ty.span());
+                return Err(self.new_error("invalid cast", label).into());
+            }
+        };
+        let value = match self.visit_expr(val)? {
+            Value::Int(int) => int.val,
+            Value::Bool(b) => i128::from(b),
+            value => return Err(self.type_error(&value, val.span(), "int' or 'bool").into()),
+        };
+        Ok(Value::Int(Int::new(int_ty.wrap(value), Some(int_ty))))
+    }
+
+    fn visit_range(
+        &mut self,
+        _expr: &Expr,
+        start: &Expr,
+        end: &Expr,
+        inclusive: bool,
+    ) -> Self::Output {
+        let start_val = self.visit_expr(start)?;
+        let start = start
+            .span()
+            .span(*typecheck!(self, &start_val, Int, start.span(), "int")?);
+        let end_val = self.visit_expr(end)?;
+        let end = end
+            .span()
+            .span(*typecheck!(self, &end_val, Int, end.span(), "int")?);
+        let (start, end) = self.unify_ints(start, end)?;
+        Ok(Value::Range {
+            start: start.val,
+            end: end.val,
+            inclusive,
+            ty: start.ty,
+        })
+    }
+
+    fn visit_match(&mut self, _expr: &Expr, scrutinee: &Expr, arms: &[MatchArm]) -> Self::Output {
+        self.eval_match(scrutinee, arms)
+    }
+
+    fn visit_if(
+        &mut self,
+        _expr: &Expr,
+        cond: &Expr,
+        then: &Block,
+        otherwise: Option<&Block>,
+    ) -> Self::Output {
+        self.eval_if(cond, then, otherwise)
+    }
+
+    fn visit_block(&mut self, _expr: &Expr, block: &Block) -> Self::Output {
+        self.eval_block(block)
+    }
+
+    fn visit_assign(&mut self, expr: &Expr, target: &Expr, val: &Expr) -> Self::Output {
+        let name = match target.data() {
+            ExprKind::Variable(name) => name,
+            // Assigning to an element doesn't require the array binding to be mutable,
+            // because arrays and maps are shared and can be mutated through any reference.
+            // Elements have no declared type, so the value takes the type of the element
+            // it replaces, or of any other value for a key that isn't in the map yet.
+            ExprKind::Index { expr: array, index } => {
+                let val_span = val.span();
+                let val = self.visit_expr(val)?;
+                let array_val = self.visit_expr(array)?;
+                // Assigning to a key that isn't in the map inserts it.
+                if let Value::Map(entries) = &array_val {
+                    let key = index.span().span(self.visit_expr(index)?);
+                    let key = self.map_key(&key)?;
+                    let val = {
+                        let entries = entries.borrow();
+                        let old = entries.get(&key).or_else(|| entries.values().next());
+                        self.coerce_assigned(val, old, None, val_span)?
+                    };
+                    entries.borrow_mut().insert(key, val.clone());
+                    return Ok(val);
+                }
+                let elems = typecheck!(self, &array_val, Array, array.span(), "array")?;
+                let idx = self.index_of(index, || elems.borrow().len())?;
+                let val = self.coerce_assigned(val, elems.borrow().get(idx), None, val_span)?;
+                elems.borrow_mut()[idx] = val.clone();
+                return Ok(val);
+            }
+            // The same applies to structs.
+            ExprKind::Field { expr: target, name } => {
+                let val_span = val.span();
+                let val = self.visit_expr(val)?;
+                let target_val = self.visit_expr(target)?;
+                let target_val = self.struct_value(&target_val, target.span())?;
+                let mut target_val = target_val.borrow_mut();
+                let ty = self
+                    .modules
+                    .get(&target_val.file)
+                    .and_then(|namespace| namespace.structs.get(&target_val.name))
+                    .and_then(|def| {
+                        def.fields
+                            .iter()
+                            .find(|field| field.name.data() == name.data())
+                    })
+                    .map(|field| field.ty.clone());
+                let val = self.coerce_assigned(val, None, ty.as_ref(), val_span)?;
+                match target_val.field_mut(name) {
+                    Some(field) => *field = val.clone(),
+                    None => return Err(self.no_field_error(&target_val, name).into()),
+                }
+                return Ok(val);
+            }
+            // The parser only allows the targets above.
+            _ => {
+                let label = self.new_label("cannot assign to this expression", target.span());
+                return Err(self.new_error("invalid assignment target", label).into());
+            }
+        };
+        let val_span = val.span();
+        let val = self.visit_expr(val)?;
+
+        let result = self.env.lookup(name, |binding| {
+            if binding.mutable {
+                let val =
+                    self.coerce_assigned(val, Some(&binding.value), binding.ty.as_ref(), val_span);
+                if let Ok(val) = &val {
+                    binding.value = val.clone();
+                }
+                Ok(val)
+            } else {
+                Err((binding.file, binding.span))
+            }
+        });
+        match result {
+            Some(Ok(val)) => Ok(val?),
+            Some(Err(binding)) => Err(self.immutable_error(name, expr.span(), binding).into()),
+            None if self.namespace().consts.contains_key(name.data()) => {
+                let label = self.new_label("cannot assign to this constant", target.span());
+                Err(self
+                    .new_error(
+                        format!("cannot assign to constant `{}`", name.data()),
+                        label,
+                    )
+                    .into())
+            }
+            None => Err(self.unresolved_error(name).into()),
+        }
     }
 
     fn visit_binary(
@@ -87,30 +1366,24 @@ impl ExprVisitor for Interpreter {
         right: &Expr,
     ) -> Self::Output {
         let left_val = self.visit_expr(left)?;
-        let left = typecheck!(self, &left_val, Int, left.span(), "int")?;
-        let right_val = self.visit_expr(right)?;
-        let right = typecheck!(self, &right_val, Int, right.span(), "int")?;
-
-        match op {
-            BinaryOperation::Plus => Ok(Value::Int(left + right)),
-            BinaryOperation::Minus => Ok(Value::Int(left - right)),
-            BinaryOperation::Mul => Ok(Value::Int(left * right)),
-            BinaryOperation::Div => Ok(Value::Int(left / right)),
-            BinaryOperation::NotEqual => Ok(Value::Bool(left != right)),
-            BinaryOperation::EqualEqual => Ok(Value::Bool(left == right)),
-            BinaryOperation::Less => Ok(Value::Bool(left < right)),
-            BinaryOperation::LessEqual => Ok(Value::Bool(left <= right)),
-            BinaryOperation::Greater => Ok(Value::Bool(left > right)),
-            BinaryOperation::GreaterEqual => Ok(Value::Bool(left >= right)),
+        if let Some(val) = self.short_circuit(op, &left_val, left.span())? {
+            return Ok(val);
         }
+        let right_val = self.visit_expr(right)?;
+        Ok(self.binary_op(
+            expr,
+            op,
+            left.span().span(left_val),
+            right.span().span(right_val),
+        )?)
     }
 
-    fn visit_unary(&mut self, _expr: &Expr, op: &UnaryOperation, right: &Expr) -> Self::Output {
+    fn visit_unary(&mut self, expr: &Expr, op: &UnaryOperation, right: &Expr) -> Self::Output {
         let val = self.visit_expr(right)?;
         match op {
             UnaryOperation::Negate => {
                 let x = typecheck!(self, &val, Int, right.span(), "int")?;
-                Ok(Value::Int(-x))
+                Ok(self.int_result(expr, "negate", x.val.checked_neg(), x.ty)?)
             }
             UnaryOperation::Not => {
                 let x = typecheck!(self, &val, Bool, right.span(), "bool")?;
@@ -119,7 +1392,1093 @@ impl ExprVisitor for Interpreter {
         }
     }
 
-    fn visit_variable(&mut self, expr: &Expr, name: &Identifier) -> Self::Output {
-        todo!()
+    fn visit_variable(&mut self, _expr: &Expr, name: &Identifier) -> Self::Output {
+        if let Some(val) = self.env.get(name) {
+            return Ok(val);
+        }
+        if let Some(function) = self.namespace().functions.get(name.data()) {
+            return Ok(Value::Function(function.clone()));
+        }
+        if let Some(val) = self.const_value(self.file, name.data())? {
+            return Ok(val);
+        }
+        Builtin::from_name(name)
+            .map(Value::Builtin)
+            .ok_or_else(|| self.unresolved_error(name).into())
+    }
+
+    fn visit_path(&mut self, expr: &Expr, path: &Path) -> Self::Output {
+        if let Some(variant) = self.resolve_variant(path)? {
+            return Ok(self.construct_variant(expr.span(), variant, vec![])?);
+        }
+        let file = self.resolve(path)?;
+        let function = self
+            .modules
+            .get(&file)
+            .and_then(|namespace| namespace.functions.get(path.name.data()));
+        match function.cloned() {
+            Some(function) => {
+                self.check_function_visibility("function", path.span(), &function)?;
+                Ok(Value::Function(function))
+            }
+            None => {
+                let def = self
+                    .modules
+                    .get(&file)
+                    .and_then(|namespace| namespace.consts.get(path.name.data()))
+                    .map(|def| (def.name.clone(), def.public));
+                if let Some((def, public)) = def {
+                    self.check_visibility("constant", path.span(), file, &def, public)?;
+                    if let Some(val) = self.const_value(file, path.name.data())? {
+                        return Ok(val);
+                    }
+                }
+                let label = self.new_label("not found in this module", path.name.span());
+                Err(self
+                    .new_error(format!("cannot find `{}`", path), label)
+                    .into())
+            }
+        }
+    }
+}
+
+impl StmtVisitor for Interpreter {
+    type Output = Exec<()>;
+
+    fn visit_expr_stmt(&mut self, _stmt: &Stmt, expr: &Expr) -> Exec<()> {
+        self.visit_expr(expr)?;
+        Ok(())
+    }
+
+    fn visit_loop(&mut self, _stmt: &Stmt, block: &Block) -> Exec<()> {
+        while !self.execute_loop_body(block)? {}
+        Ok(())
+    }
+
+    fn visit_while(&mut self, _stmt: &Stmt, cond: &Expr, block: &Block) -> Exec<()> {
+        while self.condition(cond)? {
+            if self.execute_loop_body(block)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for(
+        &mut self,
+        _stmt: &Stmt,
+        var: &Identifier,
+        iter: &Expr,
+        block: &Block,
+    ) -> Exec<()> {
+        let val = self.visit_expr(iter)?;
+        let elems = val
+            .iter()
+            .ok_or_else(|| self.type_error(&val, iter.span(), "range, array, map or string"))?;
+        for elem in elems {
+            let env = self.env.child();
+            let binding = Binding {
+                value: elem,
+                mutable: false,
+                ty: None,
+                file: self.file,
+                span: var.span(),
+            };
+            env.define(var.data().clone(), binding);
+            let prev = std::mem::replace(&mut self.env, env);
+            let stop = self.execute_loop_body(block);
+            self.env = prev;
+            if stop? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_break(&mut self, stmt: &Stmt) -> Exec<()> {
+        Err(Unwind::Break(stmt.span()))
+    }
+
+    fn visit_continue(&mut self, stmt: &Stmt) -> Exec<()> {
+        Err(Unwind::Continue(stmt.span()))
+    }
+
+    fn visit_return(&mut self, _stmt: &Stmt, val: Option<&Expr>) -> Exec<()> {
+        let val = match val {
+            Some(val) => self.visit_expr(val)?,
+            None => Value::Unit,
+        };
+        Err(Unwind::Return(val))
+    }
+
+    fn visit_let(
+        &mut self,
+        _stmt: &Stmt,
+        pattern: &LetPattern,
+        mutable: bool,
+        ty: Option<&Type>,
+        val: &Expr,
+    ) -> Exec<()> {
+        let value = self.visit_expr(val)?;
+        let value = self.coerce(value, ty, val.span())?;
+        self.bind(pattern, value, mutable, ty)?;
+        Ok(())
+    }
+
+    fn visit_error_stmt(&mut self, stmt: &Stmt) -> Exec<()> {
+        Err(self.syntax_error(stmt.span()).into())
+    }
+}
+
+impl ItemVisitor for Interpreter {
+    type Output = Result<()>;
+
+    fn visit_def(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        public: bool,
+        args: &[DefArgument],
+        ret: Option<&Type>,
+        body: &Block,
+    ) -> Result<()> {
+        let file = self.file;
+        let prev = self.namespace().functions.get(name.data());
+        if let Some(prev) = prev.and_then(|prev| prev.name.as_ref()) {
+            return Err(already_defined(file, name, prev));
+        }
+        let function = self.function(name, public, args, ret, body);
+        self.namespace()
+            .functions
+            .insert(name.data().clone(), Rc::new(function));
+        Ok(())
+    }
+
+    fn visit_const(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        public: bool,
+        ty: &Type,
+        val: &Expr,
+    ) -> Result<()> {
+        let file = self.file;
+        if let Some(prev) = self.namespace().consts.get(name.data()) {
+            return Err(already_defined(file, name, &prev.name));
+        }
+        let def = ConstDef {
+            name: name.clone(),
+            public,
+            ty: ty.clone(),
+            val: val.clone(),
+            value: None,
+            evaluating: false,
+        };
+        self.namespace().consts.insert(name.data().clone(), def);
+        Ok(())
+    }
+
+    fn visit_impl(&mut self, _item: &Item, ty: &Identifier, methods: &[Item]) -> Result<()> {
+        for method in methods {
+            if let ItemKind::Def {
+                name,
+                public,
+                args,
+                ret,
+                body,
+            } = method.data()
+            {
+                let function = self.function(name, *public, args, ret.as_ref(), body);
+                let file = self.file;
+                let methods = self
+                    .namespace()
+                    .methods
+                    .entry(ty.data().clone())
+                    .or_default();
+                let prev = methods.get(name.data());
+                if let Some(prev) = prev.and_then(|prev| prev.name.as_ref()) {
+                    return Err(already_defined(file, name, prev));
+                }
+                methods.insert(name.data().clone(), Rc::new(function));
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_struct(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        public: bool,
+        fields: &[StructField],
+    ) -> Result<()> {
+        self.check_type_name(name)?;
+        let def = StructDef {
+            name: name.clone(),
+            public,
+            fields: fields.to_vec(),
+        };
+        self.namespace()
+            .structs
+            .insert(name.data().clone(), Rc::new(def));
+        Ok(())
+    }
+
+    fn visit_enum(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        public: bool,
+        variants: &[Variant],
+    ) -> Result<()> {
+        self.check_type_name(name)?;
+        let def = EnumDef {
+            name: name.clone(),
+            public,
+            variants: variants
+                .iter()
+                .map(|variant| (variant.name.clone(), variant.fields.len()))
+                .collect(),
+        };
+        self.namespace()
+            .enums
+            .insert(name.data().clone(), Rc::new(def));
+        Ok(())
+    }
+
+    fn visit_import(
+        &mut self,
+        _item: &Item,
+        _name: &Identifier,
+        _path: Option<&Spanned<String>>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_error_item(&mut self, item: &Item) -> Result<()> {
+        Err(self.syntax_error(item.span()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Files, loader::Loader, syntax::Parser};
+
+    #[derive(Debug, Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs a program and returns everything it printed.
+    fn run_program(src: &str) -> Result<String> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let items = Parser::new(&files, file).parse_items()?;
+        execute(&Program::single(file, items))
+    }
+
+    /// Runs a program that consists of multiple files, where the first one is the root.
+    fn run_modules(sources: &[(&str, &str)]) -> (Files, Result<String>) {
+        let root = sources[0].0;
+        let sources = sources
+            .iter()
+            .map(|(path, src)| (std::path::PathBuf::from(path), src.to_string()))
+            .collect::<HashMap<_, _>>();
+        let mut files = Files::new();
+        let program = Loader::new(&mut files)
+            .with_reader(move |path| Ok(sources[path].clone()))
+            .load(root);
+        let output = program
+            .map_err(|mut errors| errors.remove(0))
+            .and_then(|program| execute(&program));
+        (files, output)
+    }
+
+    fn execute(program: &Program) -> Result<String> {
+        let buffer = Buffer::default();
+        Interpreter::new(program.root)
+            .with_output(buffer.clone())
+            .run(program)?;
+        let output = buffer.0.borrow().clone();
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn run(src: &str) -> Result<Interpreter> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let mut parser = Parser::new(&files, file);
+        let mut interpreter = Interpreter::new(file);
+        while !parser.at_end() {
+            let stmt = parser.next_stmt()?;
+            interpreter.execute(&stmt)?;
+        }
+        Ok(interpreter)
+    }
+
+    #[test]
+    fn test_mutable_assignment() {
+        let interpreter = run("let mut x = 1; x = x + 41;").unwrap();
+        assert!(matches!(
+            interpreter.env.get("x"),
+            Some(Value::Int(Int { val: 42, .. }))
+        ));
+    }
+
+    #[test]
+    fn test_immutable_assignment() {
+        let err = run("let x = 1; x = 2;").unwrap_err();
+        assert_eq!(err.message, "cannot assign twice to immutable variable `x`");
+        assert_eq!(err.labels.len(), 2);
+        assert_eq!(
+            err.labels[1].style,
+            crate::diagnostic::LabelStyle::Secondary
+        );
+        assert_eq!(err.labels[1].range, 4..5);
+        assert!(err.labels[1].message.contains("`mut x`"));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let interpreter = run("let a = [1, 2]; let b = a; push(b, 3); a[0] = len(a);").unwrap();
+        let a = interpreter.env.get("a").unwrap();
+        assert_eq!(a.to_string(), "[3, 2, 3]");
+
+        let interpreter = run("let a = [[1], \"x\"]; let x = pop(a); let y = a[0][0];").unwrap();
+        assert_eq!(interpreter.env.get("a").unwrap().to_string(), "[[1]]");
+        assert_eq!(interpreter.env.get("x").unwrap().to_string(), "x");
+        assert_eq!(interpreter.env.get("y").unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn test_print_cycles() {
+        let output = run_program(
+            "struct Node { next: [Node] }
+            def main() {
+                let a = [1];
+                push(a, a);
+                print(a);
+                let m = { \"a\": 1 };
+                insert(m, \"b\", [m]);
+                print(m);
+                let n = Node { next: [] };
+                push(n.next, n);
+                print(f\"{n}\");
+                let shared = [1];
+                print([shared, shared]);
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "[1, [...]]\n{ \"a\": 1, \"b\": [{...}] }\nNode { next: [Node { ... }] }\n[[1], [1]]\n"
+        );
+    }
+
+    #[test]
+    fn test_index_out_of_bounds() {
+        let err = run("let a = [1, 2, 3]; a[5];").unwrap_err();
+        assert_eq!(err.message, "index out of bounds");
+        assert_eq!(err.labels[0].message, "the length is 3 but the index is 5");
+
+        let err = run("let a = []; a[0 - 1] = 1;").unwrap_err();
+        assert_eq!(err.labels[0].message, "the length is 0 but the index is -1");
+    }
+
+    #[test]
+    fn test_functions() {
+        let output = run_program(
+            "def add(x: i64, y: i64): i64 {
+                return x + y;
+            }
+
+            def count(n: i64) {
+                let mut i = 0;
+                loop {
+                    i = i + 1;
+                    if (i == 2) {
+                        continue;
+                    } else if (i > n) {
+                        break;
+                    }
+                    print(i);
+                }
+            }
+
+            def main() {
+                print(add(1, 1));
+                count(3);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "2\n1\n3\n");
+
+        let err = run_program("def f(): i64 { 1 } def f(): i64 { 2 } def main() { print(f()); }")
+            .unwrap_err();
+        assert_eq!(err.message, "the name `f` is defined multiple times");
+
+        let output = run_program("def void() {} def main() { print(void()); }").unwrap();
+        assert_eq!(output, "()\n");
+        let err = run_program("def void() {} def main() { let x = 1 + void(); }").unwrap_err();
+        assert_eq!(err.labels[0].message, "expected 'int' found '()'");
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // The limit is meant for the 8 MiB stack of a main thread, which is more
+        // than a test thread has.
+        let thread = std::thread::Builder::new().stack_size(8 * 1024 * 1024);
+        thread.spawn(call_depth).unwrap().join().unwrap();
+    }
+
+    fn call_depth() {
+        let src = "def depth(n: i64): i64 { if (n == 0) { 0 } else { 1 + depth(n - 1) } }
+            def forever(n: i64): i64 { forever(n + 1) }";
+        let output = run_program(&format!("{} def main() {{ print(depth(250)); }}", src));
+        assert_eq!(output.unwrap(), "250\n");
+
+        let err = run_program(&format!("{} def main() {{ forever(0); }}", src)).unwrap_err();
+        assert_eq!(err.message, "maximum call depth exceeded");
+        assert_eq!(
+            err.labels[0].message,
+            "this call is nested more than 256 calls deep"
+        );
+    }
+
+    #[test]
+    fn test_structs() {
+        let output = run_program(
+            "struct Point { x: i64, y: i64 }
+
+            def shift(p: Point) {
+                p.x = p.x + 10;
+            }
+
+            def main() {
+                let p = Point { y: 2, x: 1 };
+                shift(p);
+                print(p);
+                print(p.x);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "Point { x: 11, y: 2 }\n11\n");
+
+        let err = run_program(
+            "struct Point { x: i64, y: i64 } def main() { let p = Point { x: 1, y: 2 }; p.z; }",
+        )
+        .unwrap_err();
+        assert_eq!(err.message, "no field `z` on struct `Point`");
+
+        let err = run_program("struct P { x: i64 } struct P { y: i64 } def main() {}").unwrap_err();
+        assert_eq!(err.message, "the name `P` is defined multiple times");
+    }
+
+    #[test]
+    fn test_methods() {
+        let err =
+            run_program("struct S {} impl S { def m() {} } impl S { def m() {} } def main() {}")
+                .unwrap_err();
+        assert_eq!(err.message, "the name `m` is defined multiple times");
+
+        let output = run_program(
+            "struct Counter { count: i64 }
+
+            impl Counter {
+                def add(n: i64): i64 {
+                    self.count = self.count + n;
+                    return self.count;
+                }
+            }
+
+            def main() {
+                let c = Counter { count: 0 };
+                c.add(2);
+                print(c.add(3));
+                let a = [1, 2];
+                a.push(\"abc\".len());
+                print(a);
+                print(a.pop() + a.len());
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "5\n[1, 2, 3]\n5\n");
+
+        let err = run_program("def main() { 1.foo(); }").unwrap_err();
+        assert_eq!(err.message, "no method named `foo` found for `int`");
+    }
+
+    #[test]
+    fn test_closures() {
+        let output = run_program(
+            "def apply(f: def(i64): i64, x: i64): i64 {
+                return f(x);
+            }
+
+            def double(x: i64): i64 {
+                return x * 2;
+            }
+
+            def counter() {
+                let mut count = 0;
+                return || {
+                    count = count + 1;
+                    return count;
+                };
+            }
+
+            def main() {
+                let n = 10;
+                print(apply(|x| x + n, 1));
+                print(apply(double, 4));
+                let next = counter();
+                next();
+                print(next());
+                let p = print;
+                p(def (a: i64, b) { return a - b; }(5, 3));
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "11\n8\n2\n2\n");
+
+        let err = run_program("def main() { let x = 1; x(); }").unwrap_err();
+        assert_eq!(err.message, "invalid type");
+    }
+
+    #[test]
+    fn test_match() {
+        let output = run_program(
+            "struct Point { x: i64, y: i64 }
+
+            def name(n: i64): string {
+                return match n { 1 => \"one\", -1 => \"minus one\", n => \"other\" };
+            }
+
+            def main() {
+                print(name(1));
+                print(name(-1));
+                print(name(5));
+                let p = Point { x: 0, y: 3 };
+                print(match p { Point { x: 1 } => 1, Point { x: 0, y } => y, _ => 0 });
+                match \"b\" {
+                    \"a\" => print(\"a\"),
+                    s => print(s),
+                }
+                let y = 10;
+                print(y);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "one\nminus one\nother\n3\nb\n10\n");
+
+        let err = run_program("def main() { let x = match 2 { 1 => 1 }; }").unwrap_err();
+        assert_eq!(err.message, "no match arm matches the value");
+    }
+
+    #[test]
+    fn test_enums() {
+        let (_, output) = run_modules(&[
+            (
+                "main.tre",
+                "import geo;
+
+                enum Shape { Circle(i64), Rect(i64, i64), Empty }
+
+                impl Shape {
+                    def area(): i64 {
+                        return match self {
+                            Shape::Circle(r) => 3 * r * r,
+                            Shape::Rect(w, h) => w * h,
+                            Shape::Empty => 0,
+                        };
+                    }
+                }
+
+                def main() {
+                    let shapes = [Shape::Circle(2), Shape::Rect(3, 4), Shape::Empty];
+                    print(shapes);
+                    print(shapes[0].area());
+                    print(shapes[1].area());
+                    print(shapes[2].area());
+                    let dir = geo::Dir::Up;
+                    print(match dir { geo::Dir::Down => 1, geo::Dir::Up => 2 });
+                    print(match Shape::Rect(1, 2) { Shape::Rect(1, h) => h, _ => 0 });
+                }",
+            ),
+            ("geo.tre", "pub enum Dir { Up, Down }"),
+        ]);
+        assert_eq!(
+            output.unwrap(),
+            "[Shape::Circle(2), Shape::Rect(3, 4), Shape::Empty]\n12\n12\n0\n2\n2\n"
+        );
+
+        let err = run_program("enum A { B(i64) } def main() { A::B(1, 2); }").unwrap_err();
+        assert_eq!(err.message, "wrong number of arguments");
+        let err = run_program("enum A { B } def main() { A::C; }").unwrap_err();
+        assert_eq!(err.message, "no variant named `C` in enum `A`");
+
+        let (_, output) = run_modules(&[
+            ("main.tre", "import geo; def main() { geo::Dir::Up; }"),
+            ("geo.tre", "enum Dir { Up }"),
+        ]);
+        let err = output.unwrap_err();
+        assert_eq!(err.message, "enum `Dir` is private");
+        assert_eq!(
+            err.notes,
+            vec!["help: consider making it public: `pub enum Dir`"]
+        );
+    }
+
+    #[test]
+    fn test_for() {
+        let output = run_program(
+            "def main() {
+                let mut sum = 0;
+                for i in 0..5 {
+                    if (i == 1) {
+                        continue;
+                    }
+                    if (i == 4) {
+                        break;
+                    }
+                    sum = sum + i;
+                }
+                print(sum);
+                let r = 1..=3;
+                print(r);
+                for i in r {
+                    print(i);
+                }
+                let xs = [1, 2];
+                for x in xs {
+                    if (x < 3) {
+                        xs.push(x + 2);
+                    }
+                }
+                print(xs);
+                for c in \"ab\" {
+                    print(c);
+                }
+                for i in 3..0 {
+                    print(i);
+                }
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "5\n1..=3\n1\n2\n3\n[1, 2, 3, 4]\na\nb\n");
+
+        let output = run_program(
+            "def find(xs: [i64], x: i64): i64 {
+                for i in 0..xs.len() {
+                    if (xs[i] == x) {
+                        return i;
+                    }
+                }
+                return -1;
+            }
+            def main() { print(find([4, 5, 6], 5)); }",
+        )
+        .unwrap();
+        assert_eq!(output, "1\n");
+
+        // Loops visit the elements the array or map had when they started.
+        let output = run_program(
+            "def main() {
+                let a = [1, 2];
+                for x in a {
+                    push(a, x);
+                    a[1] = 5;
+                    print(x);
+                }
+                print(a);
+                let m = { 1: true };
+                for k in m {
+                    insert(m, k + 1, false);
+                    print(k);
+                }
+                print(m);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "1\n2\n[1, 5, 1, 2]\n1\n{ 1: true, 2: false }\n");
+
+        let err = run_program("def main() { for x in 5 {} }").unwrap_err();
+        assert_eq!(err.message, "invalid type");
+    }
+
+    #[test]
+    fn test_imports() {
+        let (_, output) = run_modules(&[
+            (
+                "main.tre",
+                "import geo;
+                import \"util/math.tre\";
+
+                def square(x: i64): i64 {
+                    return x;
+                }
+
+                def main() {
+                    let p = geo::Point { x: 3, y: 4 };
+                    print(p.sum());
+                    print(geo::origin());
+                    print(math::square(3));
+                    print(square(3));
+                    let f = math::square;
+                    print(f(4));
+                }",
+            ),
+            (
+                "geo.tre",
+                "import \"util/math.tre\";
+                pub struct Point { x: i64, y: i64 }
+                impl Point {
+                    pub def sum(): i64 {
+                        return math::square(self.x) + math::square(self.y);
+                    }
+                }
+                pub def origin(): Point {
+                    return Point { x: 0, y: 0 };
+                }",
+            ),
+            (
+                "util/math.tre",
+                "pub def square(x: i64): i64 { return x * x; }",
+            ),
+        ]);
+        assert_eq!(output.unwrap(), "25\nPoint { x: 0, y: 0 }\n9\n3\n16\n");
+
+        // Errors inside of an imported function point to the file of the function.
+        let (files, output) = run_modules(&[
+            ("main.tre", "import a; def main() { a::fail(); }"),
+            ("a.tre", "pub def fail() { return 1 + true; }"),
+        ]);
+        let err = output.unwrap_err();
+        assert_eq!(files.name(err.labels[0].file_id), "a.tre");
+
+        let (_, output) = run_modules(&[("main.tre", "def main() { b::c(); }")]);
+        assert_eq!(output.unwrap_err().message, "cannot find module `b`");
+
+        let (_, output) = run_modules(&[
+            ("main.tre", "import a; def main() { a::f(); }"),
+            ("a.tre", "def f() {}"),
+        ]);
+        assert_eq!(output.unwrap_err().message, "function `f` is private");
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let output = run_program(
+            "def sign(x: i64): i64 {
+                if (x < 0) { -1 } else if (x == 0) { 0 } else { 1 }
+            }
+            def main() {
+                let a = if (true) { 1 } else { 2 };
+                let b = {
+                    let c = a + 1;
+                    c * 2
+                };
+                print(a + b);
+                print(sign(-5));
+                print(sign(0));
+                let double = def (x: i64) { x * 2 };
+                print(double(21));
+                print({});
+                print(if (false) { 1 });
+                print(if (true) { 1 });
+                let mut i = 0;
+                loop {
+                    i = i + 1;
+                    let done = if (i == 3) { break; } else { false };
+                }
+                print(i);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "5\n-1\n0\n42\n()\n()\n()\n3\n");
+    }
+
+    #[test]
+    fn test_integer_types() {
+        let output = run_program(
+            "def byte(x: u8): u8 { x }
+            def main() {
+                let a = byte(200);
+                print(a + 55);
+                print(300 as u8);
+                print(-1 as u32);
+                print(a as i8);
+                print(true as u64 + 1);
+                for i in byte(1)..3 { print(i + 1); }
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "255\n44\n4294967295\n-56\n2\n2\n3\n");
+
+        let output = run_program("def main() { let a: u8 = 200; print(a + 55); }").unwrap();
+        assert_eq!(output, "255\n");
+        let err = run_program("def main() { let a: u8 = 255; a + 1; }").unwrap_err();
+        assert_eq!(err.labels[0].message, "the result doesn't fit into `u8`");
+
+        let err =
+            run_program("def byte(x: u8): u8 { x } def main() { byte(200) + 56; }").unwrap_err();
+        assert_eq!(err.message, "attempt to add with overflow");
+        assert_eq!(err.labels[0].message, "the result doesn't fit into `u8`");
+
+        let err = run_program("def byte(x: u8): u8 { x } def main() { byte(0) - 1; }").unwrap_err();
+        assert_eq!(err.message, "attempt to subtract with overflow");
+
+        let err = run_program("def byte(x: u8): u8 { x } def main() { byte(1000); }").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+        assert_eq!(err.labels[0].message, "`1000` doesn't fit into `u8`");
+
+        let err = run_program("def big(x: i32): i32 { x } def main() { big(1) + (1 as i64); }")
+            .unwrap_err();
+        assert_eq!(err.message, "mismatched types");
+        assert_eq!(err.labels[0].message, "expected `i32`, found `i64`");
+
+        let err = run("9223372036854775807 + 1;").unwrap_err();
+        assert_eq!(err.labels[0].message, "the result doesn't fit into `i64`");
+        let err = run("1 / 0;").unwrap_err();
+        assert_eq!(err.message, "attempt to divide by zero");
+        let err = run("1 as string;").unwrap_err();
+        assert_eq!(err.message, "invalid cast");
+    }
+
+    #[test]
+    fn test_assignment_types() {
+        let output = run_program(
+            "struct P { x: u8 }
+            def main() {
+                let mut x: u8 = 1;
+                x = 254;
+                print(x + 1);
+                let mut (y, z): (u8, i64) = (1, 2);
+                y = 255;
+                let p = P { x: 1 };
+                p.x = 255;
+                let a = [1 as u8];
+                a[0] = 255;
+                let m = { \"a\": 1 as u8 };
+                m[\"b\"] = 255;
+                print((y, z, p, a, m));
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "255\n(255, 2, P { x: 255 }, [255], { \"a\": 1, \"b\": 255 })\n"
+        );
+
+        let err = run("let mut x: u8 = 1; x = 300;").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+        assert_eq!(err.labels[0].message, "`300` doesn't fit into `u8`");
+        let err = run("let mut x: u8 = 1; x = 5 as i64;").unwrap_err();
+        assert_eq!(err.labels[0].message, "expected `u8`, found `i64`");
+        let err = run("let mut x = 1 as u8; x = 300;").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+        let err = run("let mut (x, y): (u8, i64) = (1, 2); x = 256;").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+
+        let err = run_program("struct P { x: u8 } def main() { P { x: 1000 }; }").unwrap_err();
+        assert_eq!(err.labels[0].message, "`1000` doesn't fit into `u8`");
+        let err = run_program("struct P { x: u8 } def main() { let p = P { x: 1 }; p.x = 1000; }")
+            .unwrap_err();
+        assert_eq!(err.labels[0].message, "`1000` doesn't fit into `u8`");
+
+        let err = run("let a = [1 as u8]; a[0] = 300;").unwrap_err();
+        assert_eq!(err.labels[0].message, "`300` doesn't fit into `u8`");
+        let err = run("let m = { 1: 1 as u8 }; m[2] = 300;").unwrap_err();
+        assert_eq!(err.labels[0].message, "`300` doesn't fit into `u8`");
+    }
+
+    #[test]
+    fn test_maps() {
+        let output = run_program(
+            "def main() {
+                let m = { \"b\": 2, \"a\": 1 };
+                m[\"c\"] = 3;
+                m.insert(\"a\", 10);
+                print(m);
+                print(m[\"a\"] + m.len());
+                print(m.remove(\"b\"));
+                print(contains(m, \"b\"));
+                print(m.keys());
+                for k in m { print(k); }
+                let empty = {:};
+                insert(empty, 2, true);
+                insert(empty, 1, false);
+                print(empty);
+                print({:});
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "{ \"a\": 10, \"b\": 2, \"c\": 3 }\n13\n2\nfalse\n[\"a\", \"c\"]\na\nc\n{ 1: false, 2: true }\n{:}\n"
+        );
+
+        let err = run("let m = { \"a\": 1 }; m[\"b\"];").unwrap_err();
+        assert_eq!(err.message, "key not found");
+        assert_eq!(err.labels[0].message, "the map has no key \"b\"");
+        let err = run("let m = {:}; m.remove(1);").unwrap_err();
+        assert_eq!(err.labels[0].message, "the map has no key 1");
+        let err = run("let m = { [1]: 1 };").unwrap_err();
+        assert_eq!(
+            err.labels[0].message,
+            "expected 'int', 'string' or 'bool' found 'array'"
+        );
+    }
+
+    #[test]
+    fn test_tuples() {
+        let output = run_program(
+            "def divmod(a: i64, b: i64): (i64, i64) {
+                (a / b, a - a / b * b)
+            }
+            def bytes(): (u8, string) { (255, \"max\") }
+            def main() {
+                let (q, r) = divmod(7, 2);
+                print(q);
+                print(r);
+                let mut (a, (b, _)) = (1, (\"two\", true));
+                a = a + 1;
+                print(a);
+                print(b);
+                print(divmod(9, 4));
+                print((1,));
+                print(());
+                print((1 + 2) * 3);
+                let (max, name) = bytes();
+                print(max);
+            }",
+        )
+        .unwrap();
+        assert_eq!(output, "3\n1\n2\ntwo\n(2, 1)\n(1,)\n()\n9\n255\n");
+
+        let err = run_program("def main() { let (a, b, c) = (1, 2); }").unwrap_err();
+        assert_eq!(err.message, "mismatched types");
+        assert_eq!(
+            err.labels[0].message,
+            "expected a tuple with 3 elements, found one with 2 elements"
+        );
+        let err = run("let (a, b) = 1;").unwrap_err();
+        assert_eq!(err.labels[0].message, "expected 'tuple' found 'int'");
+        let err =
+            run_program("def pair(): (u8, u8) { (1, 256) } def main() { pair(); }").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+    }
+
+    #[test]
+    fn test_format_strings() {
+        let output = run_program(
+            "def main() {
+                let name = \"bob\";
+                let m = { \"count\": 2 };
+                print(f\"hello {name}, you have {m[\"count\"] + 1} messages\");
+                print(f\"{{{[1, 2]}}} {(1, \"a\")} {f\"{name}!\"}\");
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "hello bob, you have 3 messages\n{[1, 2]} (1, \"a\") bob!\n"
+        );
+    }
+
+    #[test]
+    fn test_consts() {
+        let output = run_program(
+            "const KB: i64 = 1024;
+            const LIMIT: i64 = 10 * KB;
+            const NAME: string = f\"limit {LIMIT}\";
+            const PAIR: (u8, bool) = (255, !false);
+            def main() {
+                print(LIMIT);
+                print(NAME);
+                print(PAIR);
+                print(AFTER);
+            }
+            const AFTER: i64 = LIMIT / 2;",
+        )
+        .unwrap();
+        assert_eq!(output, "10240\nlimit 10240\n(255, true)\n5120\n");
+
+        let err = run_program("const A: i64 = len(\"abc\"); def main() {}").unwrap_err();
+        assert_eq!(err.message, "non-constant expression in a `const` item");
+
+        let err = run_program("const A: i64 = B + 1; const B: i64 = A; def main() {}").unwrap_err();
+        assert_eq!(err.message, "cycle detected when evaluating constant `A`");
+
+        let err = run_program("const A: u8 = 200 + 100; def main() {}").unwrap_err();
+        assert_eq!(err.message, "integer out of range");
+
+        let err = run_program("const A: i64 = 1; def main() { A = 2; }").unwrap_err();
+        assert_eq!(err.message, "cannot assign to constant `A`");
+
+        let err = run_program("const A: i64 = 1; const A: i64 = 2; def main() { print(A); }")
+            .unwrap_err();
+        assert_eq!(err.message, "the name `A` is defined multiple times");
+    }
+
+    #[test]
+    fn test_operators() {
+        let output = run_program(
+            "def byte(x: u8): u8 { x }
+            def fail(): bool { print(\"evaluated\"); true }
+            def main() {
+                print(7 % 3);
+                print(-7 % 3);
+                print(2 ** 3 ** 2);
+                print(-2 ** 2);
+                print(6 & 3);
+                print(6 | 3);
+                print(6 ^ 3);
+                print(1 << 4);
+                print(-17 >> 2);
+                print(byte(15) ^ 255);
+                print(true ^ true);
+                print(true & false | true);
+                print(false && fail());
+                print(true || fail());
+                print(true && fail());
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "1\n-1\n512\n-4\n2\n7\n5\n16\n-5\n240\nfalse\ntrue\nfalse\ntrue\nevaluated\ntrue\n"
+        );
+
+        let errors = [
+            "def main() { 1 % 0; }",
+            "def main() { 2 ** -1; }",
+            "def byte(x: u8): u8 { x } def main() { byte(2) ** 8; }",
+            "def byte(x: u8): u8 { x } def main() { byte(128) << 1; }",
+            "def main() { 1 << -1; }",
+            "def main() { 1 && true; }",
+        ]
+        .iter()
+        .map(|src| run_program(src).unwrap_err().message)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "attempt to calculate the remainder with a divisor of zero",
+                "attempt to raise to a negative power",
+                "attempt to raise to a power with overflow",
+                "attempt to shift left with overflow",
+                "attempt to shift left with overflow",
+                "invalid type",
+            ]
+        );
     }
 }
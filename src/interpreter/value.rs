@@ -0,0 +1,359 @@
+use super::{Builtin, Env};
+use crate::{
+    diagnostic::FileId,
+    syntax::ast::{Block, Identifier, IntTy, Type},
+    Span,
+};
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    /// The value of blocks that don't end with an expression and of functions
+    /// that don't return anything, written as `()`.
+    Unit,
+    Int(Int),
+    String(String),
+    Bool(bool),
+    /// Arrays are reference counted, so every copy of an array value
+    /// refers to the same storage.
+    Array(Rc<RefCell<Vec<Value>>>),
+    /// `(a, b)`. The elements of tuples can't be changed, so they don't need a `RefCell`.
+    Tuple(Rc<Vec<Value>>),
+    /// Maps are shared like arrays. Their entries are sorted by key,
+    /// so they're always printed and iterated in the same order.
+    Map(Rc<RefCell<BTreeMap<Key, Value>>>),
+    /// `start..end`, or `start..=end` if the range includes `end`.
+    Range {
+        start: i128,
+        end: i128,
+        inclusive: bool,
+        /// The type of the integers in the range.
+        ty: Option<IntTy>,
+    },
+    /// Structs are shared the same way arrays are.
+    Struct(Rc<RefCell<StructValue>>),
+    /// The fields of enums can't be changed, so they don't need a `RefCell`.
+    Enum(Rc<EnumValue>),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+}
+
+/// An integer and its type, which is `None` for integer literals and lengths.
+/// Those take the type of the integer they're used with, e.g. in `x + 1` or `f(1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Int {
+    pub val: i128,
+    pub ty: Option<IntTy>,
+}
+
+impl Int {
+    pub fn new(val: i128, ty: Option<IntTy>) -> Self {
+        Self { val, ty }
+    }
+
+    /// The type whose range the integer has to fit into, which is `i64`
+    /// for integers without a type.
+    pub fn bounds(self) -> IntTy {
+        self.ty.unwrap_or(IntTy::I64)
+    }
+}
+
+/// A value that can be used as the key of a map.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Bool(bool),
+    /// Keys don't keep the type of integers, so `1` and `1 as u8` are the same key.
+    Int(i128),
+    String(String),
+}
+
+impl Key {
+    /// Returns the key for `val`, or `None` if it can't be used as a key.
+    pub fn from_value(val: &Value) -> Option<Self> {
+        match val {
+            Value::Bool(b) => Some(Key::Bool(*b)),
+            Value::Int(int) => Some(Key::Int(int.val)),
+            Value::String(s) => Some(Key::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Bool(b) => Value::Bool(*b),
+            Key::Int(val) => Value::Int(Int::new(*val, None)),
+            Key::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+/// A function that was declared using `def`, or a closure.
+#[derive(Debug)]
+pub struct Function {
+    /// The name of the function, which is `None` for closures.
+    pub name: Option<Identifier>,
+    /// Whether the function can be used by other modules.
+    pub public: bool,
+    /// The arguments and their types, which are optional for closures.
+    pub args: Vec<(Identifier, Option<Type>)>,
+    pub ret: Option<Type>,
+    pub body: Block,
+    /// The environment the function was declared in, which is how closures
+    /// capture the variables around them.
+    pub env: Env,
+    /// The module the function was declared in.
+    pub file: FileId,
+    pub span: Span,
+}
+
+/// An instance of a struct, whose fields are stored in declaration order.
+#[derive(Debug, Clone)]
+pub struct StructValue {
+    pub name: String,
+    /// The module the struct was declared in.
+    pub file: FileId,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl StructValue {
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, val)| val)
+    }
+
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.fields
+            .iter_mut()
+            .find(|(field, _)| field == name)
+            .map(|(_, val)| val)
+    }
+}
+
+/// An instance of a variant of an enum.
+#[derive(Debug, Clone)]
+pub struct EnumValue {
+    /// The name of the enum.
+    pub name: String,
+    /// The module the enum was declared in.
+    pub file: FileId,
+    pub variant: String,
+    pub fields: Vec<Value>,
+}
+
+/// Iterates over the elements of a range, array, map or string, which is how `for` loops work.
+///
+/// Arrays and maps are iterated over the elements they had when the loop started,
+/// so changing them inside the loop doesn't change which elements the loop visits.
+#[derive(Debug)]
+pub enum ValueIter {
+    Range(std::ops::Range<i128>, Option<IntTy>),
+    RangeInclusive(std::ops::RangeInclusive<i128>, Option<IntTy>),
+    Array(std::vec::IntoIter<Value>),
+    /// Strings are iterated by character, where every character is a string.
+    String(std::vec::IntoIter<char>),
+    /// Maps are iterated by key.
+    Map(std::vec::IntoIter<Key>),
+}
+
+impl Iterator for ValueIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            ValueIter::Range(range, ty) => range.next().map(|val| Value::Int(Int::new(val, *ty))),
+            ValueIter::RangeInclusive(range, ty) => {
+                range.next().map(|val| Value::Int(Int::new(val, *ty)))
+            }
+            ValueIter::Array(elems) => elems.next(),
+            ValueIter::String(chars) => chars.next().map(|c| Value::String(c.to_string())),
+            ValueIter::Map(keys) => keys.next().map(|key| key.to_value()),
+        }
+    }
+}
+
+impl Value {
+    pub fn array(elems: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(elems)))
+    }
+
+    pub fn map(entries: BTreeMap<Key, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// Returns an iterator over the elements of the value, or `None` if it isn't iterable.
+    pub fn iter(&self) -> Option<ValueIter> {
+        match self {
+            Value::Range {
+                start,
+                end,
+                inclusive: false,
+                ty,
+            } => Some(ValueIter::Range(*start..*end, *ty)),
+            Value::Range {
+                start,
+                end,
+                inclusive: true,
+                ty,
+            } => Some(ValueIter::RangeInclusive(*start..=*end, *ty)),
+            Value::Array(elems) => Some(ValueIter::Array(elems.borrow().clone().into_iter())),
+            Value::Map(entries) => Some(ValueIter::Map(
+                entries
+                    .borrow()
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            Value::String(s) => Some(ValueIter::String(s.chars().collect::<Vec<_>>().into_iter())),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of this value's type, as used in diagnostics.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Unit => "()",
+            Value::Int(Int { ty: Some(ty), .. }) => ty.name(),
+            Value::Int(Int { ty: None, .. }) => "int",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Array(_) => "array",
+            Value::Tuple(_) => "tuple",
+            Value::Map(_) => "map",
+            Value::Range { .. } => "range",
+            Value::Struct(_) => "struct",
+            Value::Enum(_) => "enum",
+            Value::Function(_) | Value::Builtin(_) => "function",
+        }
+    }
+
+    /// Formats the value the way it appears nested inside another value,
+    /// which means that strings are quoted.
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>, outer: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Value::String(x) => write!(f, "{:?}", x),
+            val => val.fmt_value(f, outer),
+        }
+    }
+
+    /// Formats the value, where `outer` are the arrays, maps and structs that are
+    /// being formatted around it. A value can contain itself, e.g. after
+    /// `push(a, a)`, which is printed as `[...]` instead of recursing forever.
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>, outer: &mut Vec<*const ()>) -> fmt::Result {
+        let ptr = match self {
+            Value::Array(elems) => Rc::as_ptr(elems) as *const (),
+            Value::Map(entries) => Rc::as_ptr(entries) as *const (),
+            Value::Struct(val) => Rc::as_ptr(val) as *const (),
+            _ => std::ptr::null(),
+        };
+        if !ptr.is_null() {
+            if outer.contains(&ptr) {
+                return match self {
+                    Value::Array(_) => write!(f, "[...]"),
+                    Value::Struct(val) => write!(f, "{} {{ ... }}", val.borrow().name),
+                    _ => write!(f, "{{...}}"),
+                };
+            }
+            outer.push(ptr);
+        }
+        let result = self.fmt_contents(f, outer);
+        if !ptr.is_null() {
+            outer.pop();
+        }
+        result
+    }
+
+    fn fmt_contents(&self, f: &mut fmt::Formatter<'_>, outer: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(x) => write!(f, "{}", x.val),
+            Value::String(x) => write!(f, "{}", x),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Array(elems) => {
+                write!(f, "[")?;
+                for (idx, elem) in elems.borrow().iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    elem.fmt_nested(f, outer)?;
+                }
+                write!(f, "]")
+            }
+            Value::Tuple(elems) => {
+                write!(f, "(")?;
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    elem.fmt_nested(f, outer)?;
+                }
+                if elems.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Map(entries) if entries.borrow().is_empty() => write!(f, "{{:}}"),
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (idx, (key, val)) in entries.borrow().iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " ")?;
+                    key.to_value().fmt_nested(f, outer)?;
+                    write!(f, ": ")?;
+                    val.fmt_nested(f, outer)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Struct(val) => {
+                let val = val.borrow();
+                write!(f, "{} {{", val.name)?;
+                for (idx, (name, field)) in val.fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}: ", name)?;
+                    field.fmt_nested(f, outer)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let op = if *inclusive { "..=" } else { ".." };
+                write!(f, "{}{}{}", start, op, end)
+            }
+            Value::Enum(val) => {
+                write!(f, "{}::{}", val.name, val.variant)?;
+                if !val.fields.is_empty() {
+                    write!(f, "(")?;
+                    for (idx, field) in val.fields.iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, ", ")?;
+                        }
+                        field.fmt_nested(f, outer)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Value::Function(function) => match &function.name {
+                Some(name) => write!(f, "<function {}>", name),
+                None => write!(f, "<closure>"),
+            },
+            Value::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_value(f, &mut vec![])
+    }
+}
@@ -2,7 +2,9 @@
 #![warn(missing_debug_implementations)]
 
 pub mod interpreter;
+pub mod loader;
 pub mod syntax;
+pub mod typeck;
 
 use codespan::{ByteIndex, FileId};
 use codespan_reporting::diagnostic::Diagnostic;
@@ -33,6 +35,7 @@ impl Span {
     }
 
     #[inline]
+    #[allow(clippy::should_implement_trait)]
     pub fn index<'s>(&self, slice: &'s str) -> &'s str {
         &slice[Range::<usize>::from(self.0)]
     }
@@ -48,15 +51,30 @@ impl Span {
     }
 }
 
-impl Into<codespan::Span> for Span {
-    fn into(self) -> codespan::Span {
-        self.0
+/// Spans are serialized as the byte offsets `{ "start": 0, "end": 3 }`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Span {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Span", 2)?;
+        state.serialize_field("start", &self.start().to_usize())?;
+        state.serialize_field("end", &self.end().to_usize())?;
+        state.end()
+    }
+}
+
+impl From<Span> for codespan::Span {
+    fn from(span: Span) -> codespan::Span {
+        span.0
     }
 }
 
-impl Into<Range<usize>> for Span {
-    fn into(self) -> Range<usize> {
-        Range::from(self.0)
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Range<usize> {
+        Range::from(span.0)
     }
 }
 
@@ -87,6 +105,11 @@ impl<T> Spanned<T> {
         &self.0
     }
 
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
     #[inline]
     pub fn span(&self) -> Span {
         self.1
@@ -108,6 +131,21 @@ where
     }
 }
 
+/// A value with its span is serialized as `{ "data": .., "span": .. }`.
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Spanned<T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Spanned", 2)?;
+        state.serialize_field("data", &self.0)?;
+        state.serialize_field("span", &self.1)?;
+        state.end()
+    }
+}
+
 impl<T> Deref for Spanned<T> {
     type Target = T;
 
@@ -132,11 +170,32 @@ pub mod diagnostic {
     pub type Diagnostic = codespan_reporting::diagnostic::Diagnostic<codespan::FileId>;
     pub type Label = codespan_reporting::diagnostic::Label<codespan::FileId>;
 
-    pub fn emit(files: &Files, diagnostic: &Diagnostic) {
+    /// The error for a name that is declared again, after it was first declared as `prev`
+    /// in the same file.
+    pub fn already_defined(
+        file: FileId,
+        name: &crate::Spanned<String>,
+        prev: &crate::Spanned<String>,
+    ) -> Diagnostic {
+        Diagnostic::error()
+            .with_message(format!(
+                "the name `{}` is defined multiple times",
+                name.data()
+            ))
+            .with_labels(vec![
+                Label::primary(file, name.span())
+                    .with_message(format!("`{}` redefined here", name.data())),
+                Label::secondary(file, prev.span())
+                    .with_message(format!("previous definition of `{}` here", prev.data())),
+            ])
+    }
+
+    /// Prints the diagnostic to stdout, which fails if stdout can't be written to.
+    pub fn emit(files: &Files, diagnostic: &Diagnostic) -> std::io::Result<()> {
         use codespan_reporting::term::{self, termcolor};
 
         let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
         let config = term::Config::default();
-        term::emit(&mut stdout, &config, files, diagnostic).expect("failed to emit diagnostics");
+        term::emit(&mut stdout, &config, files, diagnostic)
     }
 }
@@ -0,0 +1,328 @@
+//! Loads programs that are split across multiple files using `import`.
+
+use crate::{
+    diagnostic::{Diagnostic, FileId, Files, Label},
+    syntax::{
+        ast::{Item, ItemKind},
+        Parser,
+    },
+    Result, Span,
+};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A single parsed source file.
+#[derive(Debug)]
+pub struct Module {
+    pub file: FileId,
+    pub items: Vec<Item>,
+    /// The modules imported by this module, by the namespace they are available under.
+    pub imports: HashMap<String, FileId>,
+}
+
+/// All modules of a program.
+#[derive(Debug)]
+pub struct Program {
+    /// Every module comes after the modules it imports.
+    pub modules: Vec<Module>,
+    /// The file the program was started from, which contains `main`.
+    pub root: FileId,
+}
+
+impl Program {
+    /// Creates a program that consists of a single file without any imports.
+    pub fn single(file: FileId, items: Vec<Item>) -> Self {
+        let module = Module {
+            file,
+            items,
+            imports: HashMap::new(),
+        };
+        Self {
+            modules: vec![module],
+            root: file,
+        }
+    }
+}
+
+/// A module that is currently being loaded.
+#[derive(Debug)]
+struct Pending {
+    path: PathBuf,
+    file: FileId,
+    /// The import of this module that is currently being loaded.
+    import: Span,
+}
+
+type ReadFile = Box<dyn FnMut(&Path) -> io::Result<String>>;
+
+/// Loads a file and every file it imports into the same [`Files`] database.
+/// Every file is only parsed once, even if it is imported multiple times.
+pub struct Loader<'files> {
+    files: &'files mut Files,
+    read: ReadFile,
+    loaded: HashMap<PathBuf, FileId>,
+    /// The chain of imports that lead to the module that is currently loaded.
+    pending: Vec<Pending>,
+    modules: Vec<Module>,
+    /// The syntax errors of every file, which don't stop the other files from being loaded.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Debug for Loader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Loader")
+            .field("loaded", &self.loaded)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+impl<'files> Loader<'files> {
+    pub fn new(files: &'files mut Files) -> Self {
+        Self {
+            files,
+            read: Box::new(|path| std::fs::read_to_string(path)),
+            loaded: HashMap::new(),
+            pending: vec![],
+            modules: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    /// Changes how files are read, which is from the file system by default.
+    pub fn with_reader(mut self, read: impl FnMut(&Path) -> io::Result<String> + 'static) -> Self {
+        self.read = Box::new(read);
+        self
+    }
+
+    /// Loads the program that starts at the file at `path`. Returns the syntax
+    /// errors of all files, or the first error that stopped the program from
+    /// being loaded after them.
+    pub fn load(mut self, path: impl AsRef<Path>) -> std::result::Result<Program, Vec<Diagnostic>> {
+        match self.load_module(normalize(path.as_ref()), None) {
+            Ok(root) if self.diagnostics.is_empty() => Ok(Program {
+                modules: self.modules,
+                root,
+            }),
+            Ok(_) => Err(self.diagnostics),
+            Err(err) => {
+                self.diagnostics.push(err);
+                Err(self.diagnostics)
+            }
+        }
+    }
+
+    /// Loads the module at `path`, where `import` is the import that requested it.
+    fn load_module(&mut self, path: PathBuf, import: Option<(FileId, Span)>) -> Result<FileId> {
+        if let Some(file) = self.loaded.get(&path) {
+            return Ok(*file);
+        }
+        if let Some(idx) = self.pending.iter().position(|module| module.path == path) {
+            return Err(self.cycle_error(idx));
+        }
+
+        let source = (self.read)(&path).map_err(|err| {
+            let msg = format!("failed to read `{}`: {}", path.display(), err);
+            match import {
+                Some((file, span)) => {
+                    Diagnostic::error()
+                        .with_message(msg)
+                        .with_labels(vec![
+                            Label::primary(file, span).with_message("imported here")
+                        ])
+                }
+                None => Diagnostic::error().with_message(msg),
+            }
+        })?;
+        let file = self.files.add(path.display().to_string(), source);
+        let (items, diagnostics) = Parser::new(self.files, file).parse_program();
+        self.diagnostics.extend(diagnostics);
+
+        self.pending.push(Pending {
+            path: path.clone(),
+            file,
+            import: Span::default(),
+        });
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        let mut imports = HashMap::new();
+        let mut spans: HashMap<&String, Span> = HashMap::new();
+        for item in &items {
+            let (name, import) = match item.data() {
+                ItemKind::Import { name, path } => (name, path),
+                _ => continue,
+            };
+            if let Some(prev) = spans.insert(name.data(), name.span()) {
+                return Err(Diagnostic::error()
+                    .with_message(format!(
+                        "the name `{}` is imported more than once",
+                        name.data()
+                    ))
+                    .with_labels(vec![
+                        Label::primary(file, name.span()).with_message("imported again here"),
+                        Label::secondary(file, prev).with_message("first imported here"),
+                    ]));
+            }
+
+            let target = match import {
+                Some(import) => dir.join(import.data()),
+                None => dir.join(format!("{}.tre", name.data())),
+            };
+            if let Some(pending) = self.pending.last_mut() {
+                pending.import = item.span();
+            }
+            let imported = self.load_module(normalize(&target), Some((file, item.span())))?;
+            imports.insert(name.data().clone(), imported);
+        }
+        self.pending.pop();
+
+        self.loaded.insert(path, file);
+        self.modules.push(Module {
+            file,
+            items,
+            imports,
+        });
+        Ok(file)
+    }
+
+    /// Creates the error for the import cycle that starts at `self.pending[start]`
+    /// and is closed by the last pending import.
+    fn cycle_error(&self, start: usize) -> Diagnostic {
+        let cycle = &self.pending[start..];
+        let labels = cycle
+            .iter()
+            .enumerate()
+            .map(|(idx, module)| {
+                let next = cycle.get(idx + 1).unwrap_or(&cycle[0]);
+                let msg = format!(
+                    "`{}` imports `{}`",
+                    module.path.display(),
+                    next.path.display()
+                );
+                if idx + 1 == cycle.len() {
+                    Label::primary(module.file, module.import).with_message(msg)
+                } else {
+                    Label::secondary(module.file, module.import).with_message(msg)
+                }
+            })
+            .collect();
+
+        let mut names = cycle
+            .iter()
+            .map(|module| format!("`{}`", module.path.display()))
+            .collect::<Vec<_>>();
+        names.push(names[0].clone());
+        Diagnostic::error()
+            .with_message("import cycle detected")
+            .with_labels(labels)
+            .with_notes(vec![format!("cycle: {}", names.join(" -> "))])
+    }
+}
+
+/// Removes `.` and `..` from the path, so the same file always has the same path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                _ => result.push(".."),
+            },
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(sources: &[(&str, &str)]) -> (Files, std::result::Result<Program, Vec<Diagnostic>>) {
+        let sources = sources
+            .iter()
+            .map(|(path, src)| (PathBuf::from(path), src.to_string()))
+            .collect::<HashMap<_, _>>();
+        let mut files = Files::new();
+        let program = Loader::new(&mut files)
+            .with_reader(move |path| {
+                sources
+                    .get(path)
+                    .cloned()
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+            })
+            .load("main.tre");
+        (files, program)
+    }
+
+    #[test]
+    fn test_imports() {
+        let (files, program) = load(&[
+            (
+                "main.tre",
+                "import \"lib/a.tre\"; import \"./lib/b.tre\"; def main() {}",
+            ),
+            ("lib/a.tre", "import \"../lib/b.tre\"; def a() {}"),
+            ("lib/b.tre", "def b() {}"),
+        ]);
+        let program = program.unwrap();
+        let names = program
+            .modules
+            .iter()
+            .map(|module| files.name(module.file).to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["lib/b.tre", "lib/a.tre", "main.tre"]);
+        assert_eq!(program.root, program.modules[2].file);
+        assert_eq!(program.modules[2].imports["b"], program.modules[0].file);
+        assert_eq!(program.modules[1].imports["b"], program.modules[0].file);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let (_, program) = load(&[
+            ("main.tre", "import a; def main() {}"),
+            ("a.tre", "import b;"),
+            ("b.tre", "import a;"),
+        ]);
+        let err = &program.unwrap_err()[0];
+        assert_eq!(err.message, "import cycle detected");
+        assert_eq!(err.labels.len(), 2);
+        assert_eq!(err.notes, vec!["cycle: `a.tre` -> `b.tre` -> `a.tre`"]);
+
+        let (_, program) = load(&[("main.tre", "import main;")]);
+        assert_eq!(program.unwrap_err()[0].message, "import cycle detected");
+
+        let (_, program) = load(&[("main.tre", "import a;")]);
+        assert_eq!(
+            program.unwrap_err()[0].message,
+            "failed to read `a.tre`: file not found"
+        );
+
+        let (_, program) = load(&[("main.tre", "import a; import \"b/a.tre\";"), ("a.tre", "")]);
+        assert_eq!(
+            program.unwrap_err()[0].message,
+            "the name `a` is imported more than once"
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let (_, program) = load(&[
+            ("main.tre", "import a; def main() { let = 1; } def f( {}"),
+            ("a.tre", "struct A { x: }"),
+        ]);
+        let errors = program.unwrap_err();
+        let files = errors
+            .iter()
+            .map(|err| err.labels[0].file_id)
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(files[0], files[1]);
+        assert_ne!(files[0], files[2]);
+    }
+}
@@ -3,7 +3,44 @@
 
 mod repl;
 
+use tre::{
+    diagnostic::{emit, Diagnostic, Files, Severity},
+    interpreter::Interpreter,
+    loader::Loader,
+    syntax::format::format_file,
+    typeck::TypeChecker,
+};
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fmt") {
+        let check = args.iter().any(|arg| arg == "--check");
+        let paths: Vec<_> = args[1..].iter().filter(|arg| *arg != "--check").collect();
+        if paths.is_empty() {
+            eprintln!("usage: tre fmt [--check] <files>...");
+            std::process::exit(2)
+        }
+        if !format_files(&paths, check) {
+            std::process::exit(1)
+        }
+        return;
+    }
+
+    if let Some(command @ ("ast" | "tokens")) = args.first().map(String::as_str) {
+        std::process::exit(dump(command, &args[1..]))
+    }
+
+    if let Some(path) = args.first() {
+        let mut files = Files::new();
+        if let Err(diagnostics) = run_file(&mut files, path) {
+            for diagnostic in &diagnostics {
+                report(&files, diagnostic);
+            }
+            std::process::exit(1)
+        }
+        return;
+    }
+
     let mut repl = repl::Repl::new();
     match repl.run() {
         Ok(_) => {}
@@ -13,3 +50,178 @@ fn main() {
         }
     }
 }
+
+/// Prints a diagnostic, or why it couldn't be printed.
+fn report(files: &Files, diagnostic: &Diagnostic) {
+    if let Err(err) = emit(files, diagnostic) {
+        eprintln!("failed to emit diagnostics: {}", err);
+    }
+}
+
+/// Loads, checks and runs the program at `path`.
+fn run_file(files: &mut Files, path: &str) -> Result<(), Vec<Diagnostic>> {
+    let program = Loader::new(files).load(path)?;
+    // Warnings are only reported, the program still runs if there are no errors.
+    let diagnostics = TypeChecker::new(program.root).check(&program);
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity >= Severity::Error)
+    {
+        return Err(diagnostics);
+    }
+    for diagnostic in &diagnostics {
+        report(files, diagnostic);
+    }
+
+    Interpreter::new(program.root)
+        .run(&program)
+        .map_err(|err| vec![err])
+}
+
+/// Formats the files at `paths` in place, or only reports the files that aren't
+/// formatted if `check` is set. Returns `false` if any file couldn't be formatted,
+/// or isn't formatted in check mode.
+fn format_files(paths: &[&String], check: bool) -> bool {
+    let mut files = Files::new();
+    let mut ok = true;
+    for path in paths {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("failed to read {}: {}", path, err);
+                ok = false;
+                continue;
+            }
+        };
+        let file = files.add(path.as_str(), source);
+        let formatted = match format_file(&files, file) {
+            Ok(formatted) => formatted,
+            Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    report(&files, diagnostic);
+                }
+                ok = false;
+                continue;
+            }
+        };
+        if &formatted == files.source(file) {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", path);
+            ok = false;
+        } else if let Err(err) = std::fs::write(path, formatted) {
+            eprintln!("failed to write {}: {}", path, err);
+            ok = false;
+        }
+    }
+    ok
+}
+
+/// Prints the syntax tree or the tokens of the file in `args` as JSON or
+/// S-expressions, and returns the exit code.
+#[cfg(feature = "serde")]
+fn dump(command: &str, args: &[String]) -> i32 {
+    use tre::syntax::{cst, Parser};
+
+    let usage = || {
+        let trivia = if command == "tokens" {
+            " [--trivia]"
+        } else {
+            ""
+        };
+        eprintln!(
+            "usage: tre {} [--format json|sexp]{} <file>",
+            command, trivia
+        );
+        2
+    };
+    let mut format = "json";
+    let mut trivia = false;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some(value @ ("json" | "sexp")) => format = value,
+                _ => return usage(),
+            },
+            "--trivia" if command == "tokens" => trivia = true,
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => return usage(),
+        }
+    }
+    let path = match path {
+        Some(path) => path,
+        None => return usage(),
+    };
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("failed to read {}: {}", path, err);
+            return 1;
+        }
+    };
+
+    if command == "ast" {
+        let mut files = Files::new();
+        let file = files.add(path.as_str(), source);
+        let (items, diagnostics) = Parser::new(&files, file).parse_program();
+        if !diagnostics.is_empty() {
+            for diagnostic in &diagnostics {
+                report(&files, diagnostic);
+            }
+            return 1;
+        }
+        print_list(&items, format)
+    } else {
+        let tokens: Vec<_> = cst::tokens(&source)
+            .into_iter()
+            .filter(|token| trivia || !token.kind.is_trivia())
+            .collect();
+        print_list(&tokens, format)
+    }
+}
+
+/// Prints `values` as a JSON array, or as S-expressions with one element per line.
+/// Stops quietly if stdout is closed early, e.g. when it's piped into `head`.
+#[cfg(feature = "serde")]
+fn print_list<T: serde::Serialize>(values: &[T], format: &str) -> i32 {
+    use std::io::{ErrorKind, Write};
+    use tre::syntax::sexp::to_sexp;
+
+    let output = if format == "json" {
+        serde_json::to_string_pretty(values).map_err(|err| err.to_string())
+    } else {
+        values
+            .iter()
+            .map(|value| to_sexp(value).map(|sexp| sexp.pretty() + "\n"))
+            .collect::<Result<String, _>>()
+            .map_err(|err| err.to_string())
+    };
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => {
+            eprintln!("failed to serialize: {}", err);
+            return 1;
+        }
+    };
+    let mut stdout = std::io::stdout().lock();
+    match writeln!(stdout, "{}", output.trim_end()).and_then(|_| stdout.flush()) {
+        Ok(()) => 0,
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => 0,
+        Err(err) => {
+            eprintln!("failed to write the output: {}", err);
+            1
+        }
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn dump(command: &str, _args: &[String]) -> i32 {
+    eprintln!(
+        "tre {} needs tre to be built with the `serde` feature",
+        command
+    );
+    2
+}
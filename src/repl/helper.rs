@@ -53,7 +53,7 @@ impl Highlighter for ReplHelper {
         self.highlighter.highlight(line, pos)
     }
 
-    fn highlight_char(&self, line: &str, pos: usize) -> bool {
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
         false
     }
 }
@@ -61,7 +61,7 @@ impl Highlighter for ReplHelper {
 impl Validator for ReplHelper {
     fn validate(
         &self,
-        ctx: &mut validate::ValidationContext,
+        ctx: &mut validate::ValidationContext<'_>,
     ) -> rustyline::Result<validate::ValidationResult> {
         self.validator.validate(ctx)
     }
@@ -74,7 +74,7 @@ impl Validator for ReplHelper {
 pub(super) struct CommandHinter;
 
 impl Hinter for CommandHinter {
-    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+    fn hint(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
         // if pos < line.len() {
         //     return None;
         // }
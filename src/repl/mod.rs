@@ -6,7 +6,7 @@ use rustyline::{
     validate::MatchingBracketValidator, CompletionType, Config, EditMode, Editor,
 };
 use tre::{
-    diagnostic::{emit, FileId, Files},
+    diagnostic::{Diagnostic, Files},
     syntax::Parser,
     Result,
 };
@@ -68,16 +68,20 @@ impl<'s> Repl<'s> {
 
     fn process_line(&mut self, line: String) {
         self.count += 1;
-        match if line.starts_with(self.prefix) {
-            todo!()
-        } else {
-            self.execute_code(line)
-        } {
-            Ok(_) => {}
-            Err(d) => emit(&self.files, &d),
+        let result = match line.strip_prefix(self.prefix) {
+            Some(command) => self.execute_command(command),
+            None => self.execute_code(line),
+        };
+        if let Err(d) = result {
+            crate::report(&self.files, &d);
         }
     }
 
+    fn execute_command(&mut self, command: &str) -> Result<()> {
+        // There are no commands yet.
+        Err(Diagnostic::error().with_message(format!("unknown command `{}`", command.trim())))
+    }
+
     fn execute_code(&mut self, code: String) -> Result<()> {
         let file = self.files.add(format!("${}", self.count), code);
         let mut parser = Parser::new(&self.files, file);
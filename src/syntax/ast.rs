@@ -1,63 +1,324 @@
-use crate::{syntax::TokenType, Spanned};
-use lasso::Spur;
-use std::fmt;
+use crate::{
+    syntax::{printer::Printer, TokenType},
+    Span, Spanned,
+};
+use std::{convert::TryFrom, fmt};
 
-// pub type Identifier = Spanned<Spur>;
+// pub type Identifier = Spanned<lasso::Spur>;
 pub type Identifier = Spanned<String>;
-pub type Block = Vec<Stmt>;
 pub type Type = Spanned<TypeKind>;
 
+/// A name that may be qualified by the module it was imported from, like `math::Vec2`.
+///
+/// Paths also refer to enum variants, like `Shape::Circle` or `geo::Shape::Circle`.
+/// Whether `a::b` is an item of the module `a` or a variant of the enum `a` is only
+/// known once imports are resolved, so only paths with three segments have a `variant`.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum TypeKind {
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Path {
+    pub module: Option<Identifier>,
+    pub name: Identifier,
+    pub variant: Option<Identifier>,
+}
+
+impl Path {
+    pub fn span(&self) -> Span {
+        let last = self.variant.as_ref().unwrap_or(&self.name).span();
+        match &self.module {
+            Some(module) => module.span().merge(last),
+            None => last,
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(module) = &self.module {
+            write!(f, "{}::", module)?;
+        }
+        write!(f, "{}", self.name)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "::{}", variant)?;
+        }
+        Ok(())
+    }
+}
+
+/// The sized integer types, like `i32` or `u8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum IntTy {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
     U64,
+}
+
+impl IntTy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "i8" => Some(IntTy::I8),
+            "i16" => Some(IntTy::I16),
+            "i32" => Some(IntTy::I32),
+            "i64" => Some(IntTy::I64),
+            "u8" => Some(IntTy::U8),
+            "u16" => Some(IntTy::U16),
+            "u32" => Some(IntTy::U32),
+            "u64" => Some(IntTy::U64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntTy::I8 => "i8",
+            IntTy::I16 => "i16",
+            IntTy::I32 => "i32",
+            IntTy::I64 => "i64",
+            IntTy::U8 => "u8",
+            IntTy::U16 => "u16",
+            IntTy::U32 => "u32",
+            IntTy::U64 => "u64",
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntTy::I8 | IntTy::U8 => 8,
+            IntTy::I16 | IntTy::U16 => 16,
+            IntTy::I32 | IntTy::U32 => 32,
+            IntTy::I64 | IntTy::U64 => 64,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, IntTy::I8 | IntTy::I16 | IntTy::I32 | IntTy::I64)
+    }
+
+    pub fn min(self) -> i128 {
+        if self.is_signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> i128 {
+        if self.is_signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+
+    pub fn contains(self, val: i128) -> bool {
+        self.min() <= val && val <= self.max()
+    }
+
+    /// Converts `val` to this type the way `as` does, by wrapping it around
+    /// if it's out of range, e.g. `300` becomes `44` as `u8`.
+    pub fn wrap(self, val: i128) -> i128 {
+        let modulus = 1i128 << self.bits();
+        let val = val.rem_euclid(modulus);
+        if val > self.max() {
+            val - modulus
+        } else {
+            val
+        }
+    }
+}
+
+impl fmt::Display for IntTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum TypeKind {
+    /// `()`, the type of functions that don't return anything.
+    Unit,
+    Int(IntTy),
     F64,
     String,
     Bool,
+    Array(Box<Type>),
+    /// `(i64, bool)`, or `(i64,)` for a tuple with a single element.
+    Tuple(Vec<Type>),
+    /// A map from keys of the first type to values of the second, written as `{string: i64}`.
+    Map(Box<Type>, Box<Type>),
+    /// The type of a function value, written as `def(i64, bool): i64`.
+    Function {
+        args: Vec<Type>,
+        ret: Option<Box<Type>>,
+    },
+    Named(Path),
+}
+
+impl fmt::Display for TypeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeKind::Unit => write!(f, "()"),
+            TypeKind::Int(ty) => write!(f, "{}", ty),
+            TypeKind::F64 => write!(f, "f64"),
+            TypeKind::String => write!(f, "string"),
+            TypeKind::Bool => write!(f, "bool"),
+            TypeKind::Array(ty) => write!(f, "[{}]", ty),
+            TypeKind::Tuple(tys) => write_tuple(f, tys),
+            TypeKind::Map(key, val) => write!(f, "{{{}: {}}}", key, val),
+            TypeKind::Function { args, ret } => {
+                write!(f, "def(")?;
+                write_list(f, args)?;
+                write!(f, ")")?;
+                match ret {
+                    Some(ret) => write!(f, ": {}", ret),
+                    None => Ok(()),
+                }
+            }
+            TypeKind::Named(name) => write!(f, "{}", name),
+        }
+    }
 }
 
 pub type Item = Spanned<ItemKind>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ItemKind {
     Def {
         name: Identifier,
+        /// Whether the function can be used by other modules.
+        public: bool,
         args: Vec<DefArgument>,
+        ret: Option<Type>,
+        body: Block,
+    },
+    Struct {
+        name: Identifier,
+        public: bool,
+        fields: Vec<StructField>,
+    },
+    Enum {
+        name: Identifier,
+        public: bool,
+        variants: Vec<Variant>,
+    },
+    /// `const LIMIT: i64 = 10 * 1024;`, a value that is evaluated before the program runs.
+    Const {
+        name: Identifier,
+        public: bool,
+        ty: Type,
+        val: Expr,
+    },
+    /// A list of methods for the type `ty`, where every item is a `Def`.
+    Impl { ty: Identifier, methods: Vec<Item> },
+    /// Makes the items of another file available under the namespace `name`.
+    /// The file is `<name>.tre` if no `path` was given.
+    Import {
+        name: Identifier,
+        path: Option<Spanned<String>>,
     },
+    /// An item with a syntax error, which only `Parser::parse_program` produces
+    /// to keep the items around it.
+    Error,
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(f).item(self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DefArgument {
     pub name: Identifier,
     pub ty: Type,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StructField {
+    pub name: Identifier,
+    pub ty: Type,
+}
+
+/// A variant of an enum, like `Rect(i64, i64)`, whose fields have no names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<Type>,
+}
+
+/// A list of statements in curly braces, that evaluates to the expression at the end
+/// of it, if it's not followed by a semicolon, e.g. `{ let x = 1; x + 1 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub expr: Option<Box<Expr>>,
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(f).block(self, true)
+    }
+}
+
 pub type Stmt = Spanned<StmtKind>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum StmtKind {
     Let {
-        name: Identifier,
+        pattern: LetPattern,
+        mutable: bool,
+        ty: Option<Type>,
         val: Expr,
     },
-    If {
-        cond: Expr,
-        then: Block,
-        otherwise: Block,
-    },
     While {
         cond: Expr,
         block: Block,
     },
+    /// Executes the block once for every value of `iter`, which is bound to `var`.
+    For {
+        var: Identifier,
+        iter: Expr,
+        block: Block,
+    },
     Loop(Block),
+    Break,
+    Continue,
+    Return(Option<Expr>),
     Expr(Expr),
+    /// A statement with a syntax error, see `ItemKind::Error`.
+    Error,
+}
+
+impl fmt::Display for StmtKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(f).stmt(self, false)
+    }
 }
 
 pub type Expr = Spanned<ExprKind>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ExprKind {
     Literal(Literal),
+    /// `f"hello {name}"`, which evaluates the expressions in braces and
+    /// inserts their values into the string.
+    FormatString(Vec<FormatPart>),
     Variable(Identifier),
+    /// An item of an imported module, like `math::square`.
+    Path(Path),
     Binary {
         left: Box<Expr>,
         op: BinaryOperation,
@@ -68,28 +329,253 @@ pub enum ExprKind {
         expr: Box<Expr>,
     },
     Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// An anonymous function, e.g. `def (x) { return x; }` or `|x| x + 1`.
+    Closure {
+        args: Vec<ClosureArg>,
+        body: ClosureBody,
+    },
+    Array(Vec<Expr>),
+    /// `(a, b)`, or `(a,)` for a tuple with a single element. `()` is the unit value.
+    Tuple(Vec<Expr>),
+    /// A map literal, e.g. `{ "a": 1, "b": 2 }`, or `{:}` for an empty map.
+    Map(Vec<(Expr, Expr)>),
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+    },
+    StructLiteral {
+        name: Path,
+        fields: Vec<FieldInit>,
+    },
+    Field {
+        expr: Box<Expr>,
+        name: Identifier,
+    },
+    MethodCall {
+        receiver: Box<Expr>,
         name: Identifier,
         args: Vec<Expr>,
     },
+    Assign {
+        target: Box<Expr>,
+        val: Box<Expr>,
+    },
+    /// `expr as ty`, which converts an integer to another integer type.
+    Cast {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+    /// `start..end`, or `start..=end` if the range includes `end`.
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    /// Evaluates the body of the first arm whose pattern matches the value of `expr`.
+    Match {
+        expr: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    /// `if (cond) { .. } else { .. }`, where `else if` is an `else` block that
+    /// only contains the next `if`.
+    If {
+        cond: Box<Expr>,
+        then: Block,
+        otherwise: Option<Block>,
+    },
+    Block(Block),
     Grouping(Box<Expr>),
 }
 
+impl ExprKind {
+    /// Returns the value of an integer literal, which may be negated, like `-1`.
+    pub fn int_literal(&self) -> Option<i128> {
+        match self {
+            ExprKind::Literal(Literal::Int(val)) => Some(*val),
+            ExprKind::Unary {
+                op: UnaryOperation::Negate,
+                expr,
+            } => expr.data().int_literal().map(|val| -val),
+            ExprKind::Grouping(expr) => expr.data().int_literal(),
+            _ => None,
+        }
+    }
+
+    /// Whether the expression ends with a block, so it doesn't need a semicolon as a statement.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Block(_)
+        )
+    }
+}
+
+/// Prints the expression as source, with parentheses where they are needed
+/// to parse it back, e.g. `(a + b) * c`.
 impl fmt::Display for ExprKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(f).expr(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClosureArg {
+    pub name: Identifier,
+    pub ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ClosureBody {
+    /// The body of `|x| x + 1`, which evaluates to the expression.
+    Expr(Box<Expr>),
+    Block(Block),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldInit {
+    pub name: Identifier,
+    pub val: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+}
+
+impl fmt::Display for MatchArm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Printer::new(f).match_arm(self)
+    }
+}
+
+/// A piece of a format string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FormatPart {
+    /// Text that is inserted as it is, where `{{` and `}}` were replaced by single braces.
+    Literal(String),
+    /// An interpolated expression, whose span points into the format string.
+    Expr(Expr),
+}
+
+pub type LetPattern = Spanned<LetPatternKind>;
+
+/// The left side of a `let`, which can destructure tuples, e.g. `let (q, r) = divmod(7, 2);`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LetPatternKind {
+    Name(Identifier),
+    /// `_`, which ignores the value.
+    Wildcard,
+    Tuple(Vec<LetPattern>),
+}
+
+impl fmt::Display for LetPatternKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ExprKind::Literal(val) => write!(f, "{}", val),
-            ExprKind::Binary { left, op, right } => write!(f, "({} {} {})", op, left, right),
-            ExprKind::Unary { op, expr } => write!(f, "{}{}", op, expr),
-            ExprKind::Call { name: _, args: _ } => unimplemented!(),
-            ExprKind::Grouping(expr) => write!(f, "({})", expr),
-            ExprKind::Variable(var) => write!(f, "{}", var),
+            LetPatternKind::Name(name) => write!(f, "{}", name),
+            LetPatternKind::Wildcard => write!(f, "_"),
+            LetPatternKind::Tuple(patterns) => write_tuple(f, patterns),
+        }
+    }
+}
+
+pub type Pattern = Spanned<PatternKind>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PatternKind {
+    /// `_`, which matches every value.
+    Wildcard,
+    Literal(Literal),
+    /// Matches every value and binds it to the name.
+    Binding(Identifier),
+    /// `Point { x: 0, y }`, where fields that aren't listed match every value.
+    Struct {
+        name: Path,
+        fields: Vec<FieldPattern>,
+    },
+    /// `Shape::Rect(w, h)`, or `Shape::Empty` for variants without fields.
+    Variant {
+        name: Path,
+        args: Vec<Pattern>,
+    },
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternKind::Wildcard => write!(f, "_"),
+            PatternKind::Literal(literal) => write!(f, "{}", literal),
+            PatternKind::Binding(name) => write!(f, "{}", name),
+            PatternKind::Struct { name, fields } if fields.is_empty() => write!(f, "{} {{}}", name),
+            PatternKind::Struct { name, fields } => {
+                write!(f, "{} {{ ", name)?;
+                write_list(f, fields)?;
+                write!(f, " }}")
+            }
+            PatternKind::Variant { name, args } if args.is_empty() => write!(f, "{}", name),
+            PatternKind::Variant { name, args } => {
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FieldPattern {
+    pub name: Identifier,
+    pub pattern: Pattern,
+}
+
+impl fmt::Display for FieldPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pattern.data() {
+            // The shorthand `Point { x }`
+            PatternKind::Binding(name) if name.data() == self.name.data() => {
+                write!(f, "{}", name)
+            }
+            pattern => write!(f, "{}: {}", self.name, pattern),
         }
     }
 }
 
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Writes `items` as a tuple, with a trailing comma if there's only one of them.
+fn write_tuple<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    write!(f, "(")?;
+    write_list(f, items)?;
+    if items.len() == 1 {
+        write!(f, ",")?;
+    }
+    write!(f, ")")
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Literal {
-    Int(i64),
+    /// Integer literals don't have a type on their own, so they can be as large as `u64::MAX`.
+    Int(i128),
     String(String),
     Bool(bool),
 }
@@ -98,13 +584,15 @@ impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Literal::Int(x) => write!(f, "{}", x),
-            Literal::String(x) => write!(f, "{}", x),
+            // Strings keep their escapes, so they can be written as they are.
+            Literal::String(x) => write!(f, "\"{}\"", x),
             Literal::Bool(x) => write!(f, "{}", x),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum UnaryOperation {
     Not,
     Negate,
@@ -119,29 +607,43 @@ impl fmt::Display for UnaryOperation {
     }
 }
 
-impl From<TokenType> for UnaryOperation {
-    fn from(ty: TokenType) -> Self {
-        UnaryOperation::from(&ty)
+impl TryFrom<TokenType> for UnaryOperation {
+    type Error = TokenType;
+
+    fn try_from(ty: TokenType) -> Result<Self, TokenType> {
+        UnaryOperation::try_from(&ty)
     }
 }
 
-impl From<&TokenType> for UnaryOperation {
-    fn from(ty: &TokenType) -> Self {
+/// Fails with the token type if it isn't a unary operator.
+impl TryFrom<&TokenType> for UnaryOperation {
+    type Error = TokenType;
+
+    fn try_from(ty: &TokenType) -> Result<Self, TokenType> {
         match ty {
-            TokenType::Bang => UnaryOperation::Not,
-            TokenType::Minus => UnaryOperation::Negate,
-            // TODO: Convert to TryFrom impl
-            _ => panic!("failed to convert tokentype into binary operation."),
+            TokenType::Bang => Ok(UnaryOperation::Not),
+            TokenType::Minus => Ok(UnaryOperation::Negate),
+            _ => Err(*ty),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BinaryOperation {
     Plus,
     Minus,
     Mul,
     Div,
+    Rem,
+    Pow,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     NotEqual,
     EqualEqual,
     Less,
@@ -157,6 +659,15 @@ impl fmt::Display for BinaryOperation {
             BinaryOperation::Minus => write!(f, "-"),
             BinaryOperation::Mul => write!(f, "*"),
             BinaryOperation::Div => write!(f, "/"),
+            BinaryOperation::Rem => write!(f, "%"),
+            BinaryOperation::Pow => write!(f, "**"),
+            BinaryOperation::And => write!(f, "&&"),
+            BinaryOperation::Or => write!(f, "||"),
+            BinaryOperation::BitAnd => write!(f, "&"),
+            BinaryOperation::BitOr => write!(f, "|"),
+            BinaryOperation::BitXor => write!(f, "^"),
+            BinaryOperation::Shl => write!(f, "<<"),
+            BinaryOperation::Shr => write!(f, ">>"),
             BinaryOperation::NotEqual => write!(f, "!="),
             BinaryOperation::EqualEqual => write!(f, "=="),
             BinaryOperation::Less => write!(f, "<"),
@@ -167,27 +678,68 @@ impl fmt::Display for BinaryOperation {
     }
 }
 
-impl From<TokenType> for BinaryOperation {
-    fn from(ty: TokenType) -> Self {
-        BinaryOperation::from(&ty)
+impl From<&BinaryOperation> for TokenType {
+    fn from(op: &BinaryOperation) -> TokenType {
+        match op {
+            BinaryOperation::Plus => TokenType::Plus,
+            BinaryOperation::Minus => TokenType::Minus,
+            BinaryOperation::Mul => TokenType::Star,
+            BinaryOperation::Div => TokenType::Slash,
+            BinaryOperation::Rem => TokenType::Percent,
+            BinaryOperation::Pow => TokenType::StarStar,
+            BinaryOperation::And => TokenType::AmpAmp,
+            BinaryOperation::Or => TokenType::PipePipe,
+            BinaryOperation::BitAnd => TokenType::Amp,
+            BinaryOperation::BitOr => TokenType::Pipe,
+            BinaryOperation::BitXor => TokenType::Caret,
+            BinaryOperation::Shl => TokenType::LessLess,
+            BinaryOperation::Shr => TokenType::GreaterGreater,
+            BinaryOperation::NotEqual => TokenType::NotEqual,
+            BinaryOperation::EqualEqual => TokenType::EqualEqual,
+            BinaryOperation::Less => TokenType::Less,
+            BinaryOperation::LessEqual => TokenType::LessEqual,
+            BinaryOperation::Greater => TokenType::Greater,
+            BinaryOperation::GreaterEqual => TokenType::GreaterEqual,
+        }
     }
 }
 
-impl From<&TokenType> for BinaryOperation {
-    fn from(ty: &TokenType) -> Self {
-        match ty {
+impl TryFrom<TokenType> for BinaryOperation {
+    type Error = TokenType;
+
+    fn try_from(ty: TokenType) -> Result<Self, TokenType> {
+        BinaryOperation::try_from(&ty)
+    }
+}
+
+/// Fails with the token type if it isn't a binary operator. Assignments and
+/// ranges aren't binary operations, so `=`, `..` and `..=` fail too.
+impl TryFrom<&TokenType> for BinaryOperation {
+    type Error = TokenType;
+
+    fn try_from(ty: &TokenType) -> Result<Self, TokenType> {
+        let op = match ty {
             TokenType::Plus => BinaryOperation::Plus,
             TokenType::Minus => BinaryOperation::Minus,
             TokenType::Star => BinaryOperation::Mul,
             TokenType::Slash => BinaryOperation::Div,
+            TokenType::Percent => BinaryOperation::Rem,
+            TokenType::StarStar => BinaryOperation::Pow,
+            TokenType::AmpAmp => BinaryOperation::And,
+            TokenType::PipePipe => BinaryOperation::Or,
+            TokenType::Amp => BinaryOperation::BitAnd,
+            TokenType::Pipe => BinaryOperation::BitOr,
+            TokenType::Caret => BinaryOperation::BitXor,
+            TokenType::LessLess => BinaryOperation::Shl,
+            TokenType::GreaterGreater => BinaryOperation::Shr,
             TokenType::NotEqual => BinaryOperation::NotEqual,
             TokenType::EqualEqual => BinaryOperation::EqualEqual,
             TokenType::Less => BinaryOperation::Less,
             TokenType::LessEqual => BinaryOperation::LessEqual,
             TokenType::Greater => BinaryOperation::Greater,
             TokenType::GreaterEqual => BinaryOperation::GreaterEqual,
-            // TODO: Convert to TryFrom impl
-            _ => panic!("failed to convert tokentype into binary operation."),
-        }
+            _ => return Err(*ty),
+        };
+        Ok(op)
     }
 }
@@ -0,0 +1,400 @@
+//! The concrete syntax tree, which keeps every token of the source including whitespace
+//! and comments, so that it can be printed back without changing a single byte.
+//!
+//! It's built by [`Parser::lossless`](super::Parser::lossless) in the same pass that
+//! produces the [`ast`](super::ast), which is why a [`SyntaxTree`] contains both.
+
+use super::{
+    ast,
+    token::{Token, TokenType},
+    Lexer,
+};
+use crate::{diagnostic::Diagnostic, Span};
+use std::fmt;
+
+/// The kind of a node in the concrete syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The whole file.
+    Root,
+
+    // Items
+    Def,
+    Struct,
+    Enum,
+    Const,
+    Impl,
+    Import,
+
+    // Statements
+    Let,
+    While,
+    For,
+    Loop,
+    Break,
+    Continue,
+    Return,
+    ExprStmt,
+
+    // Expressions
+    Literal,
+    FormatString,
+    Variable,
+    Path,
+    Binary,
+    Unary,
+    Call,
+    Closure,
+    Array,
+    Tuple,
+    Map,
+    Index,
+    StructLiteral,
+    Field,
+    MethodCall,
+    Assign,
+    Cast,
+    Range,
+    Match,
+    If,
+    Block,
+    Grouping,
+
+    MatchArm,
+    Type,
+    Pattern,
+    LetPattern,
+    /// The tokens of an item or statement with a syntax error, which were skipped by the parser.
+    Error,
+}
+
+/// A token with its text, which is also used for whitespace and comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SyntaxToken {
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: TokenType,
+    /// The span of the whole text, which includes the quotes of strings.
+    pub span: Span,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+/// A node of the concrete syntax tree. The whitespace and comments in front of the first
+/// token of a node, and after its last token, belong to the parent of the node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    /// Returns every token of this node in source order, including trivia.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Returns the nodes that are direct children of this node.
+    pub fn child_nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Returns the span from the first to the last token of this node,
+    /// or `None` if it doesn't contain any tokens.
+    pub fn span(&self) -> Option<Span> {
+        let tokens = self.tokens();
+        let first = tokens.first()?;
+        let last = tokens.last()?;
+        Some(first.span.merge(last.span))
+    }
+}
+
+/// Writes the text of every token, which reproduces the source of the node.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens() {
+            write!(f, "{}", token.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of a lossless parse: the concrete syntax tree of a file, together with
+/// the items of the ast and the syntax errors, exactly like `Parser::parse_program`.
+#[derive(Debug)]
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    pub items: Vec<ast::Item>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Splits `source` into tokens whose texts add up to the whole source, including
+/// whitespace and comments, without parsing it.
+pub fn tokens(source: &str) -> Vec<SyntaxToken> {
+    let mut builder = Builder::default();
+    for token in Lexer::lossless(source) {
+        builder.token(&token, source);
+    }
+    builder
+        .children
+        .into_iter()
+        .filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+        .collect()
+}
+
+/// Implemented by the ast nodes the parser creates a node of the syntax tree for.
+pub(crate) trait Syntax {
+    fn node_kind(&self) -> NodeKind;
+}
+
+impl Syntax for ast::Item {
+    fn node_kind(&self) -> NodeKind {
+        match self.data() {
+            ast::ItemKind::Def { .. } => NodeKind::Def,
+            ast::ItemKind::Struct { .. } => NodeKind::Struct,
+            ast::ItemKind::Enum { .. } => NodeKind::Enum,
+            ast::ItemKind::Const { .. } => NodeKind::Const,
+            ast::ItemKind::Impl { .. } => NodeKind::Impl,
+            ast::ItemKind::Import { .. } => NodeKind::Import,
+            ast::ItemKind::Error => NodeKind::Error,
+        }
+    }
+}
+
+impl Syntax for ast::Stmt {
+    fn node_kind(&self) -> NodeKind {
+        match self.data() {
+            ast::StmtKind::Let { .. } => NodeKind::Let,
+            ast::StmtKind::While { .. } => NodeKind::While,
+            ast::StmtKind::For { .. } => NodeKind::For,
+            ast::StmtKind::Loop(_) => NodeKind::Loop,
+            ast::StmtKind::Break => NodeKind::Break,
+            ast::StmtKind::Continue => NodeKind::Continue,
+            ast::StmtKind::Return(_) => NodeKind::Return,
+            ast::StmtKind::Expr(_) => NodeKind::ExprStmt,
+            ast::StmtKind::Error => NodeKind::Error,
+        }
+    }
+}
+
+impl Syntax for ast::Expr {
+    fn node_kind(&self) -> NodeKind {
+        match self.data() {
+            ast::ExprKind::Literal(_) => NodeKind::Literal,
+            ast::ExprKind::FormatString(_) => NodeKind::FormatString,
+            ast::ExprKind::Variable(_) => NodeKind::Variable,
+            ast::ExprKind::Path(_) => NodeKind::Path,
+            ast::ExprKind::Binary { .. } => NodeKind::Binary,
+            ast::ExprKind::Unary { .. } => NodeKind::Unary,
+            ast::ExprKind::Call { .. } => NodeKind::Call,
+            ast::ExprKind::Closure { .. } => NodeKind::Closure,
+            ast::ExprKind::Array(_) => NodeKind::Array,
+            ast::ExprKind::Tuple(_) => NodeKind::Tuple,
+            ast::ExprKind::Map(_) => NodeKind::Map,
+            ast::ExprKind::Index { .. } => NodeKind::Index,
+            ast::ExprKind::StructLiteral { .. } => NodeKind::StructLiteral,
+            ast::ExprKind::Field { .. } => NodeKind::Field,
+            ast::ExprKind::MethodCall { .. } => NodeKind::MethodCall,
+            ast::ExprKind::Assign { .. } => NodeKind::Assign,
+            ast::ExprKind::Cast { .. } => NodeKind::Cast,
+            ast::ExprKind::Range { .. } => NodeKind::Range,
+            ast::ExprKind::Match { .. } => NodeKind::Match,
+            ast::ExprKind::If { .. } => NodeKind::If,
+            ast::ExprKind::Block(_) => NodeKind::Block,
+            ast::ExprKind::Grouping(_) => NodeKind::Grouping,
+        }
+    }
+}
+
+impl Syntax for ast::Type {
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Type
+    }
+}
+
+impl Syntax for ast::Pattern {
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Pattern
+    }
+}
+
+impl Syntax for ast::LetPattern {
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::LetPattern
+    }
+}
+
+impl Syntax for (ast::Block, Span) {
+    fn node_kind(&self) -> NodeKind {
+        NodeKind::Block
+    }
+}
+
+/// Collects the tokens the parser consumes, and groups them into nodes.
+///
+/// Nodes are created after the parser knows what it parsed, which is necessary for binary
+/// expressions, whose left operand is parsed before the operator. The parser remembers
+/// a checkpoint before a node, and [`Builder::finish_node`] turns every element since
+/// the checkpoint into the children of the node.
+#[derive(Debug, Default)]
+pub(crate) struct Builder {
+    children: Vec<SyntaxElement>,
+    /// The end of the last token, where the text of the next token starts.
+    offset: usize,
+}
+
+impl Builder {
+    pub fn token(&mut self, token: &Token, source: &str) {
+        let range = std::ops::Range::<usize>::from(token.span());
+        let mut end = range.end;
+        // The span of a string doesn't include its quotes.
+        if matches!(token.data(), TokenType::String | TokenType::FormatString)
+            && source[end..].starts_with('"')
+        {
+            end += 1;
+        }
+        // Everything since the last token belongs to this one, like the `f"` of a format string.
+        let start = self.offset;
+        self.offset = end;
+        self.children.push(SyntaxElement::Token(SyntaxToken {
+            kind: *token.data(),
+            span: Span::from(start..end),
+            text: source[start..end].to_string(),
+        }));
+    }
+
+    pub fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Creates a node from the elements since `checkpoint`, without the trivia at the end.
+    pub fn finish_node(&mut self, checkpoint: usize, kind: NodeKind) {
+        let end = self.children[checkpoint..]
+            .iter()
+            .rposition(|child| !is_trivia(child))
+            .map(|pos| checkpoint + pos + 1);
+        let end = match end {
+            Some(end) => end,
+            None => return,
+        };
+        let children = self.children.drain(checkpoint..end).collect();
+        self.children.insert(
+            checkpoint,
+            SyntaxElement::Node(SyntaxNode { kind, children }),
+        );
+    }
+
+    pub fn finish(self) -> SyntaxNode {
+        SyntaxNode {
+            kind: NodeKind::Root,
+            children: self.children,
+        }
+    }
+}
+
+fn is_trivia(element: &SyntaxElement) -> bool {
+    matches!(element, SyntaxElement::Token(token) if token.kind.is_trivia())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Files, syntax::Parser};
+
+    fn parse_tree(src: &str) -> SyntaxTree {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        Parser::lossless(&files, file).parse_tree()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sources = [
+            "",
+            "  \n# only a comment",
+            "# Adds one\npub def inc(x: i64): i64 {\n    return x + 1; # the result\n}\n",
+            "def main() {\n\tlet s = \"a \\\" b\";  print(f\"{s} {{x}}\");\n}",
+            "const A: [i64] = [1, 2,\n  3 ** 2 ** 2];\nstruct P { x: i64 }\nimpl P { pub def get(self) { self.x } }",
+            "def f() { match x { 1 | 2 => a, _ => { b } } let (a, _) = (1, 2); }",
+            "def f() { let x = ; ? } def g() { x = if (a) {1} else if (b) {2} else {3}; }",
+            "def f() { \"unterminated",
+        ];
+        for src in sources.iter() {
+            assert_eq!(parse_tree(src).root.to_string(), *src);
+        }
+    }
+
+    #[test]
+    fn test_same_ast() {
+        let src = "# A comment\ndef main() { let x = 1 + 2 * 3; { x } }\ndef f( { }";
+        let tree = parse_tree(src);
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let (items, diagnostics) = Parser::new(&files, file).parse_program();
+        assert_eq!(tree.items, items);
+        assert_eq!(tree.diagnostics.len(), diagnostics.len());
+    }
+
+    #[test]
+    fn test_nodes() {
+        let tree = parse_tree("# x\ndef main() {\n  a + b * c; # d\n}\n");
+        let kinds = tree.root.children.iter().map(|child| match child {
+            SyntaxElement::Node(node) => Some(node.kind),
+            SyntaxElement::Token(token) => {
+                assert!(token.kind.is_trivia());
+                None
+            }
+        });
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            vec![None, None, Some(NodeKind::Def), None]
+        );
+
+        let def = tree.root.child_nodes().next().unwrap();
+        let block = def.child_nodes().next().unwrap();
+        assert_eq!(block.kind, NodeKind::Block);
+        assert_eq!(block.to_string(), "{\n  a + b * c; # d\n}");
+        let stmt = block.child_nodes().next().unwrap();
+        assert_eq!(stmt.kind, NodeKind::ExprStmt);
+        assert_eq!(stmt.to_string(), "a + b * c;");
+        let binary = stmt.child_nodes().next().unwrap();
+        assert_eq!(binary.kind, NodeKind::Binary);
+        let operands: Vec<_> = binary.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(operands, vec![NodeKind::Variable, NodeKind::Binary]);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let src = "let s = f\"{a}\"; # done";
+        let tokens = tokens(src);
+        let texts: Vec<_> = tokens.iter().map(|token| token.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["let", " ", "s", " ", "=", " ", "f\"{a}\"", ";", " ", "# done"]
+        );
+        assert_eq!(tokens[6].kind, TokenType::FormatString);
+        assert_eq!(tokens[6].span, Span::from(8..14));
+    }
+}
@@ -0,0 +1,905 @@
+//! The source formatter behind `tre fmt`.
+//!
+//! Formatting works on the concrete syntax tree, so comments and empty lines are kept.
+//! Every node is turned into a [`Doc`], which describes where lines may be broken,
+//! and [`print`] only breaks the groups that don't fit into [`WIDTH`] columns. The
+//! output doesn't depend on the whitespace of the input, so formatting is idempotent.
+
+use super::{
+    cst::{NodeKind, SyntaxElement, SyntaxNode, SyntaxToken},
+    parser::infix_binding_power,
+    Parser, TokenType,
+};
+use crate::diagnostic::{Diagnostic, Files};
+use codespan::FileId;
+
+/// The maximum width of a line, which is only exceeded if a line can't be broken.
+pub const WIDTH: usize = 100;
+const INDENT: usize = 4;
+
+/// Formats the source of `file`, or returns its syntax errors.
+pub fn format_file(files: &Files, file: FileId) -> Result<String, Vec<Diagnostic>> {
+    let tree = Parser::lossless(files, file).parse_tree();
+    if !tree.diagnostics.is_empty() {
+        return Err(tree.diagnostics);
+    }
+    Ok(print(&root(&tree.root)))
+}
+
+/// A document, which is text together with the places where lines may be broken.
+#[derive(Debug)]
+enum Doc {
+    Text(String),
+    /// A space, or a line break if the surrounding group is broken.
+    Line,
+    /// Nothing, or a line break if the surrounding group is broken.
+    SoftLine,
+    /// A line break, which also breaks every group around it.
+    HardLine,
+    /// Text that is only printed if the surrounding group is broken, like a trailing comma.
+    IfBreak(&'static str),
+    Indent(Vec<Doc>),
+    /// Docs that are printed on a single line if they fit. The flag is whether
+    /// they contain a `HardLine`, in which case the group is always broken.
+    Group(Vec<Doc>, bool),
+}
+
+impl Doc {
+    fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    fn group(docs: Vec<Doc>) -> Self {
+        let hard = docs.iter().any(Doc::has_hard_line);
+        Doc::Group(docs, hard)
+    }
+
+    fn has_hard_line(&self) -> bool {
+        match self {
+            Doc::HardLine => true,
+            Doc::Indent(docs) => docs.iter().any(Doc::has_hard_line),
+            Doc::Group(_, hard) => *hard,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Prints `docs`, where the outermost groups are broken first until every line fits.
+fn print(docs: &[Doc]) -> String {
+    let mut out = String::new();
+    let mut col = 0;
+    // Indentation is only written in front of text, so that empty lines stay empty.
+    let mut line_indent = Some(0);
+    let mut stack: Vec<_> = docs.iter().rev().map(|doc| (0, Mode::Break, doc)).collect();
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                if let Some(indent) = line_indent.take() {
+                    out.extend(std::iter::repeat_n(' ', indent));
+                    col = indent;
+                }
+                out.push_str(text);
+                col += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                col += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.push('\n');
+                line_indent = Some(indent);
+                col = 0;
+            }
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    out.push_str(text);
+                    col += text.len();
+                }
+            }
+            Doc::Indent(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + INDENT, mode, doc)));
+            }
+            Doc::Group(docs, hard) => {
+                let col = line_indent.unwrap_or(col);
+                let flat = mode == Mode::Flat
+                    || !*hard && fits(docs, &stack, WIDTH as isize - col as isize);
+                let mode = if flat { Mode::Flat } else { Mode::Break };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+        }
+    }
+    out
+}
+
+/// Returns `true` if `docs` fit into `width` columns when they are printed flat,
+/// together with the rest of the line after them, which is on the `rest` stack.
+fn fits(docs: &[Doc], rest: &[(usize, Mode, &Doc)], mut width: isize) -> bool {
+    let mut stack: Vec<_> = docs.iter().rev().map(|doc| (Mode::Flat, doc)).collect();
+    let mut rest = rest.iter().rev();
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::IfBreak(text) if mode == Mode::Break => width -= text.len() as isize,
+            Doc::IfBreak(_) => {}
+            Doc::Indent(docs) | Doc::Group(docs, _) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)));
+            }
+        }
+    }
+    false
+}
+
+/// The separator in front of a doc, from the weakest to the strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Sep {
+    Space,
+    SoftLine,
+    Line,
+    HardLine,
+    /// An empty line.
+    Blank,
+}
+
+/// A list of docs, where a separator is only added once the doc after it is known,
+/// so that a comment can turn the separator after it into a line break.
+#[derive(Debug, Default)]
+struct Seq {
+    docs: Vec<Doc>,
+    sep: Option<Sep>,
+    /// Whether the last doc was a comment, which has to end its line.
+    after_comment: bool,
+}
+
+impl Seq {
+    fn sep(&mut self, sep: Sep) {
+        self.sep = Some(match (self.sep, sep) {
+            (Some(Sep::Space), Sep::SoftLine) | (Some(Sep::SoftLine), Sep::Space) => Sep::Line,
+            (Some(prev), sep) => prev.max(sep),
+            (None, sep) => sep,
+        });
+    }
+
+    fn push(&mut self, doc: Doc) {
+        let sep = match (self.sep.take(), std::mem::take(&mut self.after_comment)) {
+            (Some(Sep::Blank), _) => Some(Sep::Blank),
+            (_, true) => Some(Sep::HardLine),
+            (sep, false) => sep,
+        };
+        match sep {
+            None => {}
+            Some(Sep::Space) => self.docs.push(Doc::text(" ")),
+            Some(Sep::SoftLine) => self.docs.push(Doc::SoftLine),
+            Some(Sep::Line) => self.docs.push(Doc::Line),
+            Some(Sep::HardLine) => self.docs.push(Doc::HardLine),
+            Some(Sep::Blank) => self.docs.extend(vec![Doc::HardLine, Doc::HardLine]),
+        }
+        self.docs.push(doc);
+    }
+
+    /// Adds a comment, which stays at the end of the line in front of it if it was
+    /// there in the source, and is on its own line otherwise.
+    fn comment(&mut self, comment: &Comment<'_>) {
+        if comment.trailing && !self.docs.is_empty() {
+            self.docs.push(Doc::text(format!(" {}", comment.text)));
+        } else {
+            if !self.docs.is_empty() {
+                self.sep(if comment.blank {
+                    Sep::Blank
+                } else {
+                    Sep::HardLine
+                });
+            }
+            self.push(Doc::text(comment.text));
+        }
+        self.after_comment = true;
+    }
+
+    /// Returns `sep` as the separator after the last doc, which is a line break after a comment.
+    fn end(&mut self, sep: Sep) -> Doc {
+        self.sep = None;
+        match (std::mem::take(&mut self.after_comment), sep) {
+            (true, _) | (_, Sep::HardLine) | (_, Sep::Blank) => Doc::HardLine,
+            (false, Sep::Space) => Doc::text(" "),
+            (false, Sep::SoftLine) => Doc::SoftLine,
+            (false, Sep::Line) => Doc::Line,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Comment<'a> {
+    text: &'a str,
+    /// Whether the comment is on the same line as the token in front of it.
+    trailing: bool,
+    /// Whether there is an empty line in front of the comment.
+    blank: bool,
+}
+
+/// A child of a node, apart from whitespace.
+#[derive(Debug)]
+enum Part<'a> {
+    Node(&'a SyntaxNode),
+    Token(&'a SyntaxToken),
+    Comment(Comment<'a>),
+}
+
+/// A part together with the number of line breaks in front of it.
+#[derive(Debug)]
+struct Child<'a> {
+    part: Part<'a>,
+    newlines: usize,
+}
+
+impl Child<'_> {
+    fn is(&self, kind: TokenType) -> bool {
+        matches!(self.part, Part::Token(token) if token.kind == kind)
+    }
+
+    fn first_token(&self) -> Option<TokenType> {
+        match self.part {
+            Part::Node(node) => node.tokens().into_iter().map(|t| t.kind).find(is_code),
+            Part::Token(token) => Some(token.kind),
+            Part::Comment(_) => None,
+        }
+    }
+
+    fn last_token(&self) -> Option<TokenType> {
+        match self.part {
+            Part::Node(node) => node.tokens().into_iter().map(|t| t.kind).rfind(is_code),
+            Part::Token(token) => Some(token.kind),
+            Part::Comment(_) => None,
+        }
+    }
+}
+
+fn is_code(kind: &TokenType) -> bool {
+    !kind.is_trivia()
+}
+
+/// Returns the children of `node` without whitespace.
+fn children(node: &SyntaxNode) -> Vec<Child<'_>> {
+    let mut children = vec![];
+    let mut newlines = 0;
+    for child in &node.children {
+        let part = match child {
+            SyntaxElement::Node(node) => Part::Node(node),
+            SyntaxElement::Token(token) if token.kind == TokenType::Whitespace => {
+                newlines += token.text.matches('\n').count();
+                continue;
+            }
+            SyntaxElement::Token(token) if token.kind == TokenType::Comment => {
+                Part::Comment(Comment {
+                    text: token.text.trim_end(),
+                    trailing: newlines == 0,
+                    blank: newlines > 1,
+                })
+            }
+            SyntaxElement::Token(token) => Part::Token(token),
+        };
+        children.push(Child { part, newlines });
+        newlines = 0;
+    }
+    children
+}
+
+fn root(node: &SyntaxNode) -> Vec<Doc> {
+    let children = children(node);
+    let mut seq = Seq::default();
+    lines_into(&mut seq, children.iter().map(|child| vec![child]), None);
+    if !seq.docs.is_empty() {
+        seq.docs.push(Doc::HardLine);
+    }
+    seq.docs
+}
+
+/// Adds every line on its own line, and keeps one empty line between two lines if
+/// there was at least one. Every line is followed by `terminator` if there is one.
+fn lines_into<'a: 'b, 'b>(
+    seq: &mut Seq,
+    lines: impl Iterator<Item = Vec<&'b Child<'a>>>,
+    terminator: Option<&'static str>,
+) {
+    for line in lines {
+        // Comments in front of a line are lines on their own, or trail the previous one.
+        let start = line
+            .iter()
+            .position(|child| !matches!(child.part, Part::Comment(_)))
+            .unwrap_or(line.len());
+        for child in &line[..start] {
+            if let Part::Comment(comment) = &child.part {
+                seq.comment(comment);
+            }
+        }
+        let code = &line[start..];
+        if code.is_empty() {
+            continue;
+        }
+        if !seq.docs.is_empty() {
+            seq.sep(if code[0].newlines > 1 {
+                Sep::Blank
+            } else {
+                Sep::HardLine
+            });
+        }
+        inline_into(seq, code, NodeKind::Root);
+        if let Some(terminator) = terminator {
+            seq.push(Doc::text(terminator));
+        }
+    }
+}
+
+/// The doc of a node, which is `parent`'s child.
+fn node(node: &SyntaxNode, parent: NodeKind) -> Doc {
+    let children = children(node);
+    let mut seq = Seq::default();
+    match node.kind {
+        NodeKind::Block => {
+            let single_line = parent == NodeKind::Closure && is_single_expr(node);
+            block_into(&mut seq, &children, single_line);
+        }
+        NodeKind::Struct | NodeKind::Enum | NodeKind::Impl | NodeKind::Match => {
+            // Everything in front of the `{` is on the line of the keyword.
+            let open = children
+                .iter()
+                .position(|child| child.is(TokenType::LeftCurly))
+                .unwrap_or(children.len());
+            let head: Vec<_> = children[..open].iter().collect();
+            inline_into(&mut seq, &head, node.kind);
+            seq.sep(Sep::Space);
+            let body = children.get(open + 1..children.len() - 1).unwrap_or(&[]);
+            let terminator = match node.kind {
+                NodeKind::Struct | NodeKind::Enum => Some(","),
+                _ => None,
+            };
+            let lines = match terminator {
+                Some(_) => split_list(body),
+                None => body.iter().map(|child| vec![child]).collect(),
+            };
+            body_into(&mut seq, lines, terminator);
+        }
+        NodeKind::Binary => binary_into(&mut seq, node),
+        NodeKind::MatchArm => {
+            // Every arm ends with a comma, even if its body is a block, because
+            // `{ .. } -1 => ..` would be a subtraction otherwise.
+            let children: Vec<_> = children.iter().collect();
+            inline_into(&mut seq, &children, node.kind);
+            if !children
+                .last()
+                .is_some_and(|child| child.is(TokenType::Comma))
+            {
+                seq.push(Doc::text(","));
+            }
+        }
+        NodeKind::If => {
+            // The blocks are only on the line of the `if` if every one of them is a single
+            // expression and there's no `else if`, like `if (c) { a } else { b }`.
+            let single_line = parent != NodeKind::If
+                && children.iter().all(|child| match child.part {
+                    Part::Node(node) if node.kind == NodeKind::If => false,
+                    Part::Node(node) if node.kind == NodeKind::Block => is_single_expr(node),
+                    _ => true,
+                });
+            let then = children
+                .iter()
+                .position(
+                    |child| matches!(child.part, Part::Node(node) if node.kind == NodeKind::Block),
+                )
+                .unwrap_or(children.len());
+            let head: Vec<_> = children[..then].iter().collect();
+            inline_into(&mut seq, &head, node.kind);
+            for child in &children[then..] {
+                match &child.part {
+                    Part::Node(node) if node.kind == NodeKind::Block => {
+                        seq.sep(Sep::Space);
+                        block_into(&mut seq, &self::children(node), single_line);
+                    }
+                    Part::Comment(comment) => seq.comment(comment),
+                    _ => {
+                        seq.sep(Sep::Space);
+                        seq.push(child_doc(child, NodeKind::If));
+                    }
+                }
+            }
+        }
+        kind => {
+            let children: Vec<_> = children.iter().collect();
+            inline_into(&mut seq, &children, kind);
+        }
+    }
+    Doc::group(seq.docs)
+}
+
+/// Adds a chain of binary operators with the same precedence, like `a + b - c`.
+/// If it doesn't fit on a line, it's broken in front of every operator, and the
+/// operators are indented below the first operand.
+fn binary_into(seq: &mut Seq, node: &SyntaxNode) {
+    let mut ops = Seq::default();
+    let mut first = true;
+    for child in &binary_chain(node) {
+        if first && matches!(child.part, Part::Token(_)) {
+            first = false;
+            ops.after_comment = std::mem::take(&mut seq.after_comment);
+        }
+        let seq = if first { &mut *seq } else { &mut ops };
+        match &child.part {
+            Part::Comment(comment) => seq.comment(comment),
+            Part::Token(_) => {
+                seq.sep(Sep::Line);
+                seq.push(child_doc(child, NodeKind::Binary));
+            }
+            Part::Node(_) => {
+                if !first {
+                    seq.sep(Sep::Space);
+                }
+                seq.push(child_doc(child, NodeKind::Binary));
+            }
+        }
+    }
+    if ops.after_comment {
+        ops.docs.push(Doc::HardLine);
+    }
+    seq.docs.push(Doc::Indent(ops.docs));
+}
+
+/// Returns the children of a binary expression, where the left operand is replaced
+/// by its own children if it's a binary expression whose operator has the same precedence.
+fn binary_chain(node: &SyntaxNode) -> Vec<Child<'_>> {
+    let precedence = |children: &[Child<'_>]| {
+        children.iter().find_map(|child| match child.part {
+            Part::Token(token) => infix_binding_power(token.kind),
+            _ => None,
+        })
+    };
+    let mut children = children(node);
+    let left = children
+        .iter()
+        .position(|child| !matches!(child.part, Part::Comment(_)));
+    if let Some(left) = left {
+        if let Part::Node(node) = children[left].part {
+            if node.kind == NodeKind::Binary {
+                let chain = binary_chain(node);
+                if precedence(&chain) == precedence(&children) {
+                    children.splice(left..=left, chain);
+                }
+            }
+        }
+    }
+    children
+}
+
+/// Returns `true` if the block only consists of an expression, without comments.
+fn is_single_expr(block: &SyntaxNode) -> bool {
+    let mut nodes = block.children.iter().filter(|child| match child {
+        SyntaxElement::Token(token) => token.kind == TokenType::Comment,
+        SyntaxElement::Node(_) => true,
+    });
+    match (nodes.next(), nodes.next()) {
+        (Some(SyntaxElement::Node(node)), None) => !is_stmt(node.kind),
+        _ => false,
+    }
+}
+
+/// Adds a block, whose statements are on their own lines. If `single_line` is set, the
+/// block only consists of an expression, and is on a single line if it fits.
+fn block_into(seq: &mut Seq, children: &[Child<'_>], single_line: bool) {
+    let body = children.get(1..children.len() - 1).unwrap_or(&[]);
+    if single_line {
+        seq.push(Doc::group(vec![
+            Doc::text("{"),
+            Doc::Indent(vec![Doc::Line, child_doc(&body[0], NodeKind::Block)]),
+            Doc::Line,
+            Doc::text("}"),
+        ]));
+    } else {
+        body_into(seq, body.iter().map(|child| vec![child]).collect(), None);
+    }
+}
+
+fn child_doc(child: &Child<'_>, parent: NodeKind) -> Doc {
+    match child.part {
+        Part::Node(child) => node(child, parent),
+        Part::Token(token) => Doc::text(token.text.as_str()),
+        Part::Comment(ref comment) => Doc::text(comment.text),
+    }
+}
+
+/// Adds the `lines` in curly braces, where every line is on its own line.
+fn body_into(seq: &mut Seq, lines: Vec<Vec<&Child<'_>>>, terminator: Option<&'static str>) {
+    if lines.iter().all(|line| line.is_empty()) {
+        seq.push(Doc::text("{}"));
+        return;
+    }
+    let mut body = Seq::default();
+    lines_into(&mut body, lines.into_iter(), terminator);
+    body.after_comment = false;
+    seq.push(Doc::text("{"));
+    let mut indented = vec![Doc::HardLine];
+    indented.extend(body.docs);
+    seq.docs.push(Doc::Indent(indented));
+    seq.docs.push(Doc::HardLine);
+    seq.docs.push(Doc::text("}"));
+}
+
+/// Returns `true` if the node is a statement, and not an expression.
+fn is_stmt(kind: NodeKind) -> bool {
+    matches!(
+        kind,
+        NodeKind::Let
+            | NodeKind::While
+            | NodeKind::For
+            | NodeKind::Loop
+            | NodeKind::Break
+            | NodeKind::Continue
+            | NodeKind::Return
+            | NodeKind::ExprStmt
+            | NodeKind::Error
+    )
+}
+
+/// Adds the children of a `kind` node, with spaces between them where they belong.
+fn inline_into(seq: &mut Seq, children: &[&Child<'_>], kind: NodeKind) {
+    let mut prev: Option<TokenType> = None;
+    let mut i = 0;
+    while i < children.len() {
+        let child = children[i];
+        if let Part::Comment(comment) = &child.part {
+            seq.comment(comment);
+            i += 1;
+            continue;
+        }
+        if let (Some(prev), Some(next)) = (prev, child.first_token()) {
+            if space(prev, next, kind) {
+                seq.sep(Sep::Space);
+            }
+        }
+        match list_end(children, i, kind) {
+            Some(close) => {
+                list_into(seq, &children[i..=close], kind);
+                prev = children[close].last_token();
+                i = close + 1;
+            }
+            None => {
+                seq.push(child_doc(child, kind));
+                prev = child.last_token().or(prev);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Returns the index of the token that closes the list that starts at `children[start]`,
+/// if a list starts there.
+fn list_end(children: &[&Child<'_>], start: usize, kind: NodeKind) -> Option<usize> {
+    let (open, close) = match children[start].first_token()? {
+        _ if !matches!(children[start].part, Part::Token(_)) => return None,
+        TokenType::LeftParen => (TokenType::LeftParen, TokenType::RightParen),
+        TokenType::LeftBracket => (TokenType::LeftBracket, TokenType::RightBracket),
+        TokenType::LeftCurly => (TokenType::LeftCurly, TokenType::RightCurly),
+        // The arguments of a closure, like `|a, b|`.
+        TokenType::Pipe if kind == NodeKind::Closure => {
+            let end = children[start + 1..]
+                .iter()
+                .position(|child| child.is(TokenType::Pipe))?;
+            return Some(start + 1 + end);
+        }
+        _ => return None,
+    };
+    let mut depth = 0;
+    for (i, child) in children.iter().enumerate().skip(start) {
+        if child.is(open) {
+            depth += 1;
+        } else if child.is(close) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Splits a list at the commas that aren't nested in other brackets.
+fn split_list<'a, 'b>(
+    children: impl IntoIterator<Item = &'b Child<'a>>,
+) -> Vec<Vec<&'b Child<'a>>> {
+    let mut items = vec![vec![]];
+    let mut depth = 0usize;
+    for child in children {
+        match child.part {
+            Part::Token(token) => match token.kind {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftCurly => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightCurly => {
+                    depth = depth.saturating_sub(1)
+                }
+                TokenType::Comma if depth == 0 => {
+                    items.push(vec![]);
+                    continue;
+                }
+                _ => {}
+            },
+            Part::Node(_) | Part::Comment(_) => {}
+        }
+        if let Some(item) = items.last_mut() {
+            item.push(child);
+        }
+    }
+    items
+}
+
+/// Adds a comma separated list in brackets, which is put on a single line if it fits.
+/// Otherwise every item is on its own line, and followed by a comma.
+fn list_into(seq: &mut Seq, list: &[&Child<'_>], kind: NodeKind) {
+    let (open, close) = (list[0], list[list.len() - 1]);
+    let mut items = split_list(list[1..list.len() - 1].iter().copied());
+    // A comma after the last item leaves an item without code.
+    let trailing_comma = items.len() > 1
+        && items.last().is_some_and(|item| {
+            item.iter()
+                .all(|child| matches!(child.part, Part::Comment(_)))
+        });
+    let rest = match trailing_comma {
+        true => items.pop().unwrap_or_default(),
+        false => vec![],
+    };
+    let is_empty = items.len() == 1 && items[0].is_empty() && rest.is_empty();
+    if is_empty {
+        seq.push(Doc::text(format!(
+            "{}{}",
+            token_text(open),
+            token_text(close)
+        )));
+        return;
+    }
+    if items.len() == 1 && items[0].len() == 1 && items[0][0].is(TokenType::Colon) {
+        // `{:}`, the empty map
+        seq.push(Doc::text("{:}"));
+        return;
+    }
+
+    // The separator after the opening bracket, where the arguments of closures are
+    // never broken, and curly braces have spaces inside, like `Point { x: 1 }`.
+    let inner = match open.first_token() {
+        Some(TokenType::Pipe) => None,
+        Some(TokenType::LeftCurly) if kind != NodeKind::Type => Some(Sep::Line),
+        _ => Some(Sep::SoftLine),
+    };
+    // `(a,)` is a tuple with a single element, and `(a)` isn't.
+    let keep_comma = trailing_comma
+        && items.len() == 1
+        && matches!(
+            kind,
+            NodeKind::Tuple | NodeKind::Type | NodeKind::LetPattern
+        );
+
+    let mut body = Seq::default();
+    if let Some(sep) = inner {
+        body.sep(sep);
+    }
+    let count = items.len();
+    for (i, item) in items.iter().enumerate() {
+        inline_into(&mut body, item, kind);
+        if i + 1 < count {
+            body.push(Doc::text(","));
+            body.sep(if inner.is_some() {
+                Sep::Line
+            } else {
+                Sep::Space
+            });
+        } else if keep_comma {
+            body.push(Doc::text(","));
+        } else if count > 1 && inner.is_some() {
+            body.push(Doc::IfBreak(","));
+        }
+    }
+    for child in &rest {
+        if let Part::Comment(comment) = &child.part {
+            body.comment(comment);
+        }
+    }
+
+    let mut docs = vec![child_doc(open, kind)];
+    match inner {
+        Some(sep) => {
+            let end = body.end(sep);
+            docs.push(Doc::Indent(body.docs));
+            docs.push(end);
+        }
+        None => {
+            let after_comment = body.after_comment;
+            docs.extend(body.docs);
+            if after_comment {
+                docs.push(Doc::HardLine);
+            }
+        }
+    }
+    docs.push(child_doc(close, kind));
+    seq.push(Doc::group(docs));
+}
+
+fn token_text(child: &Child<'_>) -> String {
+    match child.part {
+        Part::Token(token) => token.text.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Returns `true` if there's a space between the tokens `prev` and `next` in a `kind` node.
+fn space(prev: TokenType, next: TokenType, kind: NodeKind) -> bool {
+    use TokenType::*;
+    match (prev, next) {
+        (_, Comma) | (_, Semicolon) | (_, Colon) | (_, RightParen) | (_, RightBracket) => false,
+        (_, Dot) | (_, ColonColon) | (Dot, _) | (ColonColon, _) => false,
+        (LeftParen, _) | (LeftBracket, _) | (Bang, _) => false,
+        (DotDot, _) | (DotDotEqual, _) | (_, DotDot) | (_, DotDotEqual) => false,
+        // Unary minus, like `-x` or the pattern `-1`.
+        (Minus, _) => !matches!(kind, NodeKind::Unary | NodeKind::Pattern),
+        // Calls, and the arguments of functions, variants and function types.
+        (_, LeftParen) => {
+            !matches!(kind, NodeKind::Call | NodeKind::MethodCall)
+                && prev != Identifier
+                && !(prev == Def && kind == NodeKind::Type)
+        }
+        (_, LeftBracket) => kind != NodeKind::Index,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    fn format(src: &str) -> String {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let formatted = format_file(&files, file).unwrap();
+        // Formatting the result again must not change it.
+        let again = files.add("again", formatted.clone());
+        assert_eq!(format_file(&files, again).unwrap(), formatted, "{}", src);
+        formatted
+    }
+
+    #[test]
+    fn test_format() {
+        let src = "pub def f(a:i64,b :[i64]):(i64,i64){let (q,_)=(a/2,-b[0]as u8);\n\n\n\
+                   if(q>1){q}else{a}\n}\nstruct P{x:i64,y:{string: i64}}";
+        let expected = "\
+pub def f(a: i64, b: [i64]): (i64, i64) {
+    let (q, _) = (a / 2, -b[0] as u8);
+
+    if (q > 1) { q } else { a }
+}
+struct P {
+    x: i64,
+    y: {string: i64},
+}
+";
+        assert_eq!(format(src), expected);
+        assert_eq!(format(""), "");
+        assert_eq!(
+            format("def f() { let m = {:}; let t = (1,); g(|| x, |a| { a }); }"),
+            "\
+def f() {
+    let m = {:};
+    let t = (1,);
+    g(|| x, |a| { a });
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let src = "# head\n\n\ndef f() { # open\n  a(); # call\n\n  # own line\n  [1, # one\n 2]\n  # end\n}";
+        let expected = "\
+# head
+
+def f() {
+    # open
+    a(); # call
+
+    # own line
+    [
+        1, # one
+        2,
+    ]
+    # end
+}
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let args = (0..12)
+            .map(|i| format!("argument{}", i))
+            .collect::<Vec<_>>();
+        let src = format!("def f() {{ call({}); }}", args.join(", "));
+        let expected = format!(
+            "def f() {{\n    call(\n{}    );\n}}\n",
+            args.iter()
+                .map(|arg| format!("        {},\n", arg))
+                .collect::<String>()
+        );
+        assert_eq!(format(&src), expected);
+
+        let short = "def f() {\n    call(a, b, c);\n}\n";
+        assert_eq!(format(short), short);
+        assert!(format(&src).lines().all(|line| line.len() <= WIDTH));
+
+        // Chains of binary operators are broken in front of every operator
+        // with the same precedence.
+        let words = ["a", "b", "c", "d"].map(|word| word.repeat(24));
+        let src = format!(
+            "def f() {{ let s = {} * 2 - {}; }}",
+            words.join(" + "),
+            words[0]
+        );
+        let expected = format!(
+            "def f() {{\n    let s = {}\n        + {}\n        + {}\n        + {} * 2\n        - {};\n}}\n",
+            words[0], words[1], words[2], words[3], words[0]
+        );
+        assert_eq!(format(&src), expected);
+        assert!(format(&src).lines().all(|line| line.len() <= WIDTH));
+        let short = "def f() {\n    let s = a + b * c - d;\n}\n";
+        assert_eq!(format(short), short);
+    }
+
+    #[test]
+    fn test_match_arms() {
+        let src = "def f() { match x { -1 => { a }, -2 => b, P { x: 0 } => if (c) {1} else {2} } }";
+        let expected = "\
+def f() {
+    match x {
+        -1 => {
+            a
+        },
+        -2 => b,
+        P { x: 0 } => if (c) { 1 } else { 2 },
+    }
+}
+";
+        assert_eq!(format(src), expected);
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let mut files = Files::new();
+        let file = files.add("test", "def f( {".to_string());
+        assert!(format_file(&files, file).is_err());
+    }
+
+    #[test]
+    fn test_examples() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("tre") {
+                continue;
+            }
+            let src = fs::read_to_string(&path).unwrap();
+            assert_eq!(format(&src), src, "{} is not formatted", path.display());
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+}
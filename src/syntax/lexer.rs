@@ -11,6 +11,8 @@ pub struct Lexer<'input> {
     iter: Peekable<Chars<'input>>,
     start_pos: usize,
     pos: usize,
+    /// Whether whitespace and comments are returned as tokens, see [`Lexer::lossless`].
+    trivia: bool,
 }
 
 // New method and utility methods
@@ -21,14 +23,37 @@ impl<'input> Lexer<'input> {
             input: source,
             start_pos: 0,
             pos: 0,
+            trivia: false,
+        }
+    }
+
+    /// Creates a lexer that returns whitespace and comments as `Whitespace` and
+    /// `Comment` tokens, so the source can be reproduced from the tokens.
+    pub fn lossless(source: &'input str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Creates a lexer that only lexes the part of `source` that is covered by `span`,
+    /// where the spans of the tokens are still relative to the start of `source`.
+    pub fn with_span(source: &'input str, span: Span) -> Self {
+        let range = std::ops::Range::<usize>::from(span);
+        Self {
+            iter: source[range.clone()].chars().peekable(),
+            input: &source[..range.end],
+            start_pos: range.start,
+            pos: range.start,
+            trivia: false,
         }
     }
 
     #[inline]
     fn next(&mut self) -> Option<char> {
         let c = self.iter.next();
-        if c.is_some() {
-            self.pos += 1;
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
         }
         c
     }
@@ -38,6 +63,12 @@ impl<'input> Lexer<'input> {
         self.iter.peek()
     }
 
+    /// Returns the character after the next one, without consuming anything.
+    #[inline]
+    fn peek_second(&self) -> Option<char> {
+        self.input[self.pos..].chars().nth(1)
+    }
+
     #[inline]
     fn token(&self, ty: TokenType) -> Token {
         let span = Span::from(self.start_pos..self.pos);
@@ -55,60 +86,135 @@ impl<'input> Lexer<'input> {
     pub fn next_token(&mut self) -> Option<Token> {
         self.start_pos = self.pos;
         let kind = match self.next()? {
-            '!' => match self.peek()? {
-                '=' => {
-                    assert_eq!(self.next().unwrap_or('\0'), '=');
+            '!' => match self.peek() {
+                Some('=') => {
+                    self.next();
                     TokenType::NotEqual
                 }
                 _ => TokenType::Bang,
             },
             '+' => TokenType::Plus,
             '-' => TokenType::Minus,
-            '*' => TokenType::Star,
+            '*' => match self.peek() {
+                Some('*') => {
+                    self.next();
+                    TokenType::StarStar
+                }
+                _ => TokenType::Star,
+            },
             '/' => TokenType::Slash,
-            ':' => TokenType::Colon,
+            '%' => TokenType::Percent,
+            '^' => TokenType::Caret,
+            '&' => match self.peek() {
+                Some('&') => {
+                    self.next();
+                    TokenType::AmpAmp
+                }
+                _ => TokenType::Amp,
+            },
+            ':' => match self.peek() {
+                Some(':') => {
+                    self.next();
+                    TokenType::ColonColon
+                }
+                _ => TokenType::Colon,
+            },
             ';' => TokenType::Semicolon,
             ',' => TokenType::Comma,
-            '.' => TokenType::Dot,
+            '.' => match self.peek() {
+                Some('.') => {
+                    self.next();
+                    match self.peek() {
+                        Some('=') => {
+                            self.next();
+                            TokenType::DotDotEqual
+                        }
+                        _ => TokenType::DotDot,
+                    }
+                }
+                _ => TokenType::Dot,
+            },
+            '|' => match self.peek() {
+                Some('|') => {
+                    self.next();
+                    TokenType::PipePipe
+                }
+                _ => TokenType::Pipe,
+            },
             '(' => TokenType::LeftParen,
             ')' => TokenType::RightParen,
             '{' => TokenType::LeftCurly,
             '}' => TokenType::RightCurly,
+            '[' => TokenType::LeftBracket,
+            ']' => TokenType::RightBracket,
 
-            '=' => match self.peek()? {
-                '=' => {
-                    assert_eq!(self.next().unwrap_or('\0'), '=');
+            '=' => match self.peek() {
+                Some('=') => {
+                    self.next();
                     TokenType::EqualEqual
                 }
+                Some('>') => {
+                    self.next();
+                    TokenType::FatArrow
+                }
                 _ => TokenType::Equal,
             },
-            '<' => match self.peek()? {
-                '=' => {
-                    assert_eq!(self.next().unwrap_or('\0'), '=');
+            '<' => match self.peek() {
+                Some('=') => {
+                    self.next();
                     TokenType::LessEqual
                 }
+                Some('<') => {
+                    self.next();
+                    TokenType::LessLess
+                }
                 _ => TokenType::Less,
             },
-            '>' => match self.peek()? {
-                '=' => {
-                    assert_eq!(self.next().unwrap_or('\0'), '=');
+            '>' => match self.peek() {
+                Some('=') => {
+                    self.next();
                     TokenType::GreaterEqual
                 }
+                Some('>') => {
+                    self.next();
+                    TokenType::GreaterGreater
+                }
                 _ => TokenType::Greater,
             },
 
             '"' => return self.string(),
+            'f' if self.peek() == Some(&'"') => {
+                self.next();
+                return self.format_string();
+            }
             c if is_identifier(&c) => return self.identifier(),
-            c if c.is_digit(10) => return self.number(),
-            c if c.is_whitespace() => return self.next_token(),
-            _ => return None,
+            c if c.is_ascii_digit() => return self.number(),
+            c if c.is_whitespace() => {
+                while self.peek().is_some_and(|c| c.is_whitespace()) {
+                    self.next();
+                }
+                match self.trivia {
+                    true => TokenType::Whitespace,
+                    false => return self.next_token(),
+                }
+            }
+            '#' => {
+                while self.peek().is_some_and(|c| c != &'\n') {
+                    self.next();
+                }
+                match self.trivia {
+                    true => TokenType::Comment,
+                    false => return self.next_token(),
+                }
+            }
+            _ => TokenType::Unknown,
         };
 
         Some(self.token(kind))
     }
 
     fn identifier(&mut self) -> Option<Token> {
-        while self.peek().map_or(false, |c| is_identifier(&c)) {
+        while self.peek().is_some_and(is_identifier_continue) {
             self.next();
         }
 
@@ -120,12 +226,24 @@ impl<'input> Lexer<'input> {
         token.0 = match self.current_slice() {
             "def" => TokenType::Def,
             "let" => TokenType::Let,
+            "mut" => TokenType::Mut,
             "loop" => TokenType::Loop,
             "while" => TokenType::While,
+            "for" => TokenType::For,
+            "in" => TokenType::In,
             "if" => TokenType::If,
             "else" => TokenType::Else,
             "break" => TokenType::Break,
             "continue" => TokenType::Continue,
+            "return" => TokenType::Return,
+            "struct" => TokenType::Struct,
+            "enum" => TokenType::Enum,
+            "impl" => TokenType::Impl,
+            "import" => TokenType::Import,
+            "pub" => TokenType::Pub,
+            "match" => TokenType::Match,
+            "as" => TokenType::As,
+            "const" => TokenType::Const,
             "true" | "false" => TokenType::Bool,
             _ => TokenType::Identifier,
         };
@@ -134,19 +252,31 @@ impl<'input> Lexer<'input> {
 
     fn number(&mut self) -> Option<Token> {
         // TODO: Support _ in numbers
-        while self.peek().map_or(false, |c| c.is_digit(10)) {
-            self.next();
+        self.digits();
+
+        // A dot only belongs to the number if it isn't followed by another dot
+        // or an identifier, so that `0..10` and `1.foo` aren't treated as floats.
+        let is_float = self.peek() == Some(&'.')
+            && !self
+                .peek_second()
+                .is_some_and(|c| c == '.' || is_identifier(&c));
+        if !is_float {
+            return Some(self.token(TokenType::Integer));
         }
 
-        if self.peek().map_or(false, |c| c == &'.') {
+        self.next();
+        self.digits();
+        Some(self.token(TokenType::Float))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(char::is_ascii_digit) {
             self.next();
         }
-
-        Some(self.token(TokenType::Integer))
     }
 
     fn string(&mut self) -> Option<Token> {
-        while self.peek().map_or(false, |c| c != &'"') {
+        while self.peek().is_some_and(|c| c != &'"') {
             let c = self.next().unwrap_or('\0');
             if c == '\\' && self.peek().unwrap_or(&'\0') == &'"' {
                 self.next();
@@ -163,6 +293,43 @@ impl<'input> Lexer<'input> {
         let range = Span::from(range);
         Some(range.span(TokenType::String))
     }
+
+    /// Lexes the rest of an `f"..."` string. A `"` inside of an interpolation
+    /// starts a nested string, instead of ending the format string.
+    fn format_string(&mut self) -> Option<Token> {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek().copied() {
+            if c == '"' && depth == 0 {
+                break;
+            }
+            self.next();
+            match c {
+                '\\' if self.peek() == Some(&'"') => {
+                    self.next();
+                }
+                '{' => depth += 1,
+                '}' => depth = depth.saturating_sub(1),
+                '"' => {
+                    while let Some(c) = self.next() {
+                        match c {
+                            '\\' if self.peek() == Some(&'"') => {
+                                self.next();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let has_quote = self.next().unwrap_or('\0') == '"';
+        // The content starts after the `f"`.
+        let range = (self.start_pos + 2)..(self.pos - (has_quote as usize));
+        let range = Span::from(range);
+        Some(range.span(TokenType::FormatString))
+    }
 }
 
 impl<'input> IntoIterator for Lexer<'input> {
@@ -189,13 +356,25 @@ impl<'lexer> Iterator for TokenStream<'lexer> {
 
 impl<'lexer> FusedIterator for TokenStream<'lexer> {}
 
-fn is_identifier(c: &char) -> bool {
-    match c {
-        'A'..='Z' | 'a'..='z' | '_' => true,
+/// Returns `true` if `name` can be used as an identifier, which means it isn't a keyword.
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut tokens = Lexer::new(name).into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(token), None) => {
+            token.data() == &TokenType::Identifier && token.span_ref().index(name) == name
+        }
         _ => false,
     }
 }
 
+fn is_identifier(c: &char) -> bool {
+    matches!(c, 'A'..='Z' | 'a'..='z' | '_')
+}
+
+fn is_identifier_continue(c: &char) -> bool {
+    is_identifier(c) || c.is_ascii_digit()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,20 +430,22 @@ mod tests {
 
     #[test]
     fn test_paren() {
-        let s = "(){}";
+        let s = "(){}[]";
         let tokens = lex_input(s);
         let expected = vec![
             token!(LeftParen, s, "("),
             token!(RightParen, s, ")"),
             token!(LeftCurly, s, "{"),
             token!(RightCurly, s, "}"),
+            token!(LeftBracket, s, "["),
+            token!(RightBracket, s, "]"),
         ];
         assert_eq!(expected, tokens);
     }
 
     #[test]
     fn test_some_tokens() {
-        let s = "!! ++ = !=  * * :: ., == != < <= >= > / - - ;;; # - / != --- ;;;";
+        let s = "!! ++ = !=  * * : :: ., .. ..= == != < <= >= > / - - ;;; # - / != --- ;;;";
         let tokens: Vec<_> = lex_input(s).into_iter().map(|t| t.into_inner()).collect();
         let expected = vec![
             TokenType::Bang,
@@ -276,9 +457,11 @@ mod tests {
             TokenType::Star,
             TokenType::Star,
             TokenType::Colon,
-            TokenType::Colon,
+            TokenType::ColonColon,
             TokenType::Dot,
             TokenType::Comma,
+            TokenType::DotDot,
+            TokenType::DotDotEqual,
             TokenType::EqualEqual,
             TokenType::NotEqual,
             TokenType::Less,
@@ -312,8 +495,72 @@ mod tests {
         assert_eq!(expected, tokens);
     }
 
+    #[test]
+    fn test_format_strings() {
+        let s = r#" f"a {b} {{c}}" f"{m["}"]} \" {x}" fine "#;
+        let tokens: Vec<_> = lex_input(s)
+            .into_iter()
+            .map(|t| (t.0, t.span().index(s)))
+            .collect();
+
+        let expected = vec![
+            (TokenType::FormatString, r#"a {b} {{c}}"#),
+            (TokenType::FormatString, r#"{m["}"]} \" {x}"#),
+            (TokenType::Identifier, "fine"),
+        ];
+        assert_eq!(expected, tokens);
+    }
+
     fn lex_input(input: &'_ str) -> Vec<Token> {
         let lexer = Lexer::new(input).into_iter();
         lexer.collect()
     }
+
+    #[test]
+    fn test_operators() {
+        let s = "** * % ^ & && | || << < >> >";
+        let tokens: Vec<_> = lex_input(s).into_iter().map(|t| t.into_inner()).collect();
+        let expected = vec![
+            TokenType::StarStar,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::Caret,
+            TokenType::Amp,
+            TokenType::AmpAmp,
+            TokenType::Pipe,
+            TokenType::PipePipe,
+            TokenType::LessLess,
+            TokenType::Less,
+            TokenType::GreaterGreater,
+            TokenType::Greater,
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_trivia() {
+        let s = "a  # comment\n\tb ?";
+        let tokens: Vec<_> = Lexer::lossless(s)
+            .into_iter()
+            .map(|t| (t.0, t.span().index(s)))
+            .collect();
+        let expected = vec![
+            (TokenType::Identifier, "a"),
+            (TokenType::Whitespace, "  "),
+            (TokenType::Comment, "# comment"),
+            (TokenType::Whitespace, "\n\t"),
+            (TokenType::Identifier, "b"),
+            (TokenType::Whitespace, " "),
+            (TokenType::Unknown, "?"),
+        ];
+        assert_eq!(tokens, expected);
+
+        let tokens: Vec<_> = lex_input(s).into_iter().map(|t| t.into_inner()).collect();
+        let expected = vec![
+            TokenType::Identifier,
+            TokenType::Identifier,
+            TokenType::Unknown,
+        ];
+        assert_eq!(tokens, expected);
+    }
 }
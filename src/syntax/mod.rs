@@ -1,6 +1,11 @@
 pub mod ast;
+pub mod cst;
+pub mod format;
 pub mod lexer;
 pub mod parser;
+mod printer;
+#[cfg(feature = "serde")]
+pub mod sexp;
 pub mod token;
 pub mod visit;
 
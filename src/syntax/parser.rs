@@ -1,39 +1,16 @@
 use super::{
     ast,
-    lexer::{Lexer, TokenStream},
+    cst::{self, Syntax},
+    lexer::{self, Lexer, TokenStream},
     token::{Token, TokenType},
 };
 use crate::{
     diagnostic::{Diagnostic, Files, Label},
-    Result, Span, Spanned,
+    Result, Span,
 };
 
 use codespan::FileId;
-use std::iter::Peekable;
-
-#[macro_export]
-macro_rules! binary_op {
-    ($name:ident, $next:ident, $($ty:path),*) => {
-        fn $name(&mut self) -> Result<ast::Expr> {
-            let mut expr = self.$next()?;
-
-            while self.next_one_of([
-                $($ty),*
-            ]) {
-                let op = self.next().unwrap().data().into();
-                let right = self.$next()?;
-                let span = expr.span_ref().merge(right.span());
-                expr = span.span(ast::ExprKind::Binary {
-                    left: Box::new(expr),
-                    op,
-                    right: Box::new(right),
-                })
-            }
-
-            Ok(expr)
-        }
-    };
-}
+use std::{convert::TryFrom, iter::Peekable};
 
 #[derive(Debug)]
 pub enum SyntaxError {
@@ -46,7 +23,19 @@ pub enum SyntaxError {
         found: TokenType,
     },
     ExpectedExpr,
+    InvalidAssignTarget(Span),
+    /// A token that was parsed as an operator, but isn't one.
+    InvalidOperator(TokenType),
     InvalidInteger(lexical::Error),
+    InvalidModuleName(Span),
+    /// An expression, type or pattern that is nested deeper than [`MAX_NESTING`].
+    TooDeep(Span),
+    /// An operator that makes an expression deeper than [`MAX_DEPTH`].
+    TooLong(Span),
+    /// A `{` in a format string that isn't closed.
+    UnclosedInterpolation(Span),
+    /// A `}` in a format string that doesn't close an interpolation.
+    UnmatchedBrace(Span),
     UnexpectedEof,
 }
 
@@ -56,6 +45,112 @@ pub struct Parser<'input> {
     files: &'input Files,
     tokens: Peekable<TokenStream<'input>>,
     span: Span,
+    /// Whether `Name {` starts a struct literal, which is not the case in front of a block.
+    struct_literals: bool,
+    /// Whether syntax errors are collected into `diagnostics` instead of being returned,
+    /// see [`Parser::parse_program`].
+    recover: bool,
+    diagnostics: Vec<Diagnostic>,
+    /// Whether an error was reported at the end of the input, after which every
+    /// error is caused by the same unclosed string or delimiter.
+    eof_reported: bool,
+    /// The number of `{` that were consumed and aren't closed yet.
+    depth: usize,
+    /// How many expressions, types and patterns are being parsed inside each other.
+    nesting: usize,
+    /// The depth of the deepest node that was parsed inside the innermost
+    /// [`Parser::nested`] so far, counting from the root of the syntax tree.
+    deepest: usize,
+    /// The span of the last consumed token.
+    last: Span,
+    /// The syntax tree that is built in lossless mode, see [`Parser::lossless`].
+    cst: Option<cst::Builder>,
+}
+
+/// The binding power of the unary operators, which bind tighter than every
+/// binary operator apart from `**`, so `-x ** 2` is `-(x ** 2)`.
+pub(crate) fn prefix_binding_power(ty: TokenType) -> Option<u8> {
+    match ty {
+        TokenType::Bang | TokenType::Minus => Some(27),
+        _ => None,
+    }
+}
+
+/// The left and right binding power of the binary operators, from the loosest
+/// to the tightest. Operators are left associative if their left binding power
+/// is lower than the right one, and right associative otherwise.
+pub(crate) fn infix_binding_power(ty: TokenType) -> Option<(u8, u8)> {
+    let bp = match ty {
+        TokenType::Equal => (2, 1),
+        TokenType::DotDot | TokenType::DotDotEqual => (3, 4),
+        TokenType::PipePipe => (5, 6),
+        TokenType::AmpAmp => (7, 8),
+        TokenType::EqualEqual | TokenType::NotEqual => (9, 10),
+        TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual => {
+            (11, 12)
+        }
+        TokenType::Pipe => (13, 14),
+        TokenType::Caret => (15, 16),
+        TokenType::Amp => (17, 18),
+        TokenType::LessLess | TokenType::GreaterGreater => (19, 20),
+        TokenType::Plus | TokenType::Minus => (21, 22),
+        TokenType::Star | TokenType::Slash | TokenType::Percent => (23, 24),
+        TokenType::StarStar => (30, 29),
+        _ => return None,
+    };
+    Some(bp)
+}
+
+/// The binding power of the postfix operators. `as` binds looser than unary
+/// operators, so `-x as u8` is `(-x) as u8`.
+pub(crate) fn postfix_binding_power(ty: TokenType) -> Option<u8> {
+    match ty {
+        TokenType::As => Some(25),
+        TokenType::LeftParen | TokenType::LeftBracket | TokenType::Dot => Some(31),
+        _ => None,
+    }
+}
+
+/// How deep expressions, types and patterns can be nested, e.g. in `((((1))))` or
+/// `- - - 1`. The parser recurses for every level, so this keeps it from overflowing
+/// the stack, which a debug build would do at about 500 levels on an 8 MiB stack.
+pub const MAX_NESTING: usize = 128;
+
+/// How deep the syntax tree of an expression can be. Unlike [`MAX_NESTING`], this
+/// also counts the operators of a chain like `1 + 2 + 3`, where every operator wraps
+/// the expression before it. The parser doesn't recurse for chains, but every later
+/// pass walks the syntax tree recursively, which takes a lot less stack per level.
+pub const MAX_DEPTH: usize = 1024;
+
+/// The tokens that can start an expression, apart from unary operators.
+const EXPR_START: [TokenType; 13] = [
+    TokenType::Identifier,
+    TokenType::LeftParen,
+    TokenType::LeftBracket,
+    TokenType::Def,
+    TokenType::Pipe,
+    TokenType::PipePipe,
+    TokenType::Match,
+    TokenType::If,
+    TokenType::LeftCurly,
+    TokenType::Integer,
+    TokenType::String,
+    TokenType::FormatString,
+    TokenType::Bool,
+];
+
+/// A part of a block, which is either a statement or the expression at its end.
+#[derive(Debug)]
+enum BlockPart {
+    Stmt(ast::Stmt),
+    Expr(ast::Expr),
+}
+
+/// Where parsing continues after a syntax error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    Stmt,
+    Item,
 }
 
 impl<'input> Parser<'input> {
@@ -66,57 +161,252 @@ impl<'input> Parser<'input> {
             tokens,
             span: Span::default(),
             files,
+            struct_literals: true,
+            recover: false,
+            eof_reported: false,
+            diagnostics: vec![],
+            depth: 0,
+            nesting: 0,
+            deepest: 0,
+            last: Span::default(),
+            cst: None,
+        }
+    }
+
+    /// Creates a parser that keeps whitespace and comments, and builds a concrete
+    /// syntax tree next to the ast, see [`Parser::parse_tree`].
+    pub fn lossless(files: &'input Files, file_id: FileId) -> Self {
+        let tokens = Lexer::lossless(files.source(file_id))
+            .into_iter()
+            .peekable();
+        Self {
+            tokens,
+            cst: Some(cst::Builder::default()),
+            ..Self::new(files, file_id)
+        }
+    }
+
+    /// Creates a parser for the part of the file that is covered by `span`,
+    /// which is how the interpolations of format strings are parsed.
+    fn with_span(files: &'input Files, file_id: FileId, span: Span) -> Self {
+        let tokens = Lexer::with_span(files.source(file_id), span)
+            .into_iter()
+            .peekable();
+        Self {
+            file: file_id,
+            tokens,
+            span: Span::new(span.start(), span.start()),
+            files,
+            struct_literals: true,
+            recover: false,
+            eof_reported: false,
+            diagnostics: vec![],
+            depth: 0,
+            nesting: 0,
+            deepest: 0,
+            last: Span::new(span.start(), span.start()),
+            cst: None,
         }
     }
 
+    /// Returns `true` if there are no tokens left.
+    pub fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
     fn next(&mut self) -> Option<Token> {
+        self.skip_trivia();
         let token = self.tokens.next();
+        if let (Some(token), Some(cst)) = (&token, &mut self.cst) {
+            cst.token(token, self.files.source(self.file));
+        }
+        if let Some(token) = &token {
+            match token.data() {
+                TokenType::LeftCurly => self.depth += 1,
+                TokenType::RightCurly => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+            self.last = token.span();
+        }
         self.span = token.as_ref().map_or(self.span, |token| token.span());
         token
     }
 
     fn peek(&mut self) -> Option<&Token> {
+        self.skip_trivia();
         let token = self.tokens.peek();
         self.span = token.map_or(self.span, |token| token.span());
         token
     }
 
+    /// Moves the whitespace and comments in front of the next token into the syntax tree.
+    fn skip_trivia(&mut self) {
+        while let Some(token) = self.tokens.next_if(|token| token.data().is_trivia()) {
+            if let Some(cst) = &mut self.cst {
+                cst.token(&token, self.files.source(self.file));
+            }
+        }
+    }
+
+    /// Returns the position in the syntax tree in front of the next token,
+    /// where a node can be started with [`Parser::finish_node`].
+    fn checkpoint(&mut self) -> usize {
+        self.skip_trivia();
+        self.cst.as_ref().map_or(0, cst::Builder::checkpoint)
+    }
+
+    /// Groups the tokens that were consumed since `checkpoint` into a node of the syntax tree.
+    fn finish_node(&mut self, checkpoint: usize, kind: cst::NodeKind) {
+        if let Some(cst) = &mut self.cst {
+            cst.finish_node(checkpoint, kind);
+        }
+    }
+
+    /// Parses a node with `f`, and creates a node of the syntax tree for it.
+    fn node<T: Syntax>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let checkpoint = self.checkpoint();
+        let node = f(self)?;
+        self.finish_node(checkpoint, node.node_kind());
+        Ok(node)
+    }
+
+    /// Parses a part of the input that can contain itself with `f`, which is an
+    /// error when it's nested deeper than [`MAX_NESTING`].
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let (nesting, deepest) = (self.nesting, self.deepest);
+        let result = self.enter().and_then(|_| {
+            self.deepest = self.nesting;
+            f(self)
+        });
+        self.nesting = nesting;
+        self.deepest = self.deepest.max(deepest);
+        result
+    }
+
+    /// Wraps everything that was parsed inside the innermost [`Parser::nested`]
+    /// in another node, which moves all of it one level deeper, see [`MAX_DEPTH`].
+    fn wrap(&mut self) -> Result<()> {
+        if self.deepest >= MAX_DEPTH {
+            let span = self.next_span();
+            return Err(self.make_diagnostic(SyntaxError::TooLong(span)));
+        }
+        self.deepest += 1;
+        Ok(())
+    }
+
+    /// Goes one level deeper into the syntax tree, see [`Parser::nested`].
+    fn enter(&mut self) -> Result<()> {
+        if self.nesting == MAX_NESTING {
+            let span = self.next_span();
+            return Err(self.make_diagnostic(SyntaxError::TooDeep(span)));
+        }
+        self.nesting += 1;
+        Ok(())
+    }
+
+    /// Consumes the next token, which is an error at the end of the input.
+    fn next_token(&mut self) -> Result<Token> {
+        match self.next() {
+            Some(token) => Ok(token),
+            None => Err(self.make_diagnostic(SyntaxError::UnexpectedEof)),
+        }
+    }
+
     fn eat(&mut self, ty: TokenType) -> Result<Token> {
-        let token = self.peek().cloned();
-        match token {
-            Some(ref token) if token.data() == &ty => Ok(self.next().unwrap()),
-            Some(ref token) => Err(self.make_diagnostic(SyntaxError::Expected {
+        match self.peek().map(|token| *token.data()) {
+            Some(found) if found != ty => Err(self.make_diagnostic(SyntaxError::Expected {
                 expected: ty,
-                found: *token.clone().data(),
+                found,
             })),
-            None => Err(self.make_diagnostic(SyntaxError::UnexpectedEof)),
+            _ => self.next_token(),
+        }
+    }
+
+    /// Consumes the next token if it has the given type.
+    fn eat_if(&mut self, ty: TokenType) -> Option<Token> {
+        if self.next_is(ty) {
+            self.next()
+        } else {
+            None
         }
     }
 
+    /// Runs `f` while struct literals are allowed or forbidden.
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let prev = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = f(self);
+        self.struct_literals = prev;
+        result
+    }
+
     fn next_is(&mut self, ty: TokenType) -> bool {
-        self.peek().map_or(false, |t| t.data() == &ty)
+        self.peek().is_some_and(|t| t.data() == &ty)
     }
 
     fn next_one_of<T: AsRef<[TokenType]>>(&mut self, types: T) -> bool {
-        types
-            .as_ref()
-            .into_iter()
-            .map(|ty| self.next_is(*ty))
-            .fold(false, |a, b| a || b)
-    }
-
-    fn synchronize(&mut self) {
-        self.next();
-        match self.peek().map(Spanned::data) {
-            Some(TokenType::Semicolon)
-            | Some(TokenType::Def)
-            | Some(TokenType::Let)
-            | Some(TokenType::Loop)
-            | Some(TokenType::While)
-            | Some(TokenType::If) => return,
-            Some(_) => self.next(),
-            None => None,
-        };
+        types.as_ref().iter().any(|ty| self.next_is(*ty))
+    }
+
+    /// Skips the tokens after a syntax error until the next `boundary`, where
+    /// `depth` is the number of open `{` in front of the statement or item that
+    /// failed to parse. A statement ends after a `;` or in front of the `}` of
+    /// its block, or when the next one starts with a keyword.
+    fn synchronize(&mut self, boundary: Boundary, depth: usize) {
+        while let Some(ty) = self.peek().map(|token| *token.data()) {
+            if self.depth == depth {
+                match (boundary, ty) {
+                    (Boundary::Stmt, TokenType::Semicolon) => {
+                        self.next();
+                        return;
+                    }
+                    (Boundary::Stmt, TokenType::RightCurly) => return,
+                    (Boundary::Stmt, _) if self.next_is_keyword_stmt() => return,
+                    (Boundary::Item, TokenType::Pub)
+                    | (Boundary::Item, TokenType::Def)
+                    | (Boundary::Item, TokenType::Struct)
+                    | (Boundary::Item, TokenType::Enum)
+                    | (Boundary::Item, TokenType::Const)
+                    | (Boundary::Item, TokenType::Impl)
+                    | (Boundary::Item, TokenType::Import) => return,
+                    _ => {}
+                }
+            }
+            self.next();
+        }
+    }
+
+    /// Collects a syntax error, unless it's a follow-on error at the end of the input,
+    /// like the missing `}` after a format string that runs to the end of the file.
+    fn report(&mut self, err: Diagnostic) {
+        if self.at_end() {
+            if self.eof_reported {
+                return;
+            }
+            self.eof_reported = true;
+        }
+        self.diagnostics.push(err);
+    }
+
+    /// Returns the span from `start` to the last consumed token, which is the
+    /// span of an error node that replaces the skipped tokens.
+    fn error_span(&self, start: Span) -> Span {
+        if self.last.end() < start.start() {
+            start
+        } else {
+            Span::new(start.start(), self.last.end())
+        }
+    }
+
+    /// Returns the span of the next token, or an empty span at the end of the input.
+    fn next_span(&mut self) -> Span {
+        match self.peek() {
+            Some(token) => token.span(),
+            None => Span::new(self.last.end(), self.last.end()),
+        }
     }
 
     // Error utilities
@@ -132,7 +422,7 @@ impl<'input> Parser<'input> {
                 self.new_label(format!(
                     "expected one of '{}', but got '{}'",
                     expected
-                        .into_iter()
+                        .iter()
                         .map(|ty| ty.to_string())
                         .collect::<Vec<_>>()
                         .join(" "),
@@ -142,10 +432,49 @@ impl<'input> Parser<'input> {
             SyntaxError::ExpectedExpr => {
                 self.new_error("expected expression", self.new_label("expected expression"))
             }
+            SyntaxError::InvalidAssignTarget(span) => self.new_error(
+                "invalid assignment target",
+                Label::primary(self.file, span).with_message("cannot assign to this expression"),
+            ),
+            SyntaxError::InvalidOperator(found) => self.new_error(
+                "invalid operator",
+                self.new_label(format!("expected an operator, but got '{}'", found)),
+            ),
             SyntaxError::InvalidInteger(err) => self.new_error(
                 "invalid integer",
                 self.new_label(format!("invalid integer: {:?}", err.code)),
             ),
+            SyntaxError::InvalidModuleName(span) => self.new_error(
+                "invalid module name",
+                Label::primary(self.file, span)
+                    .with_message("the file name must be a valid identifier"),
+            ),
+            SyntaxError::UnclosedInterpolation(span) => self
+                .new_error(
+                    "unclosed interpolation",
+                    Label::primary(self.file, span).with_message("this `{` is never closed"),
+                )
+                .with_notes(vec!["help: use `{{` for a literal `{`".to_string()]),
+            SyntaxError::UnmatchedBrace(span) => self
+                .new_error(
+                    "unmatched `}` in format string",
+                    Label::primary(self.file, span).with_message("this `}` doesn't close anything"),
+                )
+                .with_notes(vec!["help: use `}}` for a literal `}`".to_string()]),
+            SyntaxError::TooDeep(span) => self.new_error(
+                "expression is nested too deeply",
+                Label::primary(self.file, span).with_message(format!(
+                    "this is nested more than {} levels deep",
+                    MAX_NESTING
+                )),
+            ),
+            SyntaxError::TooLong(span) => self.new_error(
+                "expression is too long",
+                Label::primary(self.file, span).with_message(format!(
+                    "this operator makes the expression more than {} levels deep",
+                    MAX_DEPTH
+                )),
+            ),
             SyntaxError::UnexpectedEof => {
                 let span = self.span.end();
                 let label = Label::primary(self.file, Span::new(span, span))
@@ -166,34 +495,565 @@ impl<'input> Parser<'input> {
     }
 }
 
-/// Other parsing
+/// Item parsing
+impl Parser<'_> {
+    /// Parses items until the end of the input is reached, and returns the
+    /// first syntax error if there is one.
+    pub fn parse_items(&mut self) -> Result<Vec<ast::Item>> {
+        let (items, mut diagnostics) = self.parse_program();
+        if diagnostics.is_empty() {
+            Ok(items)
+        } else {
+            Err(diagnostics.remove(0))
+        }
+    }
+
+    /// Parses items until the end of the input is reached, and continues after
+    /// syntax errors at the next statement or item. Returns every syntax error,
+    /// together with the items, where the parts with errors are replaced with
+    /// `ItemKind::Error` and `StmtKind::Error`.
+    pub fn parse_program(&mut self) -> (Vec<ast::Item>, Vec<Diagnostic>) {
+        let recover = std::mem::replace(&mut self.recover, true);
+        let mut items = vec![];
+        while !self.at_end() {
+            let (start, depth) = (self.next_span(), self.depth);
+            let checkpoint = self.checkpoint();
+            let item = match self.next_item() {
+                Ok(item) => item,
+                Err(err) => {
+                    self.report(err);
+                    self.synchronize(Boundary::Item, depth);
+                    self.error_span(start).span(ast::ItemKind::Error)
+                }
+            };
+            self.finish_node(checkpoint, item.node_kind());
+            items.push(item);
+        }
+        self.recover = recover;
+        (items, std::mem::take(&mut self.diagnostics))
+    }
+
+    /// Parses the whole file like [`Parser::parse_program`], and returns the items together
+    /// with the concrete syntax tree, whose text is exactly the source of the file if this
+    /// parser was created by [`Parser::lossless`].
+    pub fn parse_tree(&mut self) -> cst::SyntaxTree {
+        let (items, diagnostics) = self.parse_program();
+        // Consumes the trivia at the end of the file.
+        self.checkpoint();
+        let root = self.cst.take().unwrap_or_default().finish();
+        cst::SyntaxTree {
+            root,
+            items,
+            diagnostics,
+        }
+    }
+
+    pub fn next_item(&mut self) -> Result<ast::Item> {
+        let public = self.eat_if(TokenType::Pub);
+        match self.peek().map(|token| *token.data()) {
+            Some(TokenType::Def) => self.next_def(public),
+            Some(TokenType::Struct) => self.next_struct(public),
+            Some(TokenType::Enum) => self.next_enum(public),
+            Some(TokenType::Const) => self.next_const(public),
+            Some(TokenType::Impl) if public.is_none() => self.next_impl(),
+            Some(TokenType::Import) if public.is_none() => self.next_import(),
+            Some(found) => {
+                let expected = match public {
+                    Some(_) => vec![
+                        TokenType::Def,
+                        TokenType::Struct,
+                        TokenType::Enum,
+                        TokenType::Const,
+                    ],
+                    None => vec![
+                        TokenType::Pub,
+                        TokenType::Def,
+                        TokenType::Struct,
+                        TokenType::Enum,
+                        TokenType::Const,
+                        TokenType::Impl,
+                        TokenType::Import,
+                    ],
+                };
+                Err(self.make_diagnostic(SyntaxError::ExpectedOneOf { expected, found }))
+            }
+            None => Err(self.make_diagnostic(SyntaxError::UnexpectedEof)),
+        }
+    }
+
+    /// Parses a function, where `public` is the `pub` token in front of it.
+    fn next_def(&mut self, public: Option<Token>) -> Result<ast::Item> {
+        let def = self.eat(TokenType::Def)?;
+        let name = self.next_identifier()?;
+        self.eat(TokenType::LeftParen)?;
+        let mut args = vec![];
+        while !self.next_is(TokenType::RightParen) {
+            let name = self.next_identifier()?;
+            self.eat(TokenType::Colon)?;
+            let ty = self.next_type()?;
+            args.push(ast::DefArgument { name, ty });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        self.eat(TokenType::RightParen)?;
+        let ret = match self.eat_if(TokenType::Colon) {
+            Some(_) => Some(self.next_type()?),
+            None => None,
+        };
+        let (body, span) = self.next_block()?;
+
+        let span = public.as_ref().unwrap_or(&def).span().merge(span);
+        Ok(span.span(ast::ItemKind::Def {
+            name,
+            public: public.is_some(),
+            args,
+            ret,
+            body,
+        }))
+    }
+
+    /// Parses `import name;` or `import "path/to/name.tre";`, where the
+    /// namespace of the latter is the name of the file without the extension.
+    fn next_import(&mut self) -> Result<ast::Item> {
+        let import = self.eat(TokenType::Import)?;
+        let (name, path) = match self.eat_if(TokenType::String) {
+            Some(token) => {
+                let path = token.span_ref().index(self.files.source(self.file));
+                let stem = std::path::Path::new(path)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .filter(|stem| lexer::is_valid_identifier(stem))
+                    .ok_or_else(|| {
+                        self.make_diagnostic(SyntaxError::InvalidModuleName(token.span()))
+                    })?;
+                let name = token.span().span(stem.to_string());
+                (name, Some(token.span().span(path.to_string())))
+            }
+            None => (self.next_identifier()?, None),
+        };
+        let semi = self.eat(TokenType::Semicolon)?;
+
+        let span = import.span().merge(semi.span());
+        Ok(span.span(ast::ItemKind::Import { name, path }))
+    }
+
+    fn next_const(&mut self, public: Option<Token>) -> Result<ast::Item> {
+        let token = self.eat(TokenType::Const)?;
+        let name = self.next_identifier()?;
+        self.eat(TokenType::Colon)?;
+        let ty = self.next_type()?;
+        self.eat(TokenType::Equal)?;
+        let val = self.next_expr()?;
+        let semicolon = self.eat(TokenType::Semicolon)?;
+
+        let span = public
+            .as_ref()
+            .unwrap_or(&token)
+            .span()
+            .merge(semicolon.span());
+        Ok(span.span(ast::ItemKind::Const {
+            name,
+            public: public.is_some(),
+            ty,
+            val,
+        }))
+    }
+
+    fn next_struct(&mut self, public: Option<Token>) -> Result<ast::Item> {
+        let token = self.eat(TokenType::Struct)?;
+        let name = self.next_identifier()?;
+        self.eat(TokenType::LeftCurly)?;
+        let mut fields = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            let name = self.next_identifier()?;
+            self.eat(TokenType::Colon)?;
+            let ty = self.next_type()?;
+            fields.push(ast::StructField { name, ty });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = public.as_ref().unwrap_or(&token).span().merge(close.span());
+        Ok(span.span(ast::ItemKind::Struct {
+            name,
+            public: public.is_some(),
+            fields,
+        }))
+    }
+
+    fn next_enum(&mut self, public: Option<Token>) -> Result<ast::Item> {
+        let token = self.eat(TokenType::Enum)?;
+        let name = self.next_identifier()?;
+        self.eat(TokenType::LeftCurly)?;
+        let mut variants = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            let name = self.next_identifier()?;
+            let mut fields = vec![];
+            if self.eat_if(TokenType::LeftParen).is_some() {
+                while !self.next_is(TokenType::RightParen) {
+                    fields.push(self.next_type()?);
+                    if self.eat_if(TokenType::Comma).is_none() {
+                        break;
+                    }
+                }
+                self.eat(TokenType::RightParen)?;
+            }
+            variants.push(ast::Variant { name, fields });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = public.as_ref().unwrap_or(&token).span().merge(close.span());
+        Ok(span.span(ast::ItemKind::Enum {
+            name,
+            public: public.is_some(),
+            variants,
+        }))
+    }
+
+    fn next_impl(&mut self) -> Result<ast::Item> {
+        let token = self.eat(TokenType::Impl)?;
+        let ty = self.next_identifier()?;
+        self.eat(TokenType::LeftCurly)?;
+        let mut methods = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            methods.push(self.node(|this| {
+                let public = this.eat_if(TokenType::Pub);
+                this.next_def(public)
+            })?);
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = token.span().merge(close.span());
+        Ok(span.span(ast::ItemKind::Impl { ty, methods }))
+    }
+
+    fn next_type(&mut self) -> Result<ast::Type> {
+        self.nested(|parser| parser.node(Self::next_type_kind))
+    }
+
+    fn next_type_kind(&mut self) -> Result<ast::Type> {
+        if let Some(open) = self.eat_if(TokenType::LeftParen) {
+            if let Some(close) = self.eat_if(TokenType::RightParen) {
+                let span = open.span().merge(close.span());
+                return Ok(span.span(ast::TypeKind::Unit));
+            }
+            let ty = self.next_type()?;
+            if self.eat_if(TokenType::Comma).is_none() {
+                self.eat(TokenType::RightParen)?;
+                return Ok(ty);
+            }
+            let mut tys = vec![ty];
+            while !self.next_is(TokenType::RightParen) {
+                tys.push(self.next_type()?);
+                if self.eat_if(TokenType::Comma).is_none() {
+                    break;
+                }
+            }
+            let close = self.eat(TokenType::RightParen)?;
+            let span = open.span().merge(close.span());
+            return Ok(span.span(ast::TypeKind::Tuple(tys)));
+        }
+
+        if let Some(open) = self.eat_if(TokenType::LeftBracket) {
+            let ty = self.next_type()?;
+            let close = self.eat(TokenType::RightBracket)?;
+            let span = open.span().merge(close.span());
+            return Ok(span.span(ast::TypeKind::Array(Box::new(ty))));
+        }
+
+        if let Some(open) = self.eat_if(TokenType::LeftCurly) {
+            let key = self.next_type()?;
+            self.eat(TokenType::Colon)?;
+            let val = self.next_type()?;
+            let close = self.eat(TokenType::RightCurly)?;
+            let span = open.span().merge(close.span());
+            return Ok(span.span(ast::TypeKind::Map(Box::new(key), Box::new(val))));
+        }
+
+        if let Some(def) = self.eat_if(TokenType::Def) {
+            self.eat(TokenType::LeftParen)?;
+            let mut args = vec![];
+            while !self.next_is(TokenType::RightParen) {
+                args.push(self.next_type()?);
+                if self.eat_if(TokenType::Comma).is_none() {
+                    break;
+                }
+            }
+            let mut span = def.span().merge(self.eat(TokenType::RightParen)?.span());
+            let ret = match self.eat_if(TokenType::Colon) {
+                Some(_) => {
+                    let ret = self.next_type()?;
+                    span = span.merge(ret.span());
+                    Some(Box::new(ret))
+                }
+                None => None,
+            };
+            return Ok(span.span(ast::TypeKind::Function { args, ret }));
+        }
+
+        let name = self.next_identifier()?;
+        if self.eat_if(TokenType::ColonColon).is_some() {
+            let path = ast::Path {
+                module: Some(name),
+                name: self.next_identifier()?,
+                variant: None,
+            };
+            return Ok(path.span().span(ast::TypeKind::Named(path)));
+        }
+        if let Some(ty) = ast::IntTy::from_name(name.data()) {
+            return Ok(name.span().span(ast::TypeKind::Int(ty)));
+        }
+        let ty = match name.data().as_str() {
+            "f64" => ast::TypeKind::F64,
+            "string" => ast::TypeKind::String,
+            "bool" => ast::TypeKind::Bool,
+            _ => ast::TypeKind::Named(ast::Path {
+                module: None,
+                name: name.clone(),
+                variant: None,
+            }),
+        };
+        Ok(name.span().span(ty))
+    }
+}
+
+/// Statement parsing
 impl Parser<'_> {
     pub fn next_stmt(&mut self) -> Result<ast::Stmt> {
         match self.peek() {
             Some(token) => match token.data() {
                 TokenType::Let => self.next_let_stmt(),
+                TokenType::While => self.next_while_stmt(),
+                TokenType::For => self.next_for_stmt(),
+                TokenType::Loop => self.next_loop_stmt(),
+                TokenType::Break => self.next_keyword_stmt(ast::StmtKind::Break),
+                TokenType::Continue => self.next_keyword_stmt(ast::StmtKind::Continue),
+                TokenType::Return => self.next_return_stmt(),
                 _ => self.next_expr_stmt(),
             },
             None => Err(self.make_diagnostic(SyntaxError::UnexpectedEof)),
         }
     }
 
+    /// Parses a list of statements surrounded by curly braces.
+    fn next_block(&mut self) -> Result<(ast::Block, Span)> {
+        self.node(|this| {
+            let open = this.eat(TokenType::LeftCurly)?;
+            this.next_block_rest(open, None)
+        })
+    }
+
+    /// Parses the rest of a block after the `open` token, where an expression
+    /// that is directly followed by the closing `}` is the value of the block.
+    /// `first` is the first expression of the block, if it was already parsed,
+    /// together with the checkpoint in front of it.
+    fn next_block_rest(
+        &mut self,
+        open: Token,
+        mut first: Option<(usize, ast::Expr)>,
+    ) -> Result<(ast::Block, Span)> {
+        let mut block = ast::Block::default();
+        while first.is_some() || !(self.next_is(TokenType::RightCurly) || self.at_end()) {
+            let (start, depth) = (self.next_span(), self.depth);
+            let (checkpoint, first) = match first.take() {
+                Some((checkpoint, expr)) => (checkpoint, Some(expr)),
+                None => (self.checkpoint(), None),
+            };
+            let stmt = match self.next_block_part(first) {
+                Ok(BlockPart::Stmt(stmt)) => stmt,
+                Ok(BlockPart::Expr(expr)) => {
+                    block.expr = Some(Box::new(expr));
+                    break;
+                }
+                Err(err) if self.recover => {
+                    self.report(err);
+                    self.synchronize(Boundary::Stmt, depth);
+                    self.error_span(start).span(ast::StmtKind::Error)
+                }
+                Err(err) => return Err(err),
+            };
+            self.finish_node(checkpoint, stmt.node_kind());
+            block.stmts.push(stmt);
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+        Ok((block, open.span().merge(close.span())))
+    }
+
+    /// Parses the next statement of a block, or the expression at its end,
+    /// where `first` is the expression the statement starts with if it was
+    /// already parsed.
+    fn next_block_part(&mut self, first: Option<ast::Expr>) -> Result<BlockPart> {
+        if first.is_none() && self.next_is_keyword_stmt() {
+            return self.next_stmt().map(BlockPart::Stmt);
+        }
+        let expr = match first {
+            Some(expr) => expr,
+            None => self.next_stmt_expr()?,
+        };
+        if self.next_is(TokenType::RightCurly) {
+            return Ok(BlockPart::Expr(expr));
+        }
+        self.finish_expr_stmt(expr).map(BlockPart::Stmt)
+    }
+
+    /// Returns `true` if the next token starts a statement that isn't an expression.
+    fn next_is_keyword_stmt(&mut self) -> bool {
+        self.next_one_of([
+            TokenType::Let,
+            TokenType::While,
+            TokenType::For,
+            TokenType::Loop,
+            TokenType::Break,
+            TokenType::Continue,
+            TokenType::Return,
+        ])
+    }
+
+    /// Parses a condition, that has to be surrounded by parenthesis.
+    fn next_condition(&mut self) -> Result<ast::Expr> {
+        self.eat(TokenType::LeftParen)?;
+        let cond = self.next_expr()?;
+        self.eat(TokenType::RightParen)?;
+        Ok(cond)
+    }
+
+    fn next_while_stmt(&mut self) -> Result<ast::Stmt> {
+        let token = self.eat(TokenType::While)?;
+        let cond = self.next_condition()?;
+        let (block, span) = self.next_block()?;
+        let span = token.span().merge(span);
+        Ok(span.span(ast::StmtKind::While { cond, block }))
+    }
+
+    fn next_for_stmt(&mut self) -> Result<ast::Stmt> {
+        let token = self.eat(TokenType::For)?;
+        let var = self.next_identifier()?;
+        self.eat(TokenType::In)?;
+        let iter = self.with_struct_literals(false, Self::next_expr)?;
+        let (block, span) = self.next_block()?;
+        let span = token.span().merge(span);
+        Ok(span.span(ast::StmtKind::For { var, iter, block }))
+    }
+
+    fn next_loop_stmt(&mut self) -> Result<ast::Stmt> {
+        let token = self.eat(TokenType::Loop)?;
+        let (block, span) = self.next_block()?;
+        let span = token.span().merge(span);
+        Ok(span.span(ast::StmtKind::Loop(block)))
+    }
+
+    /// Parses a statement that only consists of a keyword, like `break;`.
+    fn next_keyword_stmt(&mut self, stmt: ast::StmtKind) -> Result<ast::Stmt> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.make_diagnostic(SyntaxError::UnexpectedEof))?;
+        let semicolon = self.eat(TokenType::Semicolon)?;
+        let span = token.span().merge(semicolon.span());
+        Ok(span.span(stmt))
+    }
+
+    fn next_return_stmt(&mut self) -> Result<ast::Stmt> {
+        let token = self.eat(TokenType::Return)?;
+        let val = if self.next_is(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.next_expr()?)
+        };
+        let semicolon = self.eat(TokenType::Semicolon)?;
+        let span = token.span().merge(semicolon.span());
+        Ok(span.span(ast::StmtKind::Return(val)))
+    }
+
     fn next_let_stmt(&mut self) -> Result<ast::Stmt> {
         let let_token = self.eat(TokenType::Let)?;
-        let name = self.next_identifier()?;
+        let mutable = self.eat_if(TokenType::Mut).is_some();
+        let pattern = self.next_let_pattern()?;
+        let ty = match self.eat_if(TokenType::Colon) {
+            Some(_) => Some(self.next_type()?),
+            None => None,
+        };
         self.eat(TokenType::Equal)?;
         let val = self.next_expr()?;
-        let span = let_token.span().merge(val.span());
+        let semicolon = self.eat(TokenType::Semicolon)?;
+        let span = let_token.span().merge(semicolon.span());
 
-        let stmt = ast::StmtKind::Let { name, val };
+        let stmt = ast::StmtKind::Let {
+            pattern,
+            mutable,
+            ty,
+            val,
+        };
         Ok(span.span(stmt))
     }
 
+    /// Parses the left side of a `let`, which is a name, `_`, or a tuple of those.
+    fn next_let_pattern(&mut self) -> Result<ast::LetPattern> {
+        self.nested(|parser| parser.node(Self::next_let_pattern_kind))
+    }
+
+    fn next_let_pattern_kind(&mut self) -> Result<ast::LetPattern> {
+        let open = match self.eat_if(TokenType::LeftParen) {
+            Some(open) => open,
+            None => {
+                let name = self.next_identifier()?;
+                let pattern = match name.data().as_str() {
+                    "_" => ast::LetPatternKind::Wildcard,
+                    _ => ast::LetPatternKind::Name(name.clone()),
+                };
+                return Ok(name.span().span(pattern));
+            }
+        };
+
+        let mut patterns = vec![];
+        let mut comma = false;
+        while !self.next_is(TokenType::RightParen) {
+            patterns.push(self.next_let_pattern()?);
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+            comma = true;
+        }
+        let close = self.eat(TokenType::RightParen)?;
+        // Like in expressions, `(a)` is just `a`.
+        if patterns.len() == 1 && !comma {
+            return Ok(patterns.remove(0));
+        }
+        let span = open.span().merge(close.span());
+        Ok(span.span(ast::LetPatternKind::Tuple(patterns)))
+    }
+
     fn next_expr_stmt(&mut self) -> Result<ast::Stmt> {
-        let expr = self.next_expr()?;
+        let expr = self.next_stmt_expr()?;
+        self.finish_expr_stmt(expr)
+    }
+
+    /// Parses the expression of an expression statement.
+    fn next_stmt_expr(&mut self) -> Result<ast::Expr> {
+        // A statement that starts with a block ends after it,
+        // so `if (c) {} -1;` is two statements and not a subtraction.
+        if self.next_one_of([TokenType::If, TokenType::Match, TokenType::LeftCurly]) {
+            self.nested(|parser| parser.node(Self::next_primary))
+        } else {
+            self.next_expr()
+        }
+    }
+
+    /// Parses the semicolon after an expression statement,
+    /// which is optional if the expression ends with a block.
+    fn finish_expr_stmt(&mut self, expr: ast::Expr) -> Result<ast::Stmt> {
         let expr_span = expr.span_ref();
-        let semicolon = self.eat(TokenType::Semicolon)?.span();
-        let span = expr_span.merge(semicolon);
+        let semicolon = if expr.is_block_like() {
+            self.eat_if(TokenType::Semicolon)
+        } else {
+            Some(self.eat(TokenType::Semicolon)?)
+        };
+        let span = semicolon.map_or(expr.span(), |semicolon| expr_span.merge(semicolon.span()));
         Ok(span.span(ast::StmtKind::Expr(expr)))
     }
 
@@ -207,84 +1067,518 @@ impl Parser<'_> {
 /// Expression parsing
 impl Parser<'_> {
     pub fn next_expr(&mut self) -> Result<ast::Expr> {
-        self.next_equality()
-    }
-
-    binary_op!(
-        next_equality,
-        next_comparison,
-        TokenType::NotEqual,
-        TokenType::EqualEqual
-    );
-
-    binary_op!(
-        next_comparison,
-        next_addition,
-        TokenType::Greater,
-        TokenType::GreaterEqual,
-        TokenType::Less,
-        TokenType::LessEqual
-    );
-
-    binary_op!(
-        next_addition,
-        next_multiplication,
-        TokenType::Plus,
-        TokenType::Minus
-    );
-
-    binary_op!(
-        next_multiplication,
-        next_unary,
-        TokenType::Star,
-        TokenType::Slash
-    );
-
-    fn next_unary(&mut self) -> Result<ast::Expr> {
-        if self.next_one_of([TokenType::Bang, TokenType::Minus]) {
-            let token = self.next().unwrap();
-            let span = token.span();
-            let op: ast::UnaryOperation = token.into_inner().into();
-
-            let right = self.next_unary()?;
-            let span = span.merge(right.span());
-            return Ok(span.span(ast::ExprKind::Unary {
-                op,
-                expr: Box::new(right),
-            }));
-        }
-        self.next_primary()
+        self.next_expr_bp(0)
     }
 
-    fn next_primary(&mut self) -> Result<ast::Expr> {
-        match self.next() {
-            Some(token) => match token.data() {
-                TokenType::Identifier => {
-                    let span = token.span_ref();
-                    let name = span.span(span.index(self.files.source(self.file)).to_string());
-                    Ok(span.span(ast::ExprKind::Variable(name)))
+    /// Parses an expression whose operators bind at least as tight as `min_bp`,
+    /// see [`infix_binding_power`] for the binding powers of every operator.
+    fn next_expr_bp(&mut self, min_bp: u8) -> Result<ast::Expr> {
+        self.nested(|parser| {
+            let checkpoint = parser.checkpoint();
+            let mut expr = parser.next_prefix()?;
+
+            while let Some(ty) = parser.peek().map(|token| *token.data()) {
+                if let Some(bp) = postfix_binding_power(ty) {
+                    if bp < min_bp {
+                        break;
+                    }
+                    parser.wrap()?;
+                    expr = parser.next_postfix(expr)?;
+                    parser.finish_node(checkpoint, expr.node_kind());
+                    continue;
                 }
-                TokenType::String => self.next_string(token),
-                TokenType::Integer => self.next_integer(token),
-                TokenType::Bool => self.next_bool(token),
-                TokenType::LeftParen => {
-                    let expr = self.next_expr()?;
-                    self.eat(TokenType::RightParen)?;
-                    Ok(token.span().span(ast::ExprKind::Grouping(Box::new(expr))))
+
+                let (left_bp, right_bp) = match infix_binding_power(ty) {
+                    Some(bp) => bp,
+                    None => break,
+                };
+                // Ranges can't be chained, so `a..b..c` and `x = a..b..c` are errors.
+                let is_range = matches!(ty, TokenType::DotDot | TokenType::DotDotEqual);
+                if left_bp < min_bp || is_range && ends_with_range(&expr) {
+                    break;
                 }
-                ty => Err(self.make_diagnostic(SyntaxError::ExpectedOneOf {
-                    found: *ty,
-                    expected: vec![
-                        TokenType::LeftParen,
-                        TokenType::Integer,
-                        TokenType::String,
-                        TokenType::Bool,
-                    ],
-                })),
-            },
-            None => Err(self.make_diagnostic(SyntaxError::ExpectedExpr)),
-        }
-    }
+                parser.wrap()?;
+                expr = parser.next_infix(expr, right_bp)?;
+                parser.finish_node(checkpoint, expr.node_kind());
+            }
+
+            Ok(expr)
+        })
+    }
+
+    /// Parses the unary operators in front of an expression, e.g. `-x` or `!!b`.
+    fn next_prefix(&mut self) -> Result<ast::Expr> {
+        let bp = match self
+            .peek()
+            .and_then(|token| prefix_binding_power(*token.data()))
+        {
+            Some(bp) => bp,
+            None => return self.node(Self::next_primary),
+        };
+        let checkpoint = self.checkpoint();
+        let token = self.next_token()?;
+        let span = token.span();
+        let op = ast::UnaryOperation::try_from(*token.data())
+            .map_err(|ty| self.make_diagnostic(SyntaxError::InvalidOperator(ty)))?;
+
+        let right = self.next_expr_bp(bp)?;
+        let span = span.merge(right.span());
+        let expr = span.span(ast::ExprKind::Unary {
+            op,
+            expr: Box::new(right),
+        });
+        self.finish_node(checkpoint, expr.node_kind());
+        Ok(expr)
+    }
+
+    /// Parses the operator after `left` and its right operand, whose operators
+    /// have to bind at least as tight as `right_bp`.
+    fn next_infix(&mut self, left: ast::Expr, right_bp: u8) -> Result<ast::Expr> {
+        let token = self.next_token()?;
+        match token.data() {
+            TokenType::Equal => {
+                if !matches!(
+                    left.data(),
+                    ast::ExprKind::Variable(_)
+                        | ast::ExprKind::Index { .. }
+                        | ast::ExprKind::Field { .. }
+                ) {
+                    return Err(self.make_diagnostic(SyntaxError::InvalidAssignTarget(left.span())));
+                }
+                let val = self.next_expr_bp(right_bp)?;
+                let span = left.span().merge(val.span());
+                Ok(span.span(ast::ExprKind::Assign {
+                    target: Box::new(left),
+                    val: Box::new(val),
+                }))
+            }
+            TokenType::DotDot | TokenType::DotDotEqual => {
+                let inclusive = token.data() == &TokenType::DotDotEqual;
+                let end = self.next_expr_bp(right_bp)?;
+                let span = left.span().merge(end.span());
+                Ok(span.span(ast::ExprKind::Range {
+                    start: Box::new(left),
+                    end: Box::new(end),
+                    inclusive,
+                }))
+            }
+            ty => {
+                let op = ast::BinaryOperation::try_from(ty)
+                    .map_err(|ty| self.make_diagnostic(SyntaxError::InvalidOperator(ty)))?;
+                let right = self.next_expr_bp(right_bp)?;
+                let span = left.span().merge(right.span());
+                Ok(span.span(ast::ExprKind::Binary {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                }))
+            }
+        }
+    }
+
+    /// Parses a single postfix operator after `expr`, which is a call, indexing,
+    /// a field access, a method call or a cast.
+    fn next_postfix(&mut self, expr: ast::Expr) -> Result<ast::Expr> {
+        let token = self.next_token()?;
+        match token.data() {
+            TokenType::LeftParen => {
+                let (args, close) = self.next_expr_list(TokenType::RightParen)?;
+                let span = expr.span().merge(close.span());
+                Ok(span.span(ast::ExprKind::Call {
+                    callee: Box::new(expr),
+                    args,
+                }))
+            }
+            TokenType::LeftBracket => {
+                let index = self.next_expr()?;
+                let close = self.eat(TokenType::RightBracket)?;
+                let span = expr.span().merge(close.span());
+                Ok(span.span(ast::ExprKind::Index {
+                    expr: Box::new(expr),
+                    index: Box::new(index),
+                }))
+            }
+            TokenType::As => {
+                let ty = self.next_type()?;
+                let span = expr.span().merge(ty.span());
+                Ok(span.span(ast::ExprKind::Cast {
+                    expr: Box::new(expr),
+                    ty,
+                }))
+            }
+            _ => {
+                let name = self.next_identifier()?;
+                if self.eat_if(TokenType::LeftParen).is_some() {
+                    let (args, close) = self.next_expr_list(TokenType::RightParen)?;
+                    let span = expr.span().merge(close.span());
+                    return Ok(span.span(ast::ExprKind::MethodCall {
+                        receiver: Box::new(expr),
+                        name,
+                        args,
+                    }));
+                }
+                let span = expr.span().merge(name.span());
+                Ok(span.span(ast::ExprKind::Field {
+                    expr: Box::new(expr),
+                    name,
+                }))
+            }
+        }
+    }
+
+    /// Parses a comma separated list of expressions, that may have a trailing comma,
+    /// up to and including the `close` token.
+    fn next_expr_list(&mut self, close: TokenType) -> Result<(Vec<ast::Expr>, Token)> {
+        let mut exprs = vec![];
+        while !self.next_is(close) {
+            exprs.push(self.with_struct_literals(true, Self::next_expr)?);
+            if !self.next_is(TokenType::Comma) {
+                break;
+            }
+            self.next();
+        }
+        let close = self.eat(close)?;
+        Ok((exprs, close))
+    }
+
+    fn next_primary(&mut self) -> Result<ast::Expr> {
+        // The token is only consumed if it can start an expression, so that
+        // recovering from the error starts at it.
+        let token = match self.peek() {
+            Some(token) if !EXPR_START.contains(token.data()) => {
+                let found = *token.data();
+                return Err(self.make_diagnostic(SyntaxError::ExpectedOneOf {
+                    found,
+                    expected: EXPR_START.to_vec(),
+                }));
+            }
+            _ => self.next(),
+        };
+        match token {
+            Some(token) => match token.data() {
+                TokenType::Identifier => {
+                    let span = token.span_ref();
+                    let name = span.span(span.index(self.files.source(self.file)).to_string());
+                    let path = self.next_path(name)?;
+                    if self.struct_literals
+                        && path.variant.is_none()
+                        && self.next_is(TokenType::LeftCurly)
+                    {
+                        return self.next_struct_literal(path);
+                    }
+                    match path.module {
+                        Some(_) => Ok(path.span().span(ast::ExprKind::Path(path))),
+                        None => Ok(span.span(ast::ExprKind::Variable(path.name))),
+                    }
+                }
+                TokenType::String => self.next_string(token),
+                TokenType::FormatString => self.next_format_string(token),
+                TokenType::Integer => self.next_integer(token),
+                TokenType::Bool => self.next_bool(token),
+                TokenType::LeftParen => self.next_tuple_or_grouping(token),
+                TokenType::LeftBracket => {
+                    let (elems, close) = self.next_expr_list(TokenType::RightBracket)?;
+                    let span = token.span().merge(close.span());
+                    Ok(span.span(ast::ExprKind::Array(elems)))
+                }
+                TokenType::Def => {
+                    self.eat(TokenType::LeftParen)?;
+                    let args = self.next_closure_args(TokenType::RightParen)?;
+                    let (block, span) = self.next_block()?;
+                    let span = token.span().merge(span);
+                    Ok(span.span(ast::ExprKind::Closure {
+                        args,
+                        body: ast::ClosureBody::Block(block),
+                    }))
+                }
+                TokenType::Match => self.next_match(token),
+                TokenType::If => self.next_if(token),
+                TokenType::LeftCurly => self.next_block_or_map(token),
+                // `||` is lexed as a single token, and starts a closure without arguments.
+                TokenType::Pipe | TokenType::PipePipe => {
+                    let args = match token.data() {
+                        TokenType::Pipe => self.next_closure_args(TokenType::Pipe)?,
+                        _ => vec![],
+                    };
+                    let (body, span) = if self.next_is(TokenType::LeftCurly) {
+                        let (block, span) = self.next_block()?;
+                        (ast::ClosureBody::Block(block), span)
+                    } else {
+                        let expr = self.next_expr()?;
+                        let span = expr.span();
+                        (ast::ClosureBody::Expr(Box::new(expr)), span)
+                    };
+                    let span = token.span().merge(span);
+                    Ok(span.span(ast::ExprKind::Closure { args, body }))
+                }
+                ty => Err(self.make_diagnostic(SyntaxError::ExpectedOneOf {
+                    found: *ty,
+                    expected: EXPR_START.to_vec(),
+                })),
+            },
+            None => Err(self.make_diagnostic(SyntaxError::ExpectedExpr)),
+        }
+    }
+
+    /// Parses the rest of a parenthesized expression after the `open` token. It's a tuple
+    /// if it contains a comma, e.g. `(a, b)` or `(a,)`, and `()` is the unit value.
+    fn next_tuple_or_grouping(&mut self, open: Token) -> Result<ast::Expr> {
+        if let Some(close) = self.eat_if(TokenType::RightParen) {
+            let span = open.span().merge(close.span());
+            return Ok(span.span(ast::ExprKind::Tuple(vec![])));
+        }
+        let expr = self.with_struct_literals(true, Self::next_expr)?;
+        if self.eat_if(TokenType::Comma).is_none() {
+            self.eat(TokenType::RightParen)?;
+            return Ok(open.span().span(ast::ExprKind::Grouping(Box::new(expr))));
+        }
+        let (mut elems, close) = self.next_expr_list(TokenType::RightParen)?;
+        elems.insert(0, expr);
+        let span = open.span().merge(close.span());
+        Ok(span.span(ast::ExprKind::Tuple(elems)))
+    }
+
+    /// Parses a block or a map literal after the `open` token. It's a map if the first
+    /// expression is followed by a `:`, or if it's `{:}`, which is an empty map.
+    fn next_block_or_map(&mut self, open: Token) -> Result<ast::Expr> {
+        if self.eat_if(TokenType::Colon).is_some() {
+            let close = self.eat(TokenType::RightCurly)?;
+            let span = open.span().merge(close.span());
+            return Ok(span.span(ast::ExprKind::Map(vec![])));
+        }
+        if self.next_is(TokenType::RightCurly) || self.next_is_keyword_stmt() {
+            let (block, span) = self.next_block_rest(open, None)?;
+            return Ok(span.span(ast::ExprKind::Block(block)));
+        }
+
+        let checkpoint = self.checkpoint();
+        let first = self.with_struct_literals(true, Self::next_stmt_expr)?;
+        if self.eat_if(TokenType::Colon).is_none() {
+            let (block, span) = self.next_block_rest(open, Some((checkpoint, first)))?;
+            return Ok(span.span(ast::ExprKind::Block(block)));
+        }
+
+        self.with_struct_literals(true, |this| {
+            let mut entries = vec![(first, this.next_expr()?)];
+            while this.eat_if(TokenType::Comma).is_some() && !this.next_is(TokenType::RightCurly) {
+                let key = this.next_expr()?;
+                this.eat(TokenType::Colon)?;
+                entries.push((key, this.next_expr()?));
+            }
+            let close = this.eat(TokenType::RightCurly)?;
+            let span = open.span().merge(close.span());
+            Ok(span.span(ast::ExprKind::Map(entries)))
+        })
+    }
+
+    /// Parses the arguments of a closure, which may have a type, up to and including `close`.
+    fn next_closure_args(&mut self, close: TokenType) -> Result<Vec<ast::ClosureArg>> {
+        let mut args = vec![];
+        while !self.next_is(close) {
+            let name = self.next_identifier()?;
+            let ty = match self.eat_if(TokenType::Colon) {
+                Some(_) => Some(self.next_type()?),
+                None => None,
+            };
+            args.push(ast::ClosureArg { name, ty });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        self.eat(close)?;
+        Ok(args)
+    }
+
+    /// Parses the rest of a `match` expression, after the `match` token.
+    fn next_match(&mut self, token: Token) -> Result<ast::Expr> {
+        let expr = self.with_struct_literals(false, Self::next_expr)?;
+        self.eat(TokenType::LeftCurly)?;
+        let mut arms = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            let checkpoint = self.checkpoint();
+            let pattern = self.next_pattern()?;
+            self.eat(TokenType::FatArrow)?;
+            let body = self.with_struct_literals(true, Self::next_expr)?;
+            // Like statements, arms don't need a comma if their body ends with a block.
+            let block_like = body.is_block_like();
+            arms.push(ast::MatchArm { pattern, body });
+            let comma = self.eat_if(TokenType::Comma);
+            self.finish_node(checkpoint, cst::NodeKind::MatchArm);
+            if comma.is_none() && !block_like {
+                break;
+            }
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = token.span().merge(close.span());
+        Ok(span.span(ast::ExprKind::Match {
+            expr: Box::new(expr),
+            arms,
+        }))
+    }
+
+    /// Parses the rest of an `if` expression, after the `if` token.
+    fn next_if(&mut self, token: Token) -> Result<ast::Expr> {
+        let cond = self.next_condition()?;
+        let (then, mut span) = self.next_block()?;
+
+        let otherwise = match self.eat_if(TokenType::Else) {
+            // `else if` is desugared into an `else` block that only contains the `if`.
+            Some(_) => match (self.checkpoint(), self.eat_if(TokenType::If)) {
+                (checkpoint, Some(token)) => {
+                    let expr = self.next_if(token)?;
+                    self.finish_node(checkpoint, expr.node_kind());
+                    span = expr.span();
+                    Some(ast::Block {
+                        stmts: vec![],
+                        expr: Some(Box::new(expr)),
+                    })
+                }
+                (_, None) => {
+                    let (block, block_span) = self.next_block()?;
+                    span = block_span;
+                    Some(block)
+                }
+            },
+            None => None,
+        };
+
+        let span = token.span().merge(span);
+        Ok(span.span(ast::ExprKind::If {
+            cond: Box::new(cond),
+            then,
+            otherwise,
+        }))
+    }
+
+    fn next_pattern(&mut self) -> Result<ast::Pattern> {
+        self.nested(|parser| parser.node(Self::next_pattern_kind))
+    }
+
+    fn next_pattern_kind(&mut self) -> Result<ast::Pattern> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(self.make_diagnostic(SyntaxError::UnexpectedEof)),
+        };
+        let src = self.files.source(self.file);
+        let literal = match token.data() {
+            TokenType::Identifier => return self.next_binding_pattern(token),
+            TokenType::Minus => {
+                let int = self.eat(TokenType::Integer)?;
+                let span = token.span().merge(int.span());
+                let num = self.parse_integer(&int)?;
+                return Ok(span.span(ast::PatternKind::Literal(ast::Literal::Int(-num))));
+            }
+            TokenType::Integer => ast::Literal::Int(self.parse_integer(&token)?),
+            TokenType::String => ast::Literal::String(token.span_ref().index(src).to_string()),
+            TokenType::Bool => ast::Literal::Bool(token.span_ref().index(src) == "true"),
+            ty => {
+                return Err(self.make_diagnostic(SyntaxError::ExpectedOneOf {
+                    found: *ty,
+                    expected: vec![
+                        TokenType::Identifier,
+                        TokenType::Integer,
+                        TokenType::String,
+                        TokenType::Bool,
+                    ],
+                }))
+            }
+        };
+        Ok(token.span().span(ast::PatternKind::Literal(literal)))
+    }
+
+    /// Parses a wildcard, binding or struct pattern that starts with the identifier `token`.
+    fn next_binding_pattern(&mut self, token: Token) -> Result<ast::Pattern> {
+        let span = token.span_ref();
+        let name = span.span(span.index(self.files.source(self.file)).to_string());
+        let path = self.next_path(name)?;
+        match &path {
+            ast::Path { module: None, .. } if self.next_is(TokenType::LeftCurly) => {}
+            ast::Path { module: None, .. } if path.name.data() == "_" => {
+                return Ok(span.span(ast::PatternKind::Wildcard))
+            }
+            ast::Path { module: None, .. } => {
+                return Ok(span.span(ast::PatternKind::Binding(path.name)))
+            }
+            // `a::b` is a struct pattern if it's followed by fields, and a variant otherwise
+            ast::Path { variant: None, .. } if self.next_is(TokenType::LeftCurly) => {}
+            _ => return self.next_variant_pattern(path),
+        }
+
+        self.eat(TokenType::LeftCurly)?;
+        let mut fields = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            let name = self.next_identifier()?;
+            // `Point { x }` is a shorthand for `Point { x: x }`
+            let pattern = match self.eat_if(TokenType::Colon) {
+                Some(_) => self.next_pattern()?,
+                None => name.span().span(ast::PatternKind::Binding(name.clone())),
+            };
+            fields.push(ast::FieldPattern { name, pattern });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = path.span().merge(close.span());
+        Ok(span.span(ast::PatternKind::Struct { name: path, fields }))
+    }
+
+    /// Parses a variant pattern like `Shape::Rect(w, h)` or `Shape::Empty`.
+    fn next_variant_pattern(&mut self, name: ast::Path) -> Result<ast::Pattern> {
+        let mut span = name.span();
+        let mut args = vec![];
+        if self.eat_if(TokenType::LeftParen).is_some() {
+            while !self.next_is(TokenType::RightParen) {
+                args.push(self.next_pattern()?);
+                if self.eat_if(TokenType::Comma).is_none() {
+                    break;
+                }
+            }
+            span = span.merge(self.eat(TokenType::RightParen)?.span());
+        }
+        Ok(span.span(ast::PatternKind::Variant { name, args }))
+    }
+
+    /// Parses the rest of a path like `a::b::c`, where `name` is the first segment.
+    fn next_path(&mut self, name: ast::Identifier) -> Result<ast::Path> {
+        if self.eat_if(TokenType::ColonColon).is_none() {
+            return Ok(ast::Path {
+                module: None,
+                name,
+                variant: None,
+            });
+        }
+        let second = self.next_identifier()?;
+        let variant = match self.eat_if(TokenType::ColonColon) {
+            Some(_) => Some(self.next_identifier()?),
+            None => None,
+        };
+        Ok(ast::Path {
+            module: Some(name),
+            name: second,
+            variant,
+        })
+    }
+
+    fn next_struct_literal(&mut self, name: ast::Path) -> Result<ast::Expr> {
+        self.eat(TokenType::LeftCurly)?;
+        let mut fields = vec![];
+        while !self.next_is(TokenType::RightCurly) {
+            let name = self.next_identifier()?;
+            self.eat(TokenType::Colon)?;
+            let val = self.next_expr()?;
+            fields.push(ast::FieldInit { name, val });
+            if self.eat_if(TokenType::Comma).is_none() {
+                break;
+            }
+        }
+        let close = self.eat(TokenType::RightCurly)?;
+
+        let span = name.span().merge(close.span());
+        Ok(span.span(ast::ExprKind::StructLiteral { name, fields }))
+    }
 
     fn next_string(&mut self, token: Token) -> Result<ast::Expr> {
         let src = self.files.source(self.file);
@@ -294,27 +1588,884 @@ impl Parser<'_> {
             .span(ast::ExprKind::Literal(ast::Literal::String(string))))
     }
 
+    /// Splits the content of a format string into text and interpolations,
+    /// which are parsed by a parser for just the span of the interpolation.
+    fn next_format_string(&mut self, token: Token) -> Result<ast::Expr> {
+        let start = std::ops::Range::<usize>::from(token.span()).start;
+        let content = token.span_ref().index(self.files.source(self.file));
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = content.char_indices().peekable();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek().map(|(_, next)| *next) == Some(c) => {
+                    chars.next();
+                    literal.push(c);
+                }
+                '{' => {
+                    let end = match interpolation_end(content, idx + 1) {
+                        Some(end) => end,
+                        None => {
+                            let span = Span::from(start + idx..start + idx + 1);
+                            return Err(
+                                self.make_diagnostic(SyntaxError::UnclosedInterpolation(span))
+                            );
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(ast::FormatPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    let span = Span::from(start + idx + 1..start + end);
+                    parts.push(ast::FormatPart::Expr(self.parse_interpolation(span)?));
+                    while chars.next_if(|(idx, _)| *idx <= end).is_some() {}
+                }
+                '}' => {
+                    let span = Span::from(start + idx..start + idx + 1);
+                    return Err(self.make_diagnostic(SyntaxError::UnmatchedBrace(span)));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(ast::FormatPart::Literal(literal));
+        }
+        Ok(token.span().span(ast::ExprKind::FormatString(parts)))
+    }
+
+    /// Parses the expression of an interpolation, which has to cover all of `span`.
+    fn parse_interpolation(&self, span: Span) -> Result<ast::Expr> {
+        let mut parser = Parser::with_span(self.files, self.file, span);
+        let expr = parser.next_expr()?;
+        match parser.next() {
+            Some(token) => Err(parser.make_diagnostic(SyntaxError::Expected {
+                expected: TokenType::RightCurly,
+                found: *token.data(),
+            })),
+            None => Ok(expr),
+        }
+    }
+
     fn next_integer(&mut self, token: Token) -> Result<ast::Expr> {
-        let src = self.files.source(self.file);
-        let num = token.span_ref().index(src);
-        let num = lexical::parse::<i64, _>(num)
-            .map_err(|err| self.make_diagnostic(SyntaxError::InvalidInteger(err)))?;
+        let num = self.parse_integer(&token)?;
         Ok(token
             .span_ref()
             .span(ast::ExprKind::Literal(ast::Literal::Int(num))))
     }
 
+    fn parse_integer(&self, token: &Token) -> Result<i128> {
+        let src = self.files.source(self.file);
+        let num = token.span_ref().index(src);
+        lexical::parse::<u64, _>(num)
+            .map(i128::from)
+            .map_err(|err| self.make_diagnostic(SyntaxError::InvalidInteger(err)))
+    }
+
     fn next_bool(&mut self, token: Token) -> Result<ast::Expr> {
         let src = self.files.source(self.file);
-        let src = token.span_ref().index(src);
-        match src {
-            "true" => Ok(token
-                .span()
-                .span(ast::ExprKind::Literal(ast::Literal::Bool(true)))),
-            "false" => Ok(token
-                .span()
-                .span(ast::ExprKind::Literal(ast::Literal::Bool(false)))),
-            _ => unreachable!(),
+        // The lexer only produces bool tokens for `true` and `false`.
+        let val = token.span_ref().index(src) == "true";
+        Ok(token
+            .span()
+            .span(ast::ExprKind::Literal(ast::Literal::Bool(val))))
+    }
+}
+
+/// Returns the index of the `}` that closes the interpolation starting at `start`,
+/// skipping nested braces and strings.
+fn interpolation_end(content: &str, start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut chars = content[start..].char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(start + idx),
+            '}' => depth -= 1,
+            '"' => {
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '"' if !escaped => break,
+                        '\\' => escaped = !escaped,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Whether `expr` ends with a range that isn't in parentheses, like `a + |x| 0..x`.
+fn ends_with_range(expr: &ast::Expr) -> bool {
+    match expr.data() {
+        ast::ExprKind::Range { .. } => true,
+        ast::ExprKind::Binary { right: expr, .. }
+        | ast::ExprKind::Unary { expr, .. }
+        | ast::ExprKind::Assign { val: expr, .. }
+        | ast::ExprKind::Closure {
+            body: ast::ClosureBody::Expr(expr),
+            ..
+        } => ends_with_range(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_stmt(src: &str) -> Result<ast::Stmt> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        Parser::new(&files, file).next_stmt()
+    }
+
+    #[test]
+    fn test_let_mut() {
+        let stmt = parse_stmt("let x = 1;").unwrap();
+        assert!(matches!(
+            stmt.data(),
+            ast::StmtKind::Let { mutable: false, .. }
+        ));
+
+        let stmt = parse_stmt("let mut x = 1;").unwrap();
+        match stmt.data() {
+            ast::StmtKind::Let {
+                pattern, mutable, ..
+            } => {
+                assert_eq!(pattern.to_string(), "x");
+                assert!(*mutable);
+            }
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+
+        let stmt = parse_stmt("let mut x: u8 = 1;").unwrap();
+        match stmt.data() {
+            ast::StmtKind::Let { ty, .. } => {
+                assert_eq!(ty.as_ref().map(ToString::to_string).as_deref(), Some("u8"));
+            }
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+        assert_eq!(stmt.to_string(), "let mut x: u8 = 1;");
+    }
+
+    #[test]
+    fn test_assignment() {
+        let stmt = parse_stmt("a = b = 1;").unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        assert_eq!(expr.to_string(), "a = b = 1");
+
+        assert!(parse_stmt("1 = 2;").is_err());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let stmt = parse_stmt("a[0][1] = [1, [2, 3], len(a),];").unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        assert!(matches!(expr.data(), ast::ExprKind::Assign { .. }));
+        assert_eq!(expr.to_string(), "a[0][1] = [1, [2, 3], len(a)]");
+    }
+
+    #[test]
+    fn test_method_calls() {
+        let stmt = parse_stmt("a.b.c(1, 2).d().e;").unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        assert!(matches!(expr.data(), ast::ExprKind::Field { .. }));
+        assert_eq!(expr.to_string(), "a.b.c(1, 2).d().e");
+    }
+
+    #[test]
+    fn test_closures() {
+        let stmt = parse_stmt("let f = |x, y: i64| x + y;").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(val.to_string(), "|x, y: i64| x + y");
+
+        let stmt = parse_stmt("let f = |g: def(i64, bool): [i64], h: def()| g;").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(val.to_string(), "|g: def(i64, bool): [i64], h: def()| g");
+
+        let stmt = parse_stmt("def (x) { return x; }(1)(2);").unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        match expr.data() {
+            ast::ExprKind::Call { callee, .. } => {
+                assert!(matches!(callee.data(), ast::ExprKind::Call { .. }))
+            }
+            expr => panic!("expected call, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_match() {
+        let stmt = parse_stmt("let x = match n { 1 => a, -2 => b, _ => c };").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(
+            val.to_string(),
+            "match n {\n    1 => a,\n    -2 => b,\n    _ => c,\n}"
+        );
+
+        let stmt =
+            parse_stmt("match p { Point { x: 0, y } => y, geo::Line { from } => 1 }").unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        assert_eq!(
+            expr.to_string(),
+            "match p {\n    Point { x: 0, y } => y,\n    geo::Line { from } => 1,\n}"
+        );
+
+        assert!(parse_stmt("match x { 1 + 2 => 3 }").is_err());
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut files = Files::new();
+        let file = files.add("test", "import a; import \"../lib/b.tre\";".to_string());
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        let names = items
+            .iter()
+            .map(|item| match item.data() {
+                ast::ItemKind::Import { name, path } => (
+                    name.data().as_str(),
+                    path.as_ref().map(|path| path.data().as_str()),
+                ),
+                item => panic!("expected import, found {:?}", item),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![("a", None), ("b", Some("../lib/b.tre"))]);
+
+        let file = files.add("test", "import \"if.tre\";".to_string());
+        let err = Parser::new(&files, file).parse_items().unwrap_err();
+        assert_eq!(err.message, "invalid module name");
+
+        let stmt = parse_stmt("a::b(geo::Point { x: 1 });").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => assert_eq!(expr.to_string(), "a::b(geo::Point { x: 1 })"),
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut files = Files::new();
+        let file = files.add(
+            "test",
+            "pub def a() {} def b() {} pub struct C {}".to_string(),
+        );
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        let public = items
+            .iter()
+            .map(|item| match item.data() {
+                ast::ItemKind::Def { public, .. } | ast::ItemKind::Struct { public, .. } => *public,
+                item => panic!("expected def or struct, found {:?}", item),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(public, vec![true, false, true]);
+        assert_eq!(items[0].span(), Span::new(0, 14));
+
+        let file = files.add("test", "pub impl C {}".to_string());
+        let err = Parser::new(&files, file).parse_items().unwrap_err();
+        assert_eq!(err.message, "unexpected token");
+    }
+
+    #[test]
+    fn test_enums() {
+        let mut files = Files::new();
+        let file = files.add(
+            "test",
+            "pub enum Shape { Circle(f64), Rect(f64, f64), Empty, }".to_string(),
+        );
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        match items[0].data() {
+            ast::ItemKind::Enum {
+                name,
+                public,
+                variants,
+            } => {
+                assert_eq!(name.data(), "Shape");
+                assert!(public);
+                let variants = variants
+                    .iter()
+                    .map(|variant| (variant.name.data().as_str(), variant.fields.len()))
+                    .collect::<Vec<_>>();
+                assert_eq!(variants, vec![("Circle", 1), ("Rect", 2), ("Empty", 0)]);
+            }
+            item => panic!("expected enum, found {:?}", item),
+        }
+
+        let stmt = parse_stmt(
+            "match s { Shape::Circle(r) => r, geo::Shape::Rect(1, _) => 1, Shape::Empty => 0 }",
+        )
+        .unwrap();
+        let expr = match stmt.into_inner() {
+            ast::StmtKind::Expr(expr) => expr,
+            stmt => panic!("expected expression statement, found {:?}", stmt),
+        };
+        assert_eq!(
+            expr.to_string(),
+            "match s {
+    Shape::Circle(r) => r,
+    geo::Shape::Rect(1, _) => 1,
+    Shape::Empty => 0,
+}"
+        );
+
+        let stmt = parse_stmt("let s = geo::Shape::Circle(1);").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(val.to_string(), "geo::Shape::Circle(1)");
+    }
+
+    #[test]
+    fn test_for() {
+        let stmt = parse_stmt("for i in 0..n + 1 { print(i); }").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::For { var, iter, block } => {
+                assert_eq!(var.data(), "i");
+                assert_eq!(sexp(&iter), "(.. 0 (+ n 1))");
+                assert_eq!(block.stmts.len(), 1);
+            }
+            stmt => panic!("expected for loop, found {:?}", stmt),
+        }
+
+        let stmt = parse_stmt("let r = a..=b == c;").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(sexp(&val), "(..= a (== b c))");
+
+        // The block of the loop isn't a struct literal
+        assert!(parse_stmt("for x in xs { x; }").is_ok());
+        assert!(parse_stmt("let r = 0..1..2;").is_err());
+        assert!(parse_stmt("r = 0..1..2;").is_err());
+        assert!(parse_stmt("let f = |x| 0..1..2;").is_err());
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let stmt = parse_stmt("let x = if (c) { 1 } else if (d) { 2 } else { 3 };").unwrap();
+        let val = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => val,
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(val.to_string(), "if (c) { 1 } else if (d) { 2 } else { 3 }");
+
+        let stmt = parse_stmt("let y = { let a = 1; a + 1 };").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => match val.into_inner() {
+                ast::ExprKind::Block(block) => {
+                    assert_eq!(block.stmts.len(), 1);
+                    assert_eq!(block.expr.unwrap().to_string(), "a + 1");
+                }
+                val => panic!("expected block, found {:?}", val),
+            },
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+
+        // A statement that starts with a block ends after it
+        let mut files = Files::new();
+        let file = files.add("test", "{ if (c) { 1 } -1 }".to_string());
+        let mut parser = Parser::new(&files, file);
+        match parser.next_expr().unwrap().into_inner() {
+            ast::ExprKind::Block(block) => {
+                assert_eq!(block.stmts.len(), 1);
+                assert_eq!(block.expr.unwrap().to_string(), "-1");
+            }
+            expr => panic!("expected block, found {:?}", expr),
+        }
+
+        // Only the last expression of a block can leave out the semicolon
+        assert!(parse_stmt("{ 1 2 }").is_err());
+        assert!(parse_stmt("match x { 1 => { 1 } _ => 2 }").is_ok());
+    }
+
+    #[test]
+    fn test_casts() {
+        let stmt = parse_stmt("let x = -a as u8 * b as i64 + 1;").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => {
+                assert_eq!(sexp(&val), "(+ (* (as -a u8) (as b i64)) 1)")
+            }
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+        assert!(parse_stmt("let x = a as foo;").is_ok());
+        assert!(parse_stmt("let x = 1 as;").is_err());
+        assert!(parse_stmt("let x = 18446744073709551615;").is_ok());
+    }
+
+    #[test]
+    fn test_maps() {
+        let stmt = parse_stmt("let m = { \"b\": 2, \"a\": Point { x: 1 }, };").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => match val.data() {
+                ast::ExprKind::Map(entries) => {
+                    assert_eq!(entries.len(), 2);
+                    assert_eq!(entries[1].0.to_string(), "\"a\"");
+                }
+                val => panic!("expected map, found {:?}", val),
+            },
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+
+        let stmt = parse_stmt("let m = {:};").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => assert_eq!(val.to_string(), "{:}"),
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+
+        // Blocks that start with an expression are still blocks
+        let stmt = parse_stmt("let b = { a + 1; a };").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => match val.into_inner() {
+                ast::ExprKind::Block(block) => {
+                    assert_eq!(block.stmts.len(), 1);
+                    assert_eq!(block.expr.unwrap().to_string(), "a");
+                }
+                val => panic!("expected block, found {:?}", val),
+            },
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+        assert!(parse_stmt("let m = { 1: 2, 3 };").is_err());
+
+        let mut files = Files::new();
+        let file = files.add("test", "def f(m: {string: [i64]}) {}".to_string());
+        let item = Parser::new(&files, file).next_item().unwrap();
+        match item.into_inner() {
+            ast::ItemKind::Def { args, .. } => {
+                assert_eq!(args[0].ty.to_string(), "{string: [i64]}")
+            }
+            item => panic!("expected def, found {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_tuples() {
+        let stmt = parse_stmt("let (q, (r, _)) = ((a, b), (c,), (d), ());").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { pattern, val, .. } => {
+                assert_eq!(pattern.to_string(), "(q, (r, _))");
+                assert_eq!(val.to_string(), "((a, b), (c,), (d), ())");
+                match val.into_inner() {
+                    ast::ExprKind::Tuple(elems) => {
+                        assert!(matches!(elems[2].data(), ast::ExprKind::Grouping(_)))
+                    }
+                    val => panic!("expected tuple, found {:?}", val),
+                }
+            }
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+
+        let stmt = parse_stmt("let (x) = 1;").unwrap();
+        match stmt.into_inner() {
+            ast::StmtKind::Let { pattern, .. } => {
+                assert!(matches!(pattern.data(), ast::LetPatternKind::Name(_)))
+            }
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        }
+        assert!(parse_stmt("let (a, 1) = x;").is_err());
+
+        let mut files = Files::new();
+        let file = files.add(
+            "test",
+            "def f(p: (i64, (bool,), (string))): () {}".to_string(),
+        );
+        let item = Parser::new(&files, file).next_item().unwrap();
+        match item.into_inner() {
+            ast::ItemKind::Def { args, ret, .. } => {
+                assert_eq!(args[0].ty.to_string(), "(i64, (bool,), string)");
+                assert_eq!(ret.unwrap().to_string(), "()");
+            }
+            item => panic!("expected def, found {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_format_strings() {
+        let src = "let s = f\"hi {name}, {count + 1} new {{}}\";";
+        let stmt = parse_stmt(src).unwrap();
+        let parts = match stmt.into_inner() {
+            ast::StmtKind::Let { val, .. } => match val.into_inner() {
+                ast::ExprKind::FormatString(parts) => parts,
+                val => panic!("expected format string, found {:?}", val),
+            },
+            stmt => panic!("expected let statement, found {:?}", stmt),
+        };
+        assert_eq!(parts.len(), 5);
+        assert_eq!(parts[0], ast::FormatPart::Literal("hi ".to_string()));
+        assert_eq!(parts[4], ast::FormatPart::Literal(" new {}".to_string()));
+        match &parts[3] {
+            ast::FormatPart::Expr(expr) => {
+                assert_eq!(expr.to_string(), "count + 1");
+                assert_eq!(expr.span().index(src), "count + 1");
+            }
+            part => panic!("expected expression, found {:?}", part),
+        }
+
+        let mut files = Files::new();
+        let src = "f\"a {x +} b\"";
+        let file = files.add("test", src.to_string());
+        let err = Parser::new(&files, file).next_expr().unwrap_err();
+        assert_eq!(err.message, "expected expression");
+        assert_eq!(err.labels[0].range, 7..8);
+
+        let errors = ["f\"{a\"", "f\"a}\"", "f\"{a b}\"", "f\"{}\""]
+            .iter()
+            .map(|src| {
+                let file = files.add("test", src.to_string());
+                Parser::new(&files, file).next_expr().unwrap_err().message
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "unclosed interpolation",
+                "unmatched `}` in format string",
+                "unexpected token",
+                "expected expression",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consts() {
+        let mut files = Files::new();
+        let file = files.add("test", "pub const LIMIT: i64 = 10 * 1024;".to_string());
+        let item = Parser::new(&files, file).next_item().unwrap();
+        match item.into_inner() {
+            ast::ItemKind::Const {
+                name,
+                public,
+                ty,
+                val,
+            } => {
+                assert_eq!(name.data(), "LIMIT");
+                assert!(public);
+                assert_eq!(ty.to_string(), "i64");
+                assert_eq!(val.to_string(), "10 * 1024");
+            }
+            item => panic!("expected const, found {:?}", item),
+        }
+
+        let file = files.add("test", "const LIMIT = 10;".to_string());
+        assert!(Parser::new(&files, file).next_item().is_err());
+    }
+
+    fn parse_program(src: &str) -> (Vec<ast::Item>, Vec<Diagnostic>) {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        Parser::new(&files, file).parse_program()
+    }
+
+    #[test]
+    fn test_unclosed_interpolation() {
+        // The format string runs to the end of the file, so the missing `)` and `}`
+        // aren't reported as well.
+        let (_, errors) = parse_program("def main() {\n    print(f\"a {x\");\n}\n");
+        let errors: Vec<_> = errors.iter().map(|err| err.message.as_str()).collect();
+        assert_eq!(errors, ["unclosed interpolation"]);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // The limits are meant for the 8 MiB stack of a main thread, which is more
+        // than a test thread has.
+        let thread = std::thread::Builder::new().stack_size(8 * 1024 * 1024);
+        thread.spawn(nesting_limit).unwrap().join().unwrap();
+    }
+
+    fn nesting_limit() {
+        let nested = |open: &str, close: &str, n: usize| {
+            let src = format!("def main() {{ {}1{}; }}", open.repeat(n), close.repeat(n));
+            let (_, errors) = parse_program(&src);
+            errors
+                .iter()
+                .map(|err| err.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(nested("(", ")", MAX_NESTING - 1).is_empty());
+        assert_eq!(nested("(", ")", 5000), ["expression is nested too deeply"]);
+        assert_eq!(nested("- ", "", 20000), ["expression is nested too deeply"]);
+        assert_eq!(
+            nested("{ ", " }", 5000),
+            ["expression is nested too deeply"]
+        );
+        // Chains of operators only count towards `MAX_DEPTH`.
+        assert!(nested("", " + 1", MAX_DEPTH - 10).is_empty());
+        assert!(nested("", ".x", MAX_DEPTH - 10).is_empty());
+        assert_eq!(nested("", " + 1", 20000), ["expression is too long"]);
+        assert_eq!(nested("", "[0]", 20000), ["expression is too long"]);
+        // Operators also move the operands before them deeper into the syntax tree.
+        let chain = " + 1".repeat(MAX_DEPTH / 4);
+        assert!(nested("(", &format!("{})", chain), 2).is_empty());
+        assert_eq!(
+            nested("(", &format!("{})", chain), 4),
+            ["expression is too long"]
+        );
+
+        let src = format!(
+            "def f(x: {}i64{}) {{}}",
+            "(".repeat(5000),
+            ",)".repeat(5000)
+        );
+        let (_, errors) = parse_program(&src);
+        assert_eq!(errors[0].message, "expression is nested too deeply");
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let src = "def main() {
+                let x = ;
+                print(x);
+                let y = (1 + ;
+                while (true) { break; }
+                let m = { \"a\": }; x
+            }
+            struct Point { x: , y: i64 }
+            def f(): i64 { 1 }";
+        let (items, errors) = parse_program(src);
+        let errors = errors
+            .iter()
+            .map(|err| (err.message.as_str(), &src[err.labels[0].range.clone()]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                ("unexpected token", ";"),
+                ("unexpected token", ";"),
+                ("unexpected token", "}"),
+                ("unexpected token", ","),
+            ]
+        );
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[1].data(), ast::ItemKind::Error));
+        assert_eq!(items[1].span().index(src), "struct Point { x: , y: i64 }");
+        assert!(matches!(items[2].data(), ast::ItemKind::Def { .. }));
+        let body = match items[0].data() {
+            ast::ItemKind::Def { body, .. } => body,
+            item => panic!("expected def, found {:?}", item),
+        };
+        let stmts = body
+            .stmts
+            .iter()
+            .map(|stmt| match stmt.data() {
+                ast::StmtKind::Error => Some(stmt.span().index(src)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stmts,
+            vec![
+                Some("let x = ;"),
+                None,
+                Some("let y = (1 + ;"),
+                None,
+                Some("let m = { \"a\": };"),
+            ]
+        );
+        assert_eq!(body.expr.as_ref().unwrap().to_string(), "x");
+    }
+
+    #[test]
+    fn test_error_recovery_terminates() {
+        let sources = [
+            "def",
+            "pub",
+            "pub pub def f() {}",
+            "} } def f() {}",
+            "let x = 1;",
+            "def f() { let",
+            "def f() { { { }",
+            "def f() { } } }",
+            "def f() { if (x) { } else 1 }",
+            "impl A { def f() {} x def g() {} }",
+            "def f() { match x { 1 => , } }",
+            "def f() { f\"{\" }",
+        ];
+        for src in &sources {
+            let (_, errors) = parse_program(src);
+            assert!(!errors.is_empty(), "{:?} has no errors", src);
+        }
+
+        let (items, errors) = parse_program("def f() { let } def g() {}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(items.len(), 2);
+    }
+
+    /// Writes the operators of `expr` in prefix form, like `(+ a (* b c))`,
+    /// to check how it was parsed.
+    fn sexp(expr: &ast::ExprKind) -> String {
+        match expr {
+            ast::ExprKind::Binary { left, op, right } => {
+                format!("({} {} {})", op, sexp(left), sexp(right))
+            }
+            ast::ExprKind::Unary { op, expr } => format!("{}{}", op, sexp(expr)),
+            ast::ExprKind::Cast { expr, ty } => format!("(as {} {})", sexp(expr), ty),
+            ast::ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let op = if *inclusive { "..=" } else { ".." };
+                format!("({} {} {})", op, sexp(start), sexp(end))
+            }
+            ast::ExprKind::Assign { target, val } => format!("{} = {}", sexp(target), sexp(val)),
+            ast::ExprKind::Grouping(expr) => format!("({})", sexp(expr)),
+            ast::ExprKind::Closure {
+                args,
+                body: ast::ClosureBody::Expr(body),
+            } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.name.to_string())
+                    .collect::<Vec<_>>();
+                format!("|{}| {}", args.join(", "), sexp(body))
+            }
+            expr => expr.to_string(),
+        }
+    }
+
+    fn parse_expr(src: &str) -> Result<ast::Expr> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let mut parser = Parser::new(&files, file);
+        let expr = parser.next_expr()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => panic!("`{}` wasn't parsed completely, found {:?}", src, token),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+            ("1 * 2 + 3", "(+ (* 1 2) 3)"),
+            ("7 % 3 * 2 / 1", "(/ (* (% 7 3) 2) 1)"),
+            ("a * b ** c", "(* a (** b c))"),
+            ("-2 ** 2", "-(** 2 2)"),
+            ("2 ** -1", "(** 2 -1)"),
+            ("-f(x) ** 2", "-(** f(x) 2)"),
+            ("1 << 2 + 3", "(<< 1 (+ 2 3))"),
+            ("a & b << c", "(& a (<< b c))"),
+            ("a ^ b & c", "(^ a (& b c))"),
+            ("a | b ^ c", "(| a (^ b c))"),
+            ("a < b | c", "(< a (| b c))"),
+            ("a == b < c", "(== a (< b c))"),
+            ("a && b == c", "(&& a (== b c))"),
+            ("a || b && c", "(|| a (&& b c))"),
+            ("a..b || c", "(.. a (|| b c))"),
+            ("a || b..=c", "(..= (|| a b) c)"),
+            ("x = a..b", "x = (.. a b)"),
+            ("x = a || b", "x = (|| a b)"),
+            ("-x as u8 + 1", "(+ (as -x u8) 1)"),
+            ("a + b as i64", "(+ a (as b i64))"),
+            ("a ** b as u8", "(as (** a b) u8)"),
+            ("!a.b(c)[d].e", "!a.b(c)[d].e"),
+            ("(a + b) * c", "(* ((+ a b)) c)"),
+        ];
+        for (src, expected) in &cases {
+            assert_eq!(sexp(&parse_expr(src).unwrap()), *expected, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_associativity() {
+        let cases = [
+            ("a - b - c", "(- (- a b) c)"),
+            ("a / b / c", "(/ (/ a b) c)"),
+            ("a % b % c", "(% (% a b) c)"),
+            ("a ** b ** c", "(** a (** b c))"),
+            ("a << b >> c", "(>> (<< a b) c)"),
+            ("a & b & c", "(& (& a b) c)"),
+            ("a ^ b ^ c", "(^ (^ a b) c)"),
+            ("a | b | c", "(| (| a b) c)"),
+            ("a < b < c", "(< (< a b) c)"),
+            ("a == b != c", "(!= (== a b) c)"),
+            ("a && b && c", "(&& (&& a b) c)"),
+            ("a || b || c", "(|| (|| a b) c)"),
+            ("x as i64 as u8", "(as (as x i64) u8)"),
+            ("--x", "--x"),
+        ];
+        for (src, expected) in &cases {
+            assert_eq!(sexp(&parse_expr(src).unwrap()), *expected, "{}", src);
+        }
+
+        // Assignment is right associative.
+        match parse_expr("a = b = 1 + 2").unwrap().into_inner() {
+            ast::ExprKind::Assign { target, val } => {
+                assert_eq!(target.to_string(), "a");
+                assert!(matches!(val.data(), ast::ExprKind::Assign { .. }));
+                assert_eq!(sexp(&val), "b = (+ 1 2)");
+            }
+            expr => panic!("expected assignment, found {:?}", expr),
+        }
+
+        let mut files = Files::new();
+        let file = files.add("test", "a..b..c".to_string());
+        let mut parser = Parser::new(&files, file);
+        assert_eq!(sexp(&parser.next_expr().unwrap()), "(.. a b)");
+        assert!(parser.next_is(TokenType::DotDot));
+        assert_eq!(
+            parse_expr("a + b = c").unwrap_err().message,
+            "invalid assignment target"
+        );
+    }
+
+    #[test]
+    fn test_pipes() {
+        assert_eq!(sexp(&parse_expr("|x| x | 1").unwrap()), "|x| (| x 1)");
+        assert_eq!(sexp(&parse_expr("|| a || b").unwrap()), "|| (|| a b)");
+        assert_eq!(sexp(&parse_expr("a | |x| x").unwrap()), "(| a |x| x)");
+    }
+
+    #[test]
+    fn test_operator_conversions() {
+        assert_eq!(
+            ast::BinaryOperation::try_from(TokenType::Percent),
+            Ok(ast::BinaryOperation::Rem)
+        );
+        assert_eq!(
+            ast::BinaryOperation::try_from(TokenType::DotDot),
+            Err(TokenType::DotDot)
+        );
+        assert_eq!(
+            ast::UnaryOperation::try_from(&TokenType::Bang),
+            Ok(ast::UnaryOperation::Not)
+        );
+        assert_eq!(
+            ast::UnaryOperation::try_from(TokenType::Plus),
+            Err(TokenType::Plus)
+        );
+
+        // Malformed input is reported as an error instead of crashing the parser.
+        for src in &["1 +", "-", "a.", "a as", "x[1", "(1 ** )", "true && ||"] {
+            assert!(parse_expr(src).is_err(), "{}", src);
         }
     }
 }
@@ -0,0 +1,862 @@
+//! Prints the AST as Tre source, which is what the `Display` implementations of
+//! items, statements and expressions use.
+//!
+//! The parser keeps parentheses as `Grouping` expressions, so the printer only adds
+//! parentheses where an expression couldn't be parsed back otherwise, e.g. for
+//! `(a + b) * c` if it was built without a `Grouping`. Parsing the output gives
+//! back the same AST, apart from the spans.
+
+use super::{ast::*, parser, TokenType};
+use std::fmt::{self, Write};
+
+const INDENT: &str = "    ";
+
+pub(crate) struct Printer<W> {
+    out: W,
+    indent: usize,
+}
+
+impl<W: Write> Printer<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self { out, indent: 0 }
+    }
+
+    pub(crate) fn item(&mut self, item: &ItemKind) -> fmt::Result {
+        match item {
+            ItemKind::Def {
+                name,
+                public,
+                args,
+                ret,
+                body,
+            } => {
+                self.public(*public)?;
+                write!(self.out, "def {}(", name)?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    write!(self.out, "{}: {}", arg.name, arg.ty)?;
+                }
+                write!(self.out, ")")?;
+                if let Some(ret) = ret {
+                    write!(self.out, ": {}", ret)?;
+                }
+                write!(self.out, " ")?;
+                self.block(body, false)
+            }
+            ItemKind::Struct {
+                name,
+                public,
+                fields,
+            } => {
+                self.public(*public)?;
+                write!(self.out, "struct {} ", name)?;
+                self.body(fields, |this, field| {
+                    write!(this.out, "{}: {},", field.name, field.ty)
+                })
+            }
+            ItemKind::Enum {
+                name,
+                public,
+                variants,
+            } => {
+                self.public(*public)?;
+                write!(self.out, "enum {} ", name)?;
+                self.body(variants, |this, variant| {
+                    write!(this.out, "{}", variant.name)?;
+                    if !variant.fields.is_empty() {
+                        write!(this.out, "(")?;
+                        this.list(&variant.fields, |this, ty| write!(this.out, "{}", ty))?;
+                        write!(this.out, ")")?;
+                    }
+                    write!(this.out, ",")
+                })
+            }
+            ItemKind::Const {
+                name,
+                public,
+                ty,
+                val,
+            } => {
+                self.public(*public)?;
+                write!(self.out, "const {}: {} = ", name, ty)?;
+                self.expr(val)?;
+                write!(self.out, ";")
+            }
+            ItemKind::Impl { ty, methods } => {
+                write!(self.out, "impl {} ", ty)?;
+                if methods.is_empty() {
+                    return write!(self.out, "{{}}");
+                }
+                write!(self.out, "{{")?;
+                self.indent += 1;
+                for (idx, method) in methods.iter().enumerate() {
+                    if idx > 0 {
+                        writeln!(self.out)?;
+                    }
+                    self.newline()?;
+                    self.item(method)?;
+                }
+                self.indent -= 1;
+                self.newline()?;
+                write!(self.out, "}}")
+            }
+            ItemKind::Import { name, path } => match path {
+                Some(path) => write!(self.out, "import \"{}\";", path),
+                None => write!(self.out, "import {};", name),
+            },
+            ItemKind::Error => write!(self.out, "# syntax error"),
+        }
+    }
+
+    fn public(&mut self, public: bool) -> fmt::Result {
+        match public {
+            true => write!(self.out, "pub "),
+            false => Ok(()),
+        }
+    }
+
+    /// Writes items in braces on their own lines, like the fields of a struct or the arms of a match.
+    fn body<T>(
+        &mut self,
+        items: &[T],
+        mut f: impl FnMut(&mut Self, &T) -> fmt::Result,
+    ) -> fmt::Result {
+        if items.is_empty() {
+            return write!(self.out, "{{}}");
+        }
+        write!(self.out, "{{")?;
+        self.indent += 1;
+        for item in items {
+            self.newline()?;
+            f(self, item)?;
+        }
+        self.indent -= 1;
+        self.newline()?;
+        write!(self.out, "}}")
+    }
+
+    /// Writes a statement, where `semicolon` forces a semicolon after expressions
+    /// that end with a block.
+    pub(crate) fn stmt(&mut self, stmt: &StmtKind, semicolon: bool) -> fmt::Result {
+        match stmt {
+            StmtKind::Let {
+                pattern,
+                mutable,
+                ty,
+                val,
+            } => {
+                let mutable = if *mutable { "mut " } else { "" };
+                write!(self.out, "let {}{}", mutable, pattern)?;
+                if let Some(ty) = ty {
+                    write!(self.out, ": {}", ty)?;
+                }
+                write!(self.out, " = ")?;
+                self.expr(val)?;
+                write!(self.out, ";")
+            }
+            StmtKind::While { cond, block } => {
+                write!(self.out, "while (")?;
+                self.expr(cond)?;
+                write!(self.out, ") ")?;
+                self.block(block, false)
+            }
+            StmtKind::For { var, iter, block } => {
+                write!(self.out, "for {} in ", var)?;
+                self.restricted_expr(iter)?;
+                write!(self.out, " ")?;
+                self.block(block, false)
+            }
+            StmtKind::Loop(block) => {
+                write!(self.out, "loop ")?;
+                self.block(block, false)
+            }
+            StmtKind::Break => write!(self.out, "break;"),
+            StmtKind::Continue => write!(self.out, "continue;"),
+            StmtKind::Return(None) => write!(self.out, "return;"),
+            StmtKind::Return(Some(val)) => {
+                write!(self.out, "return ")?;
+                self.expr(val)?;
+                write!(self.out, ";")
+            }
+            StmtKind::Expr(expr) => {
+                self.stmt_expr(expr)?;
+                if semicolon || !expr.is_block_like() {
+                    write!(self.out, ";")?;
+                }
+                Ok(())
+            }
+            StmtKind::Error => write!(self.out, "# syntax error"),
+        }
+    }
+
+    /// Writes a block, on a single line if `inline` is set and it only contains
+    /// an expression that fits on one line, like `{ x + 1 }`.
+    pub(crate) fn block(&mut self, block: &Block, inline: bool) -> fmt::Result {
+        if block.stmts.is_empty() {
+            match &block.expr {
+                None => return write!(self.out, "{{}}"),
+                Some(expr) if inline => {
+                    let mut printer = Printer {
+                        out: String::new(),
+                        indent: self.indent,
+                    };
+                    printer.stmt_expr(expr)?;
+                    if !printer.out.contains('\n') {
+                        return write!(self.out, "{{ {} }}", printer.out);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        write!(self.out, "{{")?;
+        self.indent += 1;
+        for (idx, stmt) in block.stmts.iter().enumerate() {
+            self.newline()?;
+            // An expression that ends with a block at the end would be the value of the block.
+            let last = idx + 1 == block.stmts.len() && block.expr.is_none();
+            self.stmt(stmt, last)?;
+        }
+        if let Some(expr) = &block.expr {
+            self.newline()?;
+            self.stmt_expr(expr)?;
+        }
+        self.indent -= 1;
+        self.newline()?;
+        write!(self.out, "}}")
+    }
+
+    fn newline(&mut self) -> fmt::Result {
+        writeln!(self.out)?;
+        for _ in 0..self.indent {
+            write!(self.out, "{}", INDENT)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn expr(&mut self, expr: &ExprKind) -> fmt::Result {
+        let parent = expr;
+        match expr {
+            ExprKind::Literal(val) => write!(self.out, "{}", val),
+            ExprKind::FormatString(parts) => {
+                write!(self.out, "f\"")?;
+                for part in parts {
+                    match part {
+                        FormatPart::Literal(s) => {
+                            write!(self.out, "{}", s.replace('{', "{{").replace('}', "}}"))?
+                        }
+                        FormatPart::Expr(expr) => {
+                            write!(self.out, "{{")?;
+                            self.expr(expr)?;
+                            write!(self.out, "}}")?;
+                        }
+                    }
+                }
+                write!(self.out, "\"")
+            }
+            ExprKind::Variable(var) => write!(self.out, "{}", var),
+            ExprKind::Path(path) => write!(self.out, "{}", path),
+            ExprKind::Binary { left, op, right } => {
+                let (left_bp, right_bp) = infix_bp(op.into());
+                self.left_operand(parent, left, left_bp)?;
+                write!(self.out, " {} ", op)?;
+                self.right_operand(right, right_bp)
+            }
+            ExprKind::Unary { op, expr } => {
+                write!(self.out, "{}", op)?;
+                self.right_operand(expr, prefix_bp())
+            }
+            ExprKind::Call { callee, args } => {
+                self.left_operand(parent, callee, postfix_bp(TokenType::LeftParen))?;
+                self.args(args)
+            }
+            ExprKind::Closure { args, body } => {
+                write!(self.out, "|")?;
+                self.list(args, |this, arg| {
+                    write!(this.out, "{}", arg.name)?;
+                    match &arg.ty {
+                        Some(ty) => write!(this.out, ": {}", ty),
+                        None => Ok(()),
+                    }
+                })?;
+                write!(self.out, "| ")?;
+                match body {
+                    ClosureBody::Block(block) => self.block(block, true),
+                    // A body that starts with `{` would be a block.
+                    ClosureBody::Expr(expr) => match leftmost(expr) {
+                        ExprKind::Block(_) | ExprKind::Map(_) => self.parenthesized(expr),
+                        _ => self.expr(expr),
+                    },
+                }
+            }
+            ExprKind::Array(elems) => {
+                write!(self.out, "[")?;
+                self.list(elems, |this, elem| this.expr(elem))?;
+                write!(self.out, "]")
+            }
+            ExprKind::Tuple(elems) => {
+                write!(self.out, "(")?;
+                self.list(elems, |this, elem| this.expr(elem))?;
+                if elems.len() == 1 {
+                    write!(self.out, ",")?;
+                }
+                write!(self.out, ")")
+            }
+            ExprKind::Map(entries) if entries.is_empty() => write!(self.out, "{{:}}"),
+            ExprKind::Map(entries) => {
+                write!(self.out, "{{ ")?;
+                for (idx, (key, val)) in entries.iter().enumerate() {
+                    // The first key is parsed like the start of a block.
+                    if idx == 0 {
+                        self.stmt_expr(key)?;
+                    } else {
+                        write!(self.out, ", ")?;
+                        self.expr(key)?;
+                    }
+                    write!(self.out, ": ")?;
+                    self.expr(val)?;
+                }
+                write!(self.out, " }}")
+            }
+            ExprKind::Index { expr, index } => {
+                self.left_operand(parent, expr, postfix_bp(TokenType::LeftBracket))?;
+                write!(self.out, "[")?;
+                self.expr(index)?;
+                write!(self.out, "]")
+            }
+            ExprKind::StructLiteral { name, fields } if fields.is_empty() => {
+                write!(self.out, "{} {{}}", name)
+            }
+            ExprKind::StructLiteral { name, fields } => {
+                write!(self.out, "{} {{ ", name)?;
+                self.list(fields, |this, field| {
+                    write!(this.out, "{}: ", field.name)?;
+                    this.expr(&field.val)
+                })?;
+                write!(self.out, " }}")
+            }
+            ExprKind::Field { expr, name } => {
+                self.left_operand(parent, expr, postfix_bp(TokenType::Dot))?;
+                write!(self.out, ".{}", name)
+            }
+            ExprKind::MethodCall {
+                receiver,
+                name,
+                args,
+            } => {
+                self.left_operand(parent, receiver, postfix_bp(TokenType::Dot))?;
+                write!(self.out, ".{}", name)?;
+                self.args(args)
+            }
+            ExprKind::Assign { target, val } => {
+                let (left_bp, right_bp) = infix_bp(TokenType::Equal);
+                self.left_operand(parent, target, left_bp)?;
+                write!(self.out, " = ")?;
+                self.right_operand(val, right_bp)
+            }
+            ExprKind::Cast { expr, ty } => {
+                self.left_operand(parent, expr, postfix_bp(TokenType::As))?;
+                write!(self.out, " as {}", ty)
+            }
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let (left_bp, right_bp) = infix_bp(TokenType::DotDot);
+                self.left_operand(parent, start, left_bp)?;
+                write!(self.out, "{}", if *inclusive { "..=" } else { ".." })?;
+                self.right_operand(end, right_bp)
+            }
+            ExprKind::Match { expr, arms } => {
+                write!(self.out, "match ")?;
+                self.restricted_expr(expr)?;
+                write!(self.out, " ")?;
+                self.body(arms, |this, arm| {
+                    this.match_arm(arm)?;
+                    write!(this.out, ",")
+                })
+            }
+            ExprKind::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                write!(self.out, "if (")?;
+                self.expr(cond)?;
+                write!(self.out, ") ")?;
+                self.block(then, true)?;
+                let otherwise = match otherwise {
+                    Some(otherwise) => otherwise,
+                    None => return Ok(()),
+                };
+                write!(self.out, " else ")?;
+                match (&otherwise.stmts[..], &otherwise.expr) {
+                    ([], Some(expr)) if matches!(expr.data(), ExprKind::If { .. }) => {
+                        self.expr(expr)
+                    }
+                    _ => self.block(otherwise, true),
+                }
+            }
+            ExprKind::Block(block) => self.block(block, true),
+            ExprKind::Grouping(expr) => self.parenthesized(expr),
+        }
+    }
+
+    pub(crate) fn match_arm(&mut self, arm: &MatchArm) -> fmt::Result {
+        write!(self.out, "{} => ", arm.pattern)?;
+        self.expr(&arm.body)
+    }
+
+    /// Writes an expression at the start of a statement, which ends after a leading
+    /// `if`, `match` or block, so `(if (c) { a } else { b }) + 1` needs the parentheses.
+    fn stmt_expr(&mut self, expr: &ExprKind) -> fmt::Result {
+        let first = leftmost(expr);
+        let starts_with_block = first.is_block_like() || matches!(first, ExprKind::Map(_));
+        if starts_with_block && !std::ptr::eq(first, expr) {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    /// Writes an expression in front of a block, where `Name {` would start the block
+    /// instead of a struct literal.
+    fn restricted_expr(&mut self, expr: &ExprKind) -> fmt::Result {
+        if has_struct_literal(expr) {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    /// Writes the operand in front of the operator of `parent`, whose left binding power is `bp`.
+    fn left_operand(&mut self, parent: &ExprKind, expr: &ExprKind, bp: u8) -> fmt::Result {
+        if needs_parens(parent, expr, bp) {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    /// Writes the right operand of an operator whose right binding power is `bp`.
+    fn right_operand(&mut self, expr: &ExprKind, bp: u8) -> fmt::Result {
+        if left_bp(expr) < bp {
+            self.parenthesized(expr)
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    fn parenthesized(&mut self, expr: &ExprKind) -> fmt::Result {
+        write!(self.out, "(")?;
+        self.expr(expr)?;
+        write!(self.out, ")")
+    }
+
+    fn args(&mut self, args: &[Expr]) -> fmt::Result {
+        write!(self.out, "(")?;
+        self.list(args, |this, arg| this.expr(arg))?;
+        write!(self.out, ")")
+    }
+
+    fn list<T>(
+        &mut self,
+        items: &[T],
+        mut f: impl FnMut(&mut Self, &T) -> fmt::Result,
+    ) -> fmt::Result {
+        for (idx, item) in items.iter().enumerate() {
+            if idx > 0 {
+                write!(self.out, ", ")?;
+            }
+            f(self, item)?;
+        }
+        Ok(())
+    }
+}
+
+/// The binding powers of the parser, see [`parser::infix_binding_power`].
+/// The fallbacks are never used, because every operator that is passed has one.
+fn infix_bp(ty: TokenType) -> (u8, u8) {
+    parser::infix_binding_power(ty).unwrap_or((0, 0))
+}
+
+fn prefix_bp() -> u8 {
+    parser::prefix_binding_power(TokenType::Minus).unwrap_or(0)
+}
+
+fn postfix_bp(ty: TokenType) -> u8 {
+    parser::postfix_binding_power(ty).unwrap_or(0)
+}
+
+/// The operand in front of the operator of `expr`, and the left binding power of the operator.
+fn first_operand(expr: &ExprKind) -> Option<(&ExprKind, u8)> {
+    let (operand, bp) = match expr {
+        ExprKind::Binary { left, op, .. } => (left, infix_bp(op.into()).0),
+        ExprKind::Assign { target, .. } => (target, infix_bp(TokenType::Equal).0),
+        ExprKind::Range { start, .. } => (start, infix_bp(TokenType::DotDot).0),
+        ExprKind::Cast { expr, .. } => (expr, postfix_bp(TokenType::As)),
+        ExprKind::Call { callee: expr, .. }
+        | ExprKind::Index { expr, .. }
+        | ExprKind::Field { expr, .. }
+        | ExprKind::MethodCall { receiver: expr, .. } => (expr, postfix_bp(TokenType::Dot)),
+        _ => return None,
+    };
+    Some((operand.data(), bp))
+}
+
+/// The operand after the operator of `expr`, and the right binding power of the operator.
+fn last_operand(expr: &ExprKind) -> Option<(&ExprKind, u8)> {
+    let (operand, bp) = match expr {
+        ExprKind::Binary { op, right, .. } => (right, infix_bp(op.into()).1),
+        ExprKind::Assign { val, .. } => (val, infix_bp(TokenType::Equal).1),
+        ExprKind::Range { end, .. } => (end, infix_bp(TokenType::DotDot).1),
+        ExprKind::Unary { expr, .. } => (expr, prefix_bp()),
+        _ => return None,
+    };
+    Some((operand.data(), bp))
+}
+
+/// Whether `operand` needs parentheses in front of the operator of `parent`,
+/// whose left binding power is `bp`.
+fn needs_parens(parent: &ExprKind, operand: &ExprKind, bp: u8) -> bool {
+    match (parent, operand) {
+        // `a.b(c)` would be a method call.
+        (ExprKind::Call { .. }, ExprKind::Field { .. }) => true,
+        // Ranges can't be chained.
+        (ExprKind::Range { .. }, ExprKind::Range { .. }) => true,
+        _ => right_bp(operand) <= bp,
+    }
+}
+
+/// The binding power of the loosest operator at the start of `expr`, which is
+/// `u8::MAX` if the expression doesn't start with an operand. An operator can
+/// take `expr` as its right operand if this isn't lower than its right binding power.
+fn left_bp(expr: &ExprKind) -> u8 {
+    match first_operand(expr) {
+        Some((operand, bp)) if !needs_parens(expr, operand, bp) => bp.min(left_bp(operand)),
+        Some((_, bp)) => bp,
+        None => u8::MAX,
+    }
+}
+
+/// The binding power of the loosest operator at the end of `expr`, which is
+/// `u8::MAX` if the expression doesn't end with an operand. An operator can
+/// take `expr` as its left operand if its left binding power is lower than this.
+fn right_bp(expr: &ExprKind) -> u8 {
+    // The body of the closure takes everything after it.
+    if let ExprKind::Closure {
+        body: ClosureBody::Expr(_),
+        ..
+    } = expr
+    {
+        return 0;
+    }
+    match last_operand(expr) {
+        Some((operand, bp)) if left_bp(operand) >= bp => bp.min(right_bp(operand)),
+        Some((_, bp)) => bp,
+        None => u8::MAX,
+    }
+}
+
+/// Returns the expression that is written first in `expr`, e.g. `a` in `a.b + c`.
+fn leftmost(expr: &ExprKind) -> &ExprKind {
+    match first_operand(expr) {
+        Some((operand, bp)) if !needs_parens(expr, operand, bp) => leftmost(operand),
+        _ => expr,
+    }
+}
+
+/// Whether `expr` contains a struct literal that isn't surrounded by parentheses
+/// or brackets, which would be parsed differently in front of a block.
+fn has_struct_literal(expr: &ExprKind) -> bool {
+    match expr {
+        ExprKind::StructLiteral { .. } => true,
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Assign {
+            target: left,
+            val: right,
+        }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+            ..
+        }
+        | ExprKind::Index {
+            expr: left,
+            index: right,
+        } => has_struct_literal(left) || has_struct_literal(right),
+        ExprKind::Unary { expr, .. }
+        | ExprKind::Cast { expr, .. }
+        | ExprKind::Field { expr, .. }
+        | ExprKind::Call { callee: expr, .. }
+        | ExprKind::MethodCall { receiver: expr, .. }
+        | ExprKind::Closure {
+            body: ClosureBody::Expr(expr),
+            ..
+        } => has_struct_literal(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Files, syntax::Parser, Span};
+    use std::fs;
+
+    fn parse(src: &str) -> Vec<Item> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let (items, diagnostics) = Parser::new(&files, file).parse_program();
+        assert!(diagnostics.is_empty(), "{:?}\n{}", diagnostics, src);
+        items
+    }
+
+    fn print(items: &[Item]) -> String {
+        let items = items
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<_>>();
+        items.join("\n\n")
+    }
+
+    /// Removes the spans from the debug output of a syntax tree, so that trees
+    /// can be compared by their structure.
+    fn without_spans(debug: &str) -> String {
+        let mut out = String::new();
+        let mut rest = debug;
+        while let Some(start) = rest.find("Span(Span {") {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find("})")
+                .map_or(rest.len(), |end| start + end + 2);
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn assert_round_trip(src: &str) {
+        let items = parse(src);
+        let printed = print(&items);
+        let reparsed = parse(&printed);
+        assert_eq!(
+            without_spans(&format!("{:?}", reparsed)),
+            without_spans(&format!("{:?}", items)),
+            "{}",
+            printed
+        );
+        assert_eq!(print(&reparsed), printed);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(
+            r#"
+import geo;
+import "util/math.tre";
+pub const LIMIT: i64 = 10 * (1024 + 1);
+struct Empty {}
+pub struct Point { x: i64, y: {string: [(i64, bool)]} }
+enum Shape { Circle(i64), Rect(i64, i64), Empty }
+impl Point {
+    def norm(self: Point): i64 { self.x ** 2 + self.y["a"][0] }
+    pub def apply(self: Point, f: def(i64): i64) { f(self.x); }
+}
+def main(): () {
+    let mut (a, (b, _)) = (1, (f"x {a + 1} {{y}} {"s"}", ()));
+    let c = -a ** 2 - -(b as u8) as i64 % 3 << 1 & 2 ^ 3 | 4;
+    let d = !(a < b) && a >= b || a != b == true;
+    a = b = (c);
+    let m = { "k": [1, 2,], "v": Point { x: 1, y: {:} } };
+    let t = (a,);
+    for i in 0..=n - 1 { if (i > 2) { break; } else if (i < 0) { continue; } }
+    for p in (Point { x: 1, y: 2 }).items() {}
+    while (true) { loop { return; } }
+    match (Point { x: 1, y: 2 }).x { -1 => { 1 } 2 => if (c) { 1 } else { 2 }, _ => 3 }
+    match s { Shape::Circle(r) => r, geo::Shape::Rect(1, _) => 1, Point { x: 0, y } => y, _ => 0 };
+    if (c) { a } else { b };
+    { 1 };
+    (if (c) { 1 } else { 2 }) + 1;
+    ({ x }).y;
+    ({ "a": 1 })["a"];
+    let g = |x, y: i64| x + y;
+    let h = def (x) { return x; };
+    h(|| { 1 })(2);
+    (|x| x)(1);
+    (a.f)(1);
+    (a..b).start;
+    return |x| x;
+}
+def tail() { if (c) { 1 } }
+def tail_stmt() { if (c) { 1 }; }
+"#,
+        );
+
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("tre") {
+                assert_round_trip(&fs::read_to_string(&path).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_output() {
+        let items =
+            parse("def f(a: i64): i64 { let x = if (a) { 1 } else { 2 }; g(|y| { y }); x }");
+        assert_eq!(
+            print(&items),
+            "\
+def f(a: i64): i64 {
+    let x = if (a) { 1 } else { 2 };
+    g(|y| { y });
+    x
+}"
+        );
+        let items = parse("enum E { A(i64), B } impl E { def a() {} def b() {} }");
+        assert_eq!(
+            print(&items),
+            "enum E {\n    A(i64),\n    B,\n}\n\nimpl E {\n    def a() {}\n\n    def b() {}\n}"
+        );
+    }
+
+    fn spanned(kind: ExprKind) -> Box<Expr> {
+        Box::new(Span::default().span(kind))
+    }
+
+    fn var(name: &str) -> Box<Expr> {
+        spanned(ExprKind::Variable(Span::default().span(name.to_string())))
+    }
+
+    fn binary(left: Box<Expr>, op: BinaryOperation, right: Box<Expr>) -> Box<Expr> {
+        spanned(ExprKind::Binary { left, op, right })
+    }
+
+    #[test]
+    fn test_parentheses() {
+        // Trees that the parser only builds with a `Grouping`
+        let cases = vec![
+            (
+                binary(
+                    binary(var("a"), BinaryOperation::Plus, var("b")),
+                    BinaryOperation::Mul,
+                    var("c"),
+                ),
+                "(a + b) * c",
+            ),
+            (
+                binary(
+                    var("a"),
+                    BinaryOperation::Minus,
+                    binary(var("b"), BinaryOperation::Minus, var("c")),
+                ),
+                "a - (b - c)",
+            ),
+            (
+                binary(
+                    binary(var("a"), BinaryOperation::Pow, var("b")),
+                    BinaryOperation::Pow,
+                    var("c"),
+                ),
+                "(a ** b) ** c",
+            ),
+            (
+                binary(
+                    spanned(ExprKind::Unary {
+                        op: UnaryOperation::Negate,
+                        expr: var("a"),
+                    }),
+                    BinaryOperation::Pow,
+                    var("b"),
+                ),
+                "(-a) ** b",
+            ),
+            (
+                spanned(ExprKind::Unary {
+                    op: UnaryOperation::Not,
+                    expr: spanned(ExprKind::Cast {
+                        expr: var("a"),
+                        ty: Span::default().span(TypeKind::Bool),
+                    }),
+                }),
+                "!(a as bool)",
+            ),
+            (
+                spanned(ExprKind::Call {
+                    callee: spanned(ExprKind::Closure {
+                        args: vec![],
+                        body: ClosureBody::Expr(var("x")),
+                    }),
+                    args: vec![],
+                }),
+                "(|| x)()",
+            ),
+            (
+                binary(
+                    var("a"),
+                    BinaryOperation::Plus,
+                    spanned(ExprKind::Closure {
+                        args: vec![],
+                        body: ClosureBody::Expr(var("x")),
+                    }),
+                ),
+                "a + || x",
+            ),
+            (
+                spanned(ExprKind::Call {
+                    callee: spanned(ExprKind::Field {
+                        expr: var("a"),
+                        name: Span::default().span("f".to_string()),
+                    }),
+                    args: vec![],
+                }),
+                "(a.f)()",
+            ),
+            (
+                spanned(ExprKind::Range {
+                    start: spanned(ExprKind::Range {
+                        start: var("a"),
+                        end: var("b"),
+                        inclusive: false,
+                    }),
+                    end: var("c"),
+                    inclusive: true,
+                }),
+                "(a..b)..=c",
+            ),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(expr.to_string(), expected);
+            parse(&format!("def f() {{ {}; }}", expected));
+        }
+
+        let stmt = StmtKind::Expr(*binary(
+            spanned(ExprKind::Block(Block {
+                stmts: vec![],
+                expr: Some(var("a")),
+            })),
+            BinaryOperation::Plus,
+            var("b"),
+        ));
+        assert_eq!(stmt.to_string(), "({ a } + b);");
+
+        let stmt = StmtKind::For {
+            var: Span::default().span("x".to_string()),
+            iter: *spanned(ExprKind::StructLiteral {
+                name: Path {
+                    module: None,
+                    name: Span::default().span("P".to_string()),
+                    variant: None,
+                },
+                fields: vec![],
+            }),
+            block: Block::default(),
+        };
+        assert_eq!(stmt.to_string(), "for x in (P {}) {}");
+    }
+}
@@ -0,0 +1,523 @@
+//! Serializes tokens and syntax trees as S-expressions, which is what
+//! `tre ast --format sexp` and `tre tokens --format sexp` print.
+//!
+//! The S-expressions have the same structure as the JSON output, and follow
+//! these rules:
+//!
+//! - A struct is `(Name :field value ...)`, e.g. `(Block :stmts () :expr nil)`.
+//! - An enum variant is its name if it has no fields, like `Plus`, `(Name value ...)`
+//!   if its fields have no names, and `(Name :field value ...)` otherwise.
+//! - A span is `start..end`, the byte offsets of the source it covers, and is
+//!   written after the name of the value it belongs to, e.g.
+//!   `(Variable 4..5 ("x" 4..5))`. Values without a name get a list like `("x" 4..5)`.
+//! - Lists are `(a b c)`, a missing value is `nil`, and strings are quoted
+//!   with `"` and `\` escaped.
+
+use serde::{ser, Serialize};
+use std::fmt;
+
+/// The width that [`Sexp::pretty`] tries to fit lines into.
+const WIDTH: usize = 80;
+
+/// An S-expression, which is either an atom like `Plus`, `1` or `"a"`, or a list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    /// Whether the atom is a name, like `Plus` or `:field`, and not a literal.
+    fn is_name(&self) -> bool {
+        matches!(self, Sexp::Atom(atom) if atom.starts_with(|c: char| c.is_alphabetic() || c == ':'))
+    }
+
+    fn is_field(&self) -> bool {
+        matches!(self, Sexp::Atom(atom) if atom.starts_with(':'))
+    }
+
+    /// Prints the expression over multiple lines, where lists that don't fit into
+    /// the line have their elements on their own lines.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) {
+        let compact = self.to_string();
+        let items = match self {
+            Sexp::List(items) if indent + compact.len() > WIDTH => items,
+            _ => return out.push_str(&compact),
+        };
+        out.push('(');
+        let mut items = items.iter().peekable();
+        // The name and the atoms after it, like the span, stay on the first line.
+        let mut first = true;
+        while let Some(item) = items.next_if(|item| first || !item.is_field() && item.is_atom()) {
+            if !first {
+                out.push(' ');
+            }
+            item.write_pretty(out, indent + 1);
+            first = false;
+        }
+        let indent = indent + 2;
+        while let Some(item) = items.next() {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+            item.write_pretty(out, indent);
+            if item.is_field() {
+                if let Some(value) = items.next() {
+                    out.push(' ');
+                    value.write_pretty(out, indent + item.to_string().len() + 1);
+                }
+            }
+        }
+        out.push(')');
+    }
+
+    fn is_atom(&self) -> bool {
+        matches!(self, Sexp::Atom(_))
+    }
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Atom(atom) => write!(f, "{}", atom),
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Converts a value to an S-expression, following the rules of the [module](self).
+pub fn to_sexp<T: Serialize + ?Sized>(value: &T) -> Result<Sexp, Error> {
+    value.serialize(Serializer)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+fn atom(atom: impl ToString) -> Sexp {
+    Sexp::Atom(atom.to_string())
+}
+
+fn string(s: &str) -> Sexp {
+    Sexp::Atom(format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+#[derive(Debug)]
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Sexp;
+    type Error = Error;
+    type SerializeSeq = List;
+    type SerializeTuple = List;
+    type SerializeTupleStruct = List;
+    type SerializeTupleVariant = List;
+    type SerializeMap = List;
+    type SerializeStruct = Struct;
+    type SerializeStructVariant = Struct;
+
+    fn serialize_bool(self, v: bool) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Sexp, Error> {
+        Ok(atom(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Sexp, Error> {
+        Ok(string(&v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Sexp, Error> {
+        Ok(string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Sexp, Error> {
+        Ok(Sexp::List(v.iter().map(atom).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Sexp, Error> {
+        Ok(atom("nil"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Sexp, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Sexp, Error> {
+        Ok(atom("nil"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Sexp, Error> {
+        Ok(atom(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Sexp, Error> {
+        Ok(atom(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Sexp, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Sexp, Error> {
+        Ok(Sexp::List(vec![atom(variant), to_sexp(value)?]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<List, Error> {
+        Ok(List::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<List, Error> {
+        Ok(List::new(None, len))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<List, Error> {
+        Ok(List::new(Some(name), len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<List, Error> {
+        Ok(List::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<List, Error> {
+        Ok(List::new(None, len.unwrap_or(0)))
+    }
+
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Struct, Error> {
+        Ok(Struct::new(name, len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Struct, Error> {
+        Ok(Struct::new(variant, len))
+    }
+}
+
+/// Collects the elements of a list, which start with the name of a tuple struct or variant.
+#[derive(Debug)]
+struct List {
+    items: Vec<Sexp>,
+    /// The key of a map entry, until its value is serialized.
+    key: Option<Sexp>,
+}
+
+impl List {
+    fn new(name: Option<&'static str>, len: usize) -> Self {
+        let mut items = Vec::with_capacity(len + 1);
+        items.extend(name.map(atom));
+        Self { items, key: None }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(to_sexp(value)?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for List {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::List(self.items))
+    }
+}
+
+impl ser::SerializeTuple for List {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::List(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for List {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::List(self.items))
+    }
+}
+
+impl ser::SerializeTupleVariant for List {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::List(self.items))
+    }
+}
+
+/// Maps are lists of `(key value)` entries.
+impl ser::SerializeMap for List {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(to_sexp(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_else(|| atom("nil"));
+        self.items.push(Sexp::List(vec![key, to_sexp(value)?]));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Sexp::List(self.items))
+    }
+}
+
+/// Collects the fields of a struct or struct variant.
+#[derive(Debug)]
+struct Struct {
+    name: &'static str,
+    fields: Vec<(&'static str, Sexp)>,
+}
+
+impl Struct {
+    fn new(name: &'static str, len: usize) -> Self {
+        Self {
+            name,
+            fields: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.fields.push((key, to_sexp(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Sexp {
+        match (self.name, &self.fields[..]) {
+            // See the `Serialize` implementations of `Span` and `Spanned`.
+            ("Span", [("start", start), ("end", end)]) => atom(format!("{}..{}", start, end)),
+            ("Spanned", [("data", data), ("span", span)]) => match data {
+                Sexp::List(items) if items.first().is_some_and(Sexp::is_name) => {
+                    let mut items = items.clone();
+                    items.insert(1, span.clone());
+                    Sexp::List(items)
+                }
+                data => Sexp::List(vec![data.clone(), span.clone()]),
+            },
+            _ => {
+                let mut items = vec![atom(self.name)];
+                for (key, value) in self.fields {
+                    items.push(atom(format!(":{}", key)));
+                    items.push(value);
+                }
+                Sexp::List(items)
+            }
+        }
+    }
+}
+
+impl ser::SerializeStruct for Struct {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Struct::end(self))
+    }
+}
+
+impl ser::SerializeStructVariant for Struct {
+    type Ok = Sexp;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Sexp, Error> {
+        Ok(Struct::end(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        diagnostic::Files,
+        syntax::{cst, Parser},
+    };
+
+    fn parse(src: &str) -> Vec<crate::syntax::ast::Item> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let (items, errors) = Parser::new(&files, file).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        items
+    }
+
+    #[test]
+    fn test_ast() {
+        let items = parse("const A: i64 = -x + 1;");
+        let sexp = to_sexp(&items).unwrap();
+        assert_eq!(
+            sexp.to_string(),
+            "((Const 0..22 :name (\"A\" 6..7) :public false :ty (Int 9..12 I64) \
+             :val (Binary 15..21 :left (Unary 15..17 :op Negate :expr (Variable 16..17 (\"x\" 16..17))) \
+             :op Plus :right (Literal 20..21 (Int 1)))))"
+        );
+        assert_eq!(
+            sexp.pretty(),
+            r#"((Const 0..22
+   :name ("A" 6..7)
+   :public false
+   :ty (Int 9..12 I64)
+   :val (Binary 15..21
+          :left (Unary 15..17 :op Negate :expr (Variable 16..17 ("x" 16..17)))
+          :op Plus
+          :right (Literal 20..21 (Int 1)))))"#
+        );
+        let json = serde_json::to_value(&items[0]).unwrap();
+        assert_eq!(json["span"], serde_json::json!({ "start": 0, "end": 22 }));
+        assert_eq!(json["data"]["Const"]["val"]["data"]["Binary"]["op"], "Plus");
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = cst::tokens("let s = \"a\\\"\";");
+        assert_eq!(
+            to_sexp(&tokens[6..]).unwrap().to_string(),
+            r#"((SyntaxToken :type String :span 8..13 :text "\"a\\\"\"") (SyntaxToken :type Semicolon :span 13..14 :text ";"))"#
+        );
+        assert_eq!(
+            serde_json::to_string(&tokens[0]).unwrap(),
+            r#"{"type":"Let","span":{"start":0,"end":3},"text":"let"}"#
+        );
+    }
+}
@@ -2,10 +2,14 @@ use crate::Spanned;
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum TokenType {
     // Literals
     String,
+    /// `f"..."`, a string with `{...}` interpolations.
+    FormatString,
     Integer,
+    Float,
     Bool,
 
     // Identifier
@@ -14,73 +18,147 @@ pub enum TokenType {
     // Keywords
     Def,
     Let,
+    Mut,
     Loop,
     While,
+    For,
+    In,
     If,
     Else,
     Break,
     Continue,
+    Return,
+    Struct,
+    Enum,
+    Impl,
+    Import,
+    Pub,
+    Match,
+    As,
+    Const,
 
     // Some other chars
     Bang,
     Plus,
     Minus,
     Star,
+    StarStar,
     Slash,
+    Percent,
+    Caret,
+    Amp,
+    AmpAmp,
     Colon,
+    ColonColon,
     Semicolon,
     Comma,
     Dot,
+    DotDot,
+    DotDotEqual,
+    Pipe,
+    PipePipe,
 
     LeftParen,
     RightParen,
     LeftCurly,
     RightCurly,
+    LeftBracket,
+    RightBracket,
 
     Equal,
     EqualEqual,
+    FatArrow,
     NotEqual,
     GreaterEqual,
     Greater,
     LessEqual,
     Less,
+    LessLess,
+    GreaterGreater,
+
+    // Trivia, which is only produced by `Lexer::lossless`
+    Whitespace,
+    /// `# ...` up to the end of the line.
+    Comment,
+
+    /// A character that doesn't start any token.
+    Unknown,
+}
+
+impl TokenType {
+    /// Returns `true` for whitespace and comments, which the parser skips.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment)
+    }
 }
 
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let val = match self {
             TokenType::String => "<string>",
+            TokenType::FormatString => "<format string>",
             TokenType::Integer => "<int>",
+            TokenType::Float => "<float>",
             TokenType::Bool => "<bool>",
             TokenType::Identifier => "<identifier>",
             TokenType::Def => "def",
             TokenType::Let => "let",
+            TokenType::Mut => "mut",
             TokenType::Loop => "loop",
             TokenType::While => "while",
+            TokenType::For => "for",
+            TokenType::In => "in",
             TokenType::If => "if",
             TokenType::Else => "else",
             TokenType::Break => "break",
             TokenType::Continue => "continue",
+            TokenType::Return => "return",
+            TokenType::Struct => "struct",
+            TokenType::Enum => "enum",
+            TokenType::Impl => "impl",
+            TokenType::Import => "import",
+            TokenType::Pub => "pub",
+            TokenType::Match => "match",
+            TokenType::As => "as",
+            TokenType::Const => "const",
             TokenType::Bang => "!",
             TokenType::Plus => "+",
             TokenType::Minus => "-",
             TokenType::Star => "*",
+            TokenType::StarStar => "**",
             TokenType::Slash => "/",
+            TokenType::Percent => "%",
+            TokenType::Caret => "^",
+            TokenType::Amp => "&",
+            TokenType::AmpAmp => "&&",
             TokenType::Colon => ":",
+            TokenType::ColonColon => "::",
             TokenType::Semicolon => ";",
             TokenType::Comma => ",",
             TokenType::Dot => ".",
+            TokenType::DotDot => "..",
+            TokenType::DotDotEqual => "..=",
+            TokenType::Pipe => "|",
+            TokenType::PipePipe => "||",
             TokenType::LeftParen => "(",
             TokenType::RightParen => ")",
             TokenType::LeftCurly => "{",
             TokenType::RightCurly => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
             TokenType::Equal => "=",
             TokenType::EqualEqual => "==",
+            TokenType::FatArrow => "=>",
             TokenType::NotEqual => "!=",
             TokenType::GreaterEqual => ">=",
             TokenType::Greater => ">",
             TokenType::LessEqual => "<=",
             TokenType::Less => "<",
+            TokenType::LessLess => "<<",
+            TokenType::GreaterGreater => ">>",
+            TokenType::Whitespace => "<whitespace>",
+            TokenType::Comment => "<comment>",
+            TokenType::Unknown => "<unknown>",
         };
         write!(f, "{}", val)
     }
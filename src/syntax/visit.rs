@@ -1,14 +1,50 @@
+//! Traversals of the AST.
+//!
+//! [`ItemVisitor`], [`StmtVisitor`] and [`ExprVisitor`] compute a value for every node,
+//! like the type checker and the interpreter do, so they handle every kind of node.
+//! Passes that only look at some nodes use [`Visitor`], or [`VisitorMut`] to change
+//! them in place, which walk the rest of the tree by default. [`Fold`] rebuilds the
+//! tree and can replace nodes with new ones.
+
 use super::ast::{
-    BinaryOperation, Block, DefArgument, Expr, ExprKind, Identifier, Item, ItemKind, Literal, Stmt,
-    StmtKind, Type, UnaryOperation,
+    BinaryOperation, Block, ClosureArg, ClosureBody, DefArgument, Expr, ExprKind, FieldInit,
+    FieldPattern, FormatPart, Identifier, Item, ItemKind, LetPattern, LetPatternKind, Literal,
+    MatchArm, Path, Pattern, PatternKind, Stmt, StmtKind, StructField, Type, TypeKind,
+    UnaryOperation, Variant,
 };
+use crate::Spanned;
 
 pub trait ItemVisitor {
     type Output;
 
     fn visit_item(&mut self, item: &Item) -> Self::Output {
         match item.data() {
-            ItemKind::Def { name, args } => self.visit_def(item, name, args),
+            ItemKind::Def {
+                name,
+                public,
+                args,
+                ret,
+                body,
+            } => self.visit_def(item, name, *public, args, ret.as_ref(), body),
+            ItemKind::Struct {
+                name,
+                public,
+                fields,
+            } => self.visit_struct(item, name, *public, fields),
+            ItemKind::Enum {
+                name,
+                public,
+                variants,
+            } => self.visit_enum(item, name, *public, variants),
+            ItemKind::Const {
+                name,
+                public,
+                ty,
+                val,
+            } => self.visit_const(item, name, *public, ty, val),
+            ItemKind::Impl { ty, methods } => self.visit_impl(item, ty, methods),
+            ItemKind::Import { name, path } => self.visit_import(item, name, path.as_ref()),
+            ItemKind::Error => self.visit_error_item(item),
         }
     }
 
@@ -16,8 +52,47 @@ pub trait ItemVisitor {
         &mut self,
         item: &Item,
         name: &Identifier,
-        args: &Vec<DefArgument>,
+        public: bool,
+        args: &[DefArgument],
+        ret: Option<&Type>,
+        body: &Block,
+    ) -> Self::Output;
+
+    fn visit_struct(
+        &mut self,
+        item: &Item,
+        name: &Identifier,
+        public: bool,
+        fields: &[StructField],
+    ) -> Self::Output;
+
+    fn visit_enum(
+        &mut self,
+        item: &Item,
+        name: &Identifier,
+        public: bool,
+        variants: &[Variant],
+    ) -> Self::Output;
+
+    fn visit_const(
+        &mut self,
+        item: &Item,
+        name: &Identifier,
+        public: bool,
+        ty: &Type,
+        val: &Expr,
     ) -> Self::Output;
+
+    fn visit_impl(&mut self, item: &Item, ty: &Identifier, methods: &[Item]) -> Self::Output;
+
+    fn visit_import(
+        &mut self,
+        item: &Item,
+        name: &Identifier,
+        path: Option<&Spanned<String>>,
+    ) -> Self::Output;
+
+    fn visit_error_item(&mut self, item: &Item) -> Self::Output;
 }
 
 pub trait ExprVisitor {
@@ -26,19 +101,112 @@ pub trait ExprVisitor {
     fn visit_expr(&mut self, expr: &Expr) -> Self::Output {
         match expr.data() {
             ExprKind::Literal(literal) => self.visit_literal(expr, literal),
+            ExprKind::FormatString(parts) => self.visit_format_string(expr, parts),
             ExprKind::Binary { left, op, right } => self.visit_binary(expr, left, op, right),
-            ExprKind::Unary { op, expr } => self.visit_unary(expr, op, expr),
-            ExprKind::Call { name, args } => self.visit_call(expr, name, args),
+            ExprKind::Unary { op, expr: right } => self.visit_unary(expr, op, right),
+            ExprKind::Call { callee, args } => self.visit_call(expr, callee, args),
+            ExprKind::Closure { args, body } => self.visit_closure(expr, args, body),
+            ExprKind::Array(elems) => self.visit_array(expr, elems),
+            ExprKind::Tuple(elems) => self.visit_tuple(expr, elems),
+            ExprKind::Map(entries) => self.visit_map(expr, entries),
+            ExprKind::Index { expr: array, index } => self.visit_index(expr, array, index),
+            ExprKind::StructLiteral { name, fields } => {
+                self.visit_struct_literal(expr, name, fields)
+            }
+            ExprKind::Field { expr: target, name } => self.visit_field(expr, target, name),
+            ExprKind::MethodCall {
+                receiver,
+                name,
+                args,
+            } => self.visit_method_call(expr, receiver, name, args),
+            ExprKind::Assign { target, val } => self.visit_assign(expr, target, val),
+            ExprKind::Cast { expr: val, ty } => self.visit_cast(expr, val, ty),
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => self.visit_range(expr, start, end, *inclusive),
+            ExprKind::Match {
+                expr: scrutinee,
+                arms,
+            } => self.visit_match(expr, scrutinee, arms),
+            ExprKind::If {
+                cond,
+                then,
+                otherwise,
+            } => self.visit_if(expr, cond, then, otherwise.as_ref()),
+            ExprKind::Block(block) => self.visit_block(expr, block),
             ExprKind::Grouping(expr) => self.visit_expr(expr),
             ExprKind::Variable(name) => self.visit_variable(expr, name),
+            ExprKind::Path(path) => self.visit_path(expr, path),
         }
     }
 
     fn visit_variable(&mut self, expr: &Expr, name: &Identifier) -> Self::Output;
 
+    fn visit_path(&mut self, expr: &Expr, path: &Path) -> Self::Output;
+
     fn visit_literal(&mut self, expr: &Expr, literal: &Literal) -> Self::Output;
 
-    fn visit_call(&mut self, expr: &Expr, name: &Identifier, args: &Vec<Expr>) -> Self::Output;
+    fn visit_format_string(&mut self, expr: &Expr, parts: &[FormatPart]) -> Self::Output;
+
+    fn visit_call(&mut self, expr: &Expr, callee: &Expr, args: &[Expr]) -> Self::Output;
+
+    fn visit_closure(
+        &mut self,
+        expr: &Expr,
+        args: &[ClosureArg],
+        body: &ClosureBody,
+    ) -> Self::Output;
+
+    fn visit_array(&mut self, expr: &Expr, elems: &[Expr]) -> Self::Output;
+
+    fn visit_tuple(&mut self, expr: &Expr, elems: &[Expr]) -> Self::Output;
+
+    fn visit_map(&mut self, expr: &Expr, entries: &[(Expr, Expr)]) -> Self::Output;
+
+    fn visit_index(&mut self, expr: &Expr, array: &Expr, index: &Expr) -> Self::Output;
+
+    fn visit_struct_literal(
+        &mut self,
+        expr: &Expr,
+        name: &Path,
+        fields: &[FieldInit],
+    ) -> Self::Output;
+
+    fn visit_field(&mut self, expr: &Expr, target: &Expr, name: &Identifier) -> Self::Output;
+
+    fn visit_method_call(
+        &mut self,
+        expr: &Expr,
+        receiver: &Expr,
+        name: &Identifier,
+        args: &[Expr],
+    ) -> Self::Output;
+
+    fn visit_assign(&mut self, expr: &Expr, target: &Expr, val: &Expr) -> Self::Output;
+
+    fn visit_cast(&mut self, expr: &Expr, val: &Expr, ty: &Type) -> Self::Output;
+
+    fn visit_range(
+        &mut self,
+        expr: &Expr,
+        start: &Expr,
+        end: &Expr,
+        inclusive: bool,
+    ) -> Self::Output;
+
+    fn visit_match(&mut self, expr: &Expr, scrutinee: &Expr, arms: &[MatchArm]) -> Self::Output;
+
+    fn visit_if(
+        &mut self,
+        expr: &Expr,
+        cond: &Expr,
+        then: &Block,
+        otherwise: Option<&Block>,
+    ) -> Self::Output;
+
+    fn visit_block(&mut self, expr: &Expr, block: &Block) -> Self::Output;
 
     fn visit_binary(
         &mut self,
@@ -51,26 +219,30 @@ pub trait ExprVisitor {
     fn visit_unary(&mut self, expr: &Expr, op: &UnaryOperation, right: &Expr) -> Self::Output;
 }
 
-pub trait StmtVisitor: ExprVisitor
-where
-    <Self as ExprVisitor>::Output: Into<<Self as StmtVisitor>::Output>,
-{
+pub trait StmtVisitor: ExprVisitor {
     type Output;
 
     fn visit_stmt(&mut self, stmt: &Stmt) -> <Self as StmtVisitor>::Output {
         match stmt.data() {
-            StmtKind::Let { name, val } => self.visit_let(stmt, name, val),
-            StmtKind::If {
-                cond,
-                then,
-                otherwise,
-            } => self.visit_if(stmt, cond, then, otherwise),
+            StmtKind::Let {
+                pattern,
+                mutable,
+                ty,
+                val,
+            } => self.visit_let(stmt, pattern, *mutable, ty.as_ref(), val),
             StmtKind::While { cond, block } => self.visit_while(stmt, cond, block),
+            StmtKind::For { var, iter, block } => self.visit_for(stmt, var, iter, block),
             StmtKind::Loop(block) => self.visit_loop(stmt, block),
-            StmtKind::Expr(expr) => self.visit_expr(expr).into(),
+            StmtKind::Break => self.visit_break(stmt),
+            StmtKind::Continue => self.visit_continue(stmt),
+            StmtKind::Return(val) => self.visit_return(stmt, val.as_ref()),
+            StmtKind::Expr(expr) => self.visit_expr_stmt(stmt, expr),
+            StmtKind::Error => self.visit_error_stmt(stmt),
         }
     }
 
+    fn visit_expr_stmt(&mut self, stmt: &Stmt, expr: &Expr) -> <Self as StmtVisitor>::Output;
+
     fn visit_loop(&mut self, stmt: &Stmt, block: &Block) -> <Self as StmtVisitor>::Output;
 
     fn visit_while(
@@ -80,18 +252,1145 @@ where
         block: &Block,
     ) -> <Self as StmtVisitor>::Output;
 
-    fn visit_if(
+    fn visit_for(
         &mut self,
         stmt: &Stmt,
-        cond: &Expr,
-        then: &Block,
-        otherwise: &Block,
+        var: &Identifier,
+        iter: &Expr,
+        block: &Block,
     ) -> <Self as StmtVisitor>::Output;
 
+    fn visit_break(&mut self, stmt: &Stmt) -> <Self as StmtVisitor>::Output;
+
+    fn visit_continue(&mut self, stmt: &Stmt) -> <Self as StmtVisitor>::Output;
+
+    fn visit_return(&mut self, stmt: &Stmt, val: Option<&Expr>) -> <Self as StmtVisitor>::Output;
+
     fn visit_let(
         &mut self,
         stmt: &Stmt,
-        name: &Identifier,
+        pattern: &LetPattern,
+        mutable: bool,
+        ty: Option<&Type>,
         val: &Expr,
     ) -> <Self as StmtVisitor>::Output;
+
+    fn visit_error_stmt(&mut self, stmt: &Stmt) -> <Self as StmtVisitor>::Output;
+}
+
+/// Walks the whole syntax tree without computing a value for every node, which is
+/// what most passes, like linters, need.
+///
+/// Every method defaults to the matching `walk_*` function, which visits the children
+/// of the node, so an implementation only overrides the nodes it cares about, and
+/// calls the `walk_*` function in them to keep walking into their children.
+pub trait Visitor {
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        walk_match_arm(self, arm)
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern)
+    }
+
+    fn visit_let_pattern(&mut self, pattern: &LetPattern) {
+        walk_let_pattern(self, pattern)
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        walk_type(self, ty)
+    }
+
+    fn visit_path(&mut self, path: &Path) {
+        walk_path(self, path)
+    }
+
+    /// Visits every name, of items, variables, fields, arguments and path segments.
+    fn visit_ident(&mut self, _ident: &Identifier) {}
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &Item) {
+    match item.data() {
+        ItemKind::Def {
+            name,
+            args,
+            ret,
+            body,
+            ..
+        } => {
+            visitor.visit_ident(name);
+            for arg in args {
+                visitor.visit_ident(&arg.name);
+                visitor.visit_type(&arg.ty);
+            }
+            if let Some(ret) = ret {
+                visitor.visit_type(ret);
+            }
+            visitor.visit_block(body);
+        }
+        ItemKind::Struct { name, fields, .. } => {
+            visitor.visit_ident(name);
+            for field in fields {
+                visitor.visit_ident(&field.name);
+                visitor.visit_type(&field.ty);
+            }
+        }
+        ItemKind::Enum { name, variants, .. } => {
+            visitor.visit_ident(name);
+            for variant in variants {
+                visitor.visit_ident(&variant.name);
+                for ty in &variant.fields {
+                    visitor.visit_type(ty);
+                }
+            }
+        }
+        ItemKind::Const { name, ty, val, .. } => {
+            visitor.visit_ident(name);
+            visitor.visit_type(ty);
+            visitor.visit_expr(val);
+        }
+        ItemKind::Impl { ty, methods } => {
+            visitor.visit_ident(ty);
+            for method in methods {
+                visitor.visit_item(method);
+            }
+        }
+        ItemKind::Import { name, .. } => visitor.visit_ident(name),
+        ItemKind::Error => {}
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt.data() {
+        StmtKind::Let {
+            pattern, ty, val, ..
+        } => {
+            visitor.visit_let_pattern(pattern);
+            if let Some(ty) = ty {
+                visitor.visit_type(ty);
+            }
+            visitor.visit_expr(val);
+        }
+        StmtKind::While { cond, block } => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(block);
+        }
+        StmtKind::For { var, iter, block } => {
+            visitor.visit_ident(var);
+            visitor.visit_expr(iter);
+            visitor.visit_block(block);
+        }
+        StmtKind::Loop(block) => visitor.visit_block(block),
+        StmtKind::Return(Some(expr)) | StmtKind::Expr(expr) => visitor.visit_expr(expr),
+        StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr.data() {
+        ExprKind::Literal(_) => {}
+        ExprKind::FormatString(parts) => {
+            for part in parts {
+                if let FormatPart::Expr(expr) = part {
+                    visitor.visit_expr(expr);
+                }
+            }
+        }
+        ExprKind::Variable(name) => visitor.visit_ident(name),
+        ExprKind::Path(path) => visitor.visit_path(path),
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Index {
+            expr: left,
+            index: right,
+        }
+        | ExprKind::Assign {
+            target: left,
+            val: right,
+        }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        ExprKind::Unary { expr, .. } | ExprKind::Grouping(expr) => visitor.visit_expr(expr),
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Closure { args, body } => {
+            for arg in args {
+                visitor.visit_ident(&arg.name);
+                if let Some(ty) = &arg.ty {
+                    visitor.visit_type(ty);
+                }
+            }
+            match body {
+                ClosureBody::Expr(expr) => visitor.visit_expr(expr),
+                ClosureBody::Block(block) => visitor.visit_block(block),
+            }
+        }
+        ExprKind::Array(elems) | ExprKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_expr(elem);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, val) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(val);
+            }
+        }
+        ExprKind::StructLiteral { name, fields } => {
+            visitor.visit_path(name);
+            for field in fields {
+                visitor.visit_ident(&field.name);
+                visitor.visit_expr(&field.val);
+            }
+        }
+        ExprKind::Field { expr, name } => {
+            visitor.visit_expr(expr);
+            visitor.visit_ident(name);
+        }
+        ExprKind::MethodCall {
+            receiver,
+            name,
+            args,
+        } => {
+            visitor.visit_expr(receiver);
+            visitor.visit_ident(name);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Cast { expr, ty } => {
+            visitor.visit_expr(expr);
+            visitor.visit_type(ty);
+        }
+        ExprKind::Match { expr, arms } => {
+            visitor.visit_expr(expr);
+            for arm in arms {
+                visitor.visit_match_arm(arm);
+            }
+        }
+        ExprKind::If {
+            cond,
+            then,
+            otherwise,
+        } => {
+            visitor.visit_expr(cond);
+            visitor.visit_block(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_block(otherwise);
+            }
+        }
+        ExprKind::Block(block) => visitor.visit_block(block),
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+    if let Some(expr) = &block.expr {
+        visitor.visit_expr(expr);
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &MatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    visitor.visit_expr(&arm.body);
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &Pattern) {
+    match pattern.data() {
+        PatternKind::Wildcard | PatternKind::Literal(_) => {}
+        PatternKind::Binding(name) => visitor.visit_ident(name),
+        PatternKind::Struct { name, fields } => {
+            visitor.visit_path(name);
+            for field in fields {
+                visitor.visit_ident(&field.name);
+                visitor.visit_pattern(&field.pattern);
+            }
+        }
+        PatternKind::Variant { name, args } => {
+            visitor.visit_path(name);
+            for arg in args {
+                visitor.visit_pattern(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_let_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &LetPattern) {
+    match pattern.data() {
+        LetPatternKind::Name(name) => visitor.visit_ident(name),
+        LetPatternKind::Wildcard => {}
+        LetPatternKind::Tuple(patterns) => {
+            for pattern in patterns {
+                visitor.visit_let_pattern(pattern);
+            }
+        }
+    }
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, ty: &Type) {
+    match ty.data() {
+        TypeKind::Unit | TypeKind::Int(_) | TypeKind::F64 | TypeKind::String | TypeKind::Bool => {}
+        TypeKind::Array(ty) => visitor.visit_type(ty),
+        TypeKind::Tuple(tys) => {
+            for ty in tys {
+                visitor.visit_type(ty);
+            }
+        }
+        TypeKind::Map(key, val) => {
+            visitor.visit_type(key);
+            visitor.visit_type(val);
+        }
+        TypeKind::Function { args, ret } => {
+            for arg in args {
+                visitor.visit_type(arg);
+            }
+            if let Some(ret) = ret {
+                visitor.visit_type(ret);
+            }
+        }
+        TypeKind::Named(path) => visitor.visit_path(path),
+    }
+}
+
+pub fn walk_path<V: Visitor + ?Sized>(visitor: &mut V, path: &Path) {
+    if let Some(module) = &path.module {
+        visitor.visit_ident(module);
+    }
+    visitor.visit_ident(&path.name);
+    if let Some(variant) = &path.variant {
+        visitor.visit_ident(variant);
+    }
+}
+
+/// Like [`Visitor`], but gets mutable references to the nodes, so it can rewrite
+/// the syntax tree in place, e.g. to rename a variable or to change a literal.
+pub trait VisitorMut {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        walk_match_arm_mut(self, arm)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_let_pattern_mut(&mut self, pattern: &mut LetPattern) {
+        walk_let_pattern_mut(self, pattern)
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty)
+    }
+
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        walk_path_mut(self, path)
+    }
+
+    /// Visits every name, of items, variables, fields, arguments and path segments.
+    fn visit_ident_mut(&mut self, _ident: &mut Identifier) {}
+}
+
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Item) {
+    match item.data_mut() {
+        ItemKind::Def {
+            name,
+            args,
+            ret,
+            body,
+            ..
+        } => {
+            visitor.visit_ident_mut(name);
+            for arg in args {
+                visitor.visit_ident_mut(&mut arg.name);
+                visitor.visit_type_mut(&mut arg.ty);
+            }
+            if let Some(ret) = ret {
+                visitor.visit_type_mut(ret);
+            }
+            visitor.visit_block_mut(body);
+        }
+        ItemKind::Struct { name, fields, .. } => {
+            visitor.visit_ident_mut(name);
+            for field in fields {
+                visitor.visit_ident_mut(&mut field.name);
+                visitor.visit_type_mut(&mut field.ty);
+            }
+        }
+        ItemKind::Enum { name, variants, .. } => {
+            visitor.visit_ident_mut(name);
+            for variant in variants {
+                visitor.visit_ident_mut(&mut variant.name);
+                for ty in &mut variant.fields {
+                    visitor.visit_type_mut(ty);
+                }
+            }
+        }
+        ItemKind::Const { name, ty, val, .. } => {
+            visitor.visit_ident_mut(name);
+            visitor.visit_type_mut(ty);
+            visitor.visit_expr_mut(val);
+        }
+        ItemKind::Impl { ty, methods } => {
+            visitor.visit_ident_mut(ty);
+            for method in methods {
+                visitor.visit_item_mut(method);
+            }
+        }
+        ItemKind::Import { name, .. } => visitor.visit_ident_mut(name),
+        ItemKind::Error => {}
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt.data_mut() {
+        StmtKind::Let {
+            pattern, ty, val, ..
+        } => {
+            visitor.visit_let_pattern_mut(pattern);
+            if let Some(ty) = ty {
+                visitor.visit_type_mut(ty);
+            }
+            visitor.visit_expr_mut(val);
+        }
+        StmtKind::While { cond, block } => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_block_mut(block);
+        }
+        StmtKind::For { var, iter, block } => {
+            visitor.visit_ident_mut(var);
+            visitor.visit_expr_mut(iter);
+            visitor.visit_block_mut(block);
+        }
+        StmtKind::Loop(block) => visitor.visit_block_mut(block),
+        StmtKind::Return(Some(expr)) | StmtKind::Expr(expr) => visitor.visit_expr_mut(expr),
+        StmtKind::Return(None) | StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr.data_mut() {
+        ExprKind::Literal(_) => {}
+        ExprKind::FormatString(parts) => {
+            for part in parts {
+                if let FormatPart::Expr(expr) = part {
+                    visitor.visit_expr_mut(expr);
+                }
+            }
+        }
+        ExprKind::Variable(name) => visitor.visit_ident_mut(name),
+        ExprKind::Path(path) => visitor.visit_path_mut(path),
+        ExprKind::Binary { left, right, .. }
+        | ExprKind::Index {
+            expr: left,
+            index: right,
+        }
+        | ExprKind::Assign {
+            target: left,
+            val: right,
+        }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+            ..
+        } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        ExprKind::Unary { expr, .. } | ExprKind::Grouping(expr) => visitor.visit_expr_mut(expr),
+        ExprKind::Call { callee, args } => {
+            visitor.visit_expr_mut(callee);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Closure { args, body } => {
+            for arg in args {
+                visitor.visit_ident_mut(&mut arg.name);
+                if let Some(ty) = &mut arg.ty {
+                    visitor.visit_type_mut(ty);
+                }
+            }
+            match body {
+                ClosureBody::Expr(expr) => visitor.visit_expr_mut(expr),
+                ClosureBody::Block(block) => visitor.visit_block_mut(block),
+            }
+        }
+        ExprKind::Array(elems) | ExprKind::Tuple(elems) => {
+            for elem in elems {
+                visitor.visit_expr_mut(elem);
+            }
+        }
+        ExprKind::Map(entries) => {
+            for (key, val) in entries {
+                visitor.visit_expr_mut(key);
+                visitor.visit_expr_mut(val);
+            }
+        }
+        ExprKind::StructLiteral { name, fields } => {
+            visitor.visit_path_mut(name);
+            for field in fields {
+                visitor.visit_ident_mut(&mut field.name);
+                visitor.visit_expr_mut(&mut field.val);
+            }
+        }
+        ExprKind::Field { expr, name } => {
+            visitor.visit_expr_mut(expr);
+            visitor.visit_ident_mut(name);
+        }
+        ExprKind::MethodCall {
+            receiver,
+            name,
+            args,
+        } => {
+            visitor.visit_expr_mut(receiver);
+            visitor.visit_ident_mut(name);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        ExprKind::Cast { expr, ty } => {
+            visitor.visit_expr_mut(expr);
+            visitor.visit_type_mut(ty);
+        }
+        ExprKind::Match { expr, arms } => {
+            visitor.visit_expr_mut(expr);
+            for arm in arms {
+                visitor.visit_match_arm_mut(arm);
+            }
+        }
+        ExprKind::If {
+            cond,
+            then,
+            otherwise,
+        } => {
+            visitor.visit_expr_mut(cond);
+            visitor.visit_block_mut(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_block_mut(otherwise);
+            }
+        }
+        ExprKind::Block(block) => visitor.visit_block_mut(block),
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+    if let Some(expr) = &mut block.expr {
+        visitor.visit_expr_mut(expr);
+    }
+}
+
+pub fn walk_match_arm_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arm: &mut MatchArm) {
+    visitor.visit_pattern_mut(&mut arm.pattern);
+    visitor.visit_expr_mut(&mut arm.body);
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern.data_mut() {
+        PatternKind::Wildcard | PatternKind::Literal(_) => {}
+        PatternKind::Binding(name) => visitor.visit_ident_mut(name),
+        PatternKind::Struct { name, fields } => {
+            visitor.visit_path_mut(name);
+            for field in fields {
+                visitor.visit_ident_mut(&mut field.name);
+                visitor.visit_pattern_mut(&mut field.pattern);
+            }
+        }
+        PatternKind::Variant { name, args } => {
+            visitor.visit_path_mut(name);
+            for arg in args {
+                visitor.visit_pattern_mut(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_let_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut LetPattern) {
+    match pattern.data_mut() {
+        LetPatternKind::Name(name) => visitor.visit_ident_mut(name),
+        LetPatternKind::Wildcard => {}
+        LetPatternKind::Tuple(patterns) => {
+            for pattern in patterns {
+                visitor.visit_let_pattern_mut(pattern);
+            }
+        }
+    }
+}
+
+pub fn walk_type_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ty: &mut Type) {
+    match ty.data_mut() {
+        TypeKind::Unit | TypeKind::Int(_) | TypeKind::F64 | TypeKind::String | TypeKind::Bool => {}
+        TypeKind::Array(ty) => visitor.visit_type_mut(ty),
+        TypeKind::Tuple(tys) => {
+            for ty in tys {
+                visitor.visit_type_mut(ty);
+            }
+        }
+        TypeKind::Map(key, val) => {
+            visitor.visit_type_mut(key);
+            visitor.visit_type_mut(val);
+        }
+        TypeKind::Function { args, ret } => {
+            for arg in args {
+                visitor.visit_type_mut(arg);
+            }
+            if let Some(ret) = ret {
+                visitor.visit_type_mut(ret);
+            }
+        }
+        TypeKind::Named(path) => visitor.visit_path_mut(path),
+    }
+}
+
+pub fn walk_path_mut<V: VisitorMut + ?Sized>(visitor: &mut V, path: &mut Path) {
+    if let Some(module) = &mut path.module {
+        visitor.visit_ident_mut(module);
+    }
+    visitor.visit_ident_mut(&mut path.name);
+    if let Some(variant) = &mut path.variant {
+        visitor.visit_ident_mut(variant);
+    }
+}
+
+/// Consumes the syntax tree and rebuilds it, so nodes can be replaced by nodes of
+/// the same type, e.g. `1 + 2` by `3` in a constant folding pass.
+///
+/// The methods default to the matching `fold_*` function, which folds the children
+/// of the node and rebuilds it with its original span.
+pub trait Fold {
+    fn fold_item(&mut self, item: Item) -> Item {
+        fold_item(self, item)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        fold_stmt(self, stmt)
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        fold_block(self, block)
+    }
+
+    fn fold_match_arm(&mut self, arm: MatchArm) -> MatchArm {
+        fold_match_arm(self, arm)
+    }
+
+    fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+        fold_pattern(self, pattern)
+    }
+
+    fn fold_let_pattern(&mut self, pattern: LetPattern) -> LetPattern {
+        fold_let_pattern(self, pattern)
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        fold_type(self, ty)
+    }
+
+    fn fold_path(&mut self, path: Path) -> Path {
+        fold_path(self, path)
+    }
+
+    fn fold_ident(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+}
+
+fn fold_exprs<F: Fold + ?Sized>(folder: &mut F, exprs: Vec<Expr>) -> Vec<Expr> {
+    exprs
+        .into_iter()
+        .map(|expr| folder.fold_expr(expr))
+        .collect()
+}
+
+/// Folds the expression in place, so the box is reused.
+fn fold_boxed<F: Fold + ?Sized>(folder: &mut F, mut expr: Box<Expr>) -> Box<Expr> {
+    *expr = folder.fold_expr(*expr);
+    expr
+}
+
+fn fold_types<F: Fold + ?Sized>(folder: &mut F, tys: Vec<Type>) -> Vec<Type> {
+    tys.into_iter().map(|ty| folder.fold_type(ty)).collect()
+}
+
+pub fn fold_item<F: Fold + ?Sized>(folder: &mut F, item: Item) -> Item {
+    let span = item.span();
+    let item = match item.into_inner() {
+        ItemKind::Def {
+            name,
+            public,
+            args,
+            ret,
+            body,
+        } => ItemKind::Def {
+            name: folder.fold_ident(name),
+            public,
+            args: args
+                .into_iter()
+                .map(|arg| DefArgument {
+                    name: folder.fold_ident(arg.name),
+                    ty: folder.fold_type(arg.ty),
+                })
+                .collect(),
+            ret: ret.map(|ret| folder.fold_type(ret)),
+            body: folder.fold_block(body),
+        },
+        ItemKind::Struct {
+            name,
+            public,
+            fields,
+        } => ItemKind::Struct {
+            name: folder.fold_ident(name),
+            public,
+            fields: fields
+                .into_iter()
+                .map(|field| StructField {
+                    name: folder.fold_ident(field.name),
+                    ty: folder.fold_type(field.ty),
+                })
+                .collect(),
+        },
+        ItemKind::Enum {
+            name,
+            public,
+            variants,
+        } => ItemKind::Enum {
+            name: folder.fold_ident(name),
+            public,
+            variants: variants
+                .into_iter()
+                .map(|variant| Variant {
+                    name: folder.fold_ident(variant.name),
+                    fields: fold_types(folder, variant.fields),
+                })
+                .collect(),
+        },
+        ItemKind::Const {
+            name,
+            public,
+            ty,
+            val,
+        } => ItemKind::Const {
+            name: folder.fold_ident(name),
+            public,
+            ty: folder.fold_type(ty),
+            val: folder.fold_expr(val),
+        },
+        ItemKind::Impl { ty, methods } => ItemKind::Impl {
+            ty: folder.fold_ident(ty),
+            methods: methods
+                .into_iter()
+                .map(|method| folder.fold_item(method))
+                .collect(),
+        },
+        ItemKind::Import { name, path } => ItemKind::Import {
+            name: folder.fold_ident(name),
+            path,
+        },
+        ItemKind::Error => ItemKind::Error,
+    };
+    span.span(item)
+}
+
+pub fn fold_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let span = stmt.span();
+    let stmt = match stmt.into_inner() {
+        StmtKind::Let {
+            pattern,
+            mutable,
+            ty,
+            val,
+        } => StmtKind::Let {
+            pattern: folder.fold_let_pattern(pattern),
+            mutable,
+            ty: ty.map(|ty| folder.fold_type(ty)),
+            val: folder.fold_expr(val),
+        },
+        StmtKind::While { cond, block } => StmtKind::While {
+            cond: folder.fold_expr(cond),
+            block: folder.fold_block(block),
+        },
+        StmtKind::For { var, iter, block } => StmtKind::For {
+            var: folder.fold_ident(var),
+            iter: folder.fold_expr(iter),
+            block: folder.fold_block(block),
+        },
+        StmtKind::Loop(block) => StmtKind::Loop(folder.fold_block(block)),
+        StmtKind::Return(val) => StmtKind::Return(val.map(|val| folder.fold_expr(val))),
+        StmtKind::Expr(expr) => StmtKind::Expr(folder.fold_expr(expr)),
+        stmt @ (StmtKind::Break | StmtKind::Continue | StmtKind::Error) => stmt,
+    };
+    span.span(stmt)
+}
+
+pub fn fold_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    let span = expr.span();
+    let expr = match expr.into_inner() {
+        ExprKind::Literal(literal) => ExprKind::Literal(literal),
+        ExprKind::FormatString(parts) => ExprKind::FormatString(
+            parts
+                .into_iter()
+                .map(|part| match part {
+                    FormatPart::Expr(expr) => FormatPart::Expr(folder.fold_expr(expr)),
+                    FormatPart::Literal(literal) => FormatPart::Literal(literal),
+                })
+                .collect(),
+        ),
+        ExprKind::Variable(name) => ExprKind::Variable(folder.fold_ident(name)),
+        ExprKind::Path(path) => ExprKind::Path(folder.fold_path(path)),
+        ExprKind::Binary { left, op, right } => ExprKind::Binary {
+            left: fold_boxed(folder, left),
+            op,
+            right: fold_boxed(folder, right),
+        },
+        ExprKind::Unary { op, expr } => ExprKind::Unary {
+            op,
+            expr: fold_boxed(folder, expr),
+        },
+        ExprKind::Call { callee, args } => ExprKind::Call {
+            callee: fold_boxed(folder, callee),
+            args: fold_exprs(folder, args),
+        },
+        ExprKind::Closure { args, body } => ExprKind::Closure {
+            args: args
+                .into_iter()
+                .map(|arg| ClosureArg {
+                    name: folder.fold_ident(arg.name),
+                    ty: arg.ty.map(|ty| folder.fold_type(ty)),
+                })
+                .collect(),
+            body: match body {
+                ClosureBody::Expr(expr) => ClosureBody::Expr(fold_boxed(folder, expr)),
+                ClosureBody::Block(block) => ClosureBody::Block(folder.fold_block(block)),
+            },
+        },
+        ExprKind::Array(elems) => ExprKind::Array(fold_exprs(folder, elems)),
+        ExprKind::Tuple(elems) => ExprKind::Tuple(fold_exprs(folder, elems)),
+        ExprKind::Map(entries) => ExprKind::Map(
+            entries
+                .into_iter()
+                .map(|(key, val)| (folder.fold_expr(key), folder.fold_expr(val)))
+                .collect(),
+        ),
+        ExprKind::Index { expr, index } => ExprKind::Index {
+            expr: fold_boxed(folder, expr),
+            index: fold_boxed(folder, index),
+        },
+        ExprKind::StructLiteral { name, fields } => ExprKind::StructLiteral {
+            name: folder.fold_path(name),
+            fields: fields
+                .into_iter()
+                .map(|field| FieldInit {
+                    name: folder.fold_ident(field.name),
+                    val: folder.fold_expr(field.val),
+                })
+                .collect(),
+        },
+        ExprKind::Field { expr, name } => ExprKind::Field {
+            expr: fold_boxed(folder, expr),
+            name: folder.fold_ident(name),
+        },
+        ExprKind::MethodCall {
+            receiver,
+            name,
+            args,
+        } => ExprKind::MethodCall {
+            receiver: fold_boxed(folder, receiver),
+            name: folder.fold_ident(name),
+            args: fold_exprs(folder, args),
+        },
+        ExprKind::Assign { target, val } => ExprKind::Assign {
+            target: fold_boxed(folder, target),
+            val: fold_boxed(folder, val),
+        },
+        ExprKind::Cast { expr, ty } => ExprKind::Cast {
+            expr: fold_boxed(folder, expr),
+            ty: folder.fold_type(ty),
+        },
+        ExprKind::Range {
+            start,
+            end,
+            inclusive,
+        } => ExprKind::Range {
+            start: fold_boxed(folder, start),
+            end: fold_boxed(folder, end),
+            inclusive,
+        },
+        ExprKind::Match { expr, arms } => ExprKind::Match {
+            expr: fold_boxed(folder, expr),
+            arms: arms
+                .into_iter()
+                .map(|arm| folder.fold_match_arm(arm))
+                .collect(),
+        },
+        ExprKind::If {
+            cond,
+            then,
+            otherwise,
+        } => ExprKind::If {
+            cond: fold_boxed(folder, cond),
+            then: folder.fold_block(then),
+            otherwise: otherwise.map(|block| folder.fold_block(block)),
+        },
+        ExprKind::Block(block) => ExprKind::Block(folder.fold_block(block)),
+        ExprKind::Grouping(expr) => ExprKind::Grouping(fold_boxed(folder, expr)),
+    };
+    span.span(expr)
+}
+
+pub fn fold_block<F: Fold + ?Sized>(folder: &mut F, block: Block) -> Block {
+    Block {
+        stmts: block
+            .stmts
+            .into_iter()
+            .map(|stmt| folder.fold_stmt(stmt))
+            .collect(),
+        expr: block.expr.map(|expr| fold_boxed(folder, expr)),
+    }
+}
+
+pub fn fold_match_arm<F: Fold + ?Sized>(folder: &mut F, arm: MatchArm) -> MatchArm {
+    MatchArm {
+        pattern: folder.fold_pattern(arm.pattern),
+        body: folder.fold_expr(arm.body),
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: Pattern) -> Pattern {
+    let span = pattern.span();
+    let pattern = match pattern.into_inner() {
+        pattern @ (PatternKind::Wildcard | PatternKind::Literal(_)) => pattern,
+        PatternKind::Binding(name) => PatternKind::Binding(folder.fold_ident(name)),
+        PatternKind::Struct { name, fields } => PatternKind::Struct {
+            name: folder.fold_path(name),
+            fields: fields
+                .into_iter()
+                .map(|field| FieldPattern {
+                    name: folder.fold_ident(field.name),
+                    pattern: folder.fold_pattern(field.pattern),
+                })
+                .collect(),
+        },
+        PatternKind::Variant { name, args } => PatternKind::Variant {
+            name: folder.fold_path(name),
+            args: args
+                .into_iter()
+                .map(|arg| folder.fold_pattern(arg))
+                .collect(),
+        },
+    };
+    span.span(pattern)
+}
+
+pub fn fold_let_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: LetPattern) -> LetPattern {
+    let span = pattern.span();
+    let pattern = match pattern.into_inner() {
+        LetPatternKind::Name(name) => LetPatternKind::Name(folder.fold_ident(name)),
+        LetPatternKind::Wildcard => LetPatternKind::Wildcard,
+        LetPatternKind::Tuple(patterns) => LetPatternKind::Tuple(
+            patterns
+                .into_iter()
+                .map(|pattern| folder.fold_let_pattern(pattern))
+                .collect(),
+        ),
+    };
+    span.span(pattern)
+}
+
+pub fn fold_type<F: Fold + ?Sized>(folder: &mut F, ty: Type) -> Type {
+    let span = ty.span();
+    let ty = match ty.into_inner() {
+        TypeKind::Array(ty) => TypeKind::Array(Box::new(folder.fold_type(*ty))),
+        TypeKind::Tuple(tys) => TypeKind::Tuple(fold_types(folder, tys)),
+        TypeKind::Map(key, val) => TypeKind::Map(
+            Box::new(folder.fold_type(*key)),
+            Box::new(folder.fold_type(*val)),
+        ),
+        TypeKind::Function { args, ret } => TypeKind::Function {
+            args: fold_types(folder, args),
+            ret: ret.map(|ret| Box::new(folder.fold_type(*ret))),
+        },
+        TypeKind::Named(path) => TypeKind::Named(folder.fold_path(path)),
+        ty @ (TypeKind::Unit
+        | TypeKind::Int(_)
+        | TypeKind::F64
+        | TypeKind::String
+        | TypeKind::Bool) => ty,
+    };
+    span.span(ty)
+}
+
+pub fn fold_path<F: Fold + ?Sized>(folder: &mut F, path: Path) -> Path {
+    Path {
+        module: path.module.map(|module| folder.fold_ident(module)),
+        name: folder.fold_ident(path.name),
+        variant: path.variant.map(|variant| folder.fold_ident(variant)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Files, syntax::Parser};
+
+    fn parse(src: &str) -> Vec<Item> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let (items, errors) = Parser::new(&files, file).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        items
+    }
+
+    fn print(items: &[Item]) -> String {
+        let items: Vec<_> = items.iter().map(ToString::to_string).collect();
+        items.join("\n")
+    }
+
+    const SRC: &str = r#"
+def main(): i64 {
+    let (x, _) = (1 + 2, f"{x * 3}");
+    for i in 0..x { print(match i { n => n + x, }); }
+    (|y| y + x)(2 * 3 + 1)
+}
+"#;
+
+    /// Collects the names of the variables that are used, not declared.
+    #[derive(Default)]
+    struct Variables(Vec<String>);
+
+    impl Visitor for Variables {
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Variable(name) = expr.data() {
+                self.0.push(name.data().clone());
+            }
+            walk_expr(self, expr)
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut variables = Variables::default();
+        for item in &parse(SRC) {
+            variables.visit_item(item);
+        }
+        assert_eq!(variables.0, ["x", "x", "print", "i", "n", "x", "y", "x"]);
+    }
+
+    /// Renames every `x`, including declarations.
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_ident_mut(&mut self, ident: &mut Identifier) {
+            if ident.data() == "x" {
+                *ident.data_mut() = "renamed".to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let mut items = parse(SRC);
+        for item in &mut items {
+            Rename.visit_item_mut(item);
+        }
+        assert_eq!(
+            print(&items),
+            r#"def main(): i64 {
+    let (renamed, _) = (1 + 2, f"{renamed * 3}");
+    for i in 0..renamed {
+        print(match i {
+            n => n + renamed,
+        });
+    }
+    (|y| y + renamed)(2 * 3 + 1)
+}"#
+        );
+    }
+
+    /// Evaluates additions and multiplications of integer literals.
+    struct ConstantFolding;
+
+    impl Fold for ConstantFolding {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            let expr = fold_expr(self, expr);
+            let val = match expr.data() {
+                ExprKind::Binary { left, op, right } => {
+                    match (left.data().int_literal(), op, right.data().int_literal()) {
+                        (Some(left), BinaryOperation::Plus, Some(right)) => left + right,
+                        (Some(left), BinaryOperation::Mul, Some(right)) => left * right,
+                        _ => return expr,
+                    }
+                }
+                _ => return expr,
+            };
+            expr.span().span(ExprKind::Literal(Literal::Int(val)))
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let items: Vec<_> = parse(SRC)
+            .into_iter()
+            .map(|item| ConstantFolding.fold_item(item))
+            .collect();
+        assert_eq!(
+            print(&items),
+            r#"def main(): i64 {
+    let (x, _) = (3, f"{x * 3}");
+    for i in 0..x {
+        print(match i {
+            n => n + x,
+        });
+    }
+    (|y| y + x)(7)
+}"#
+        );
+    }
 }
@@ -0,0 +1,2408 @@
+//! Static checks that run before a program is interpreted.
+//!
+//! The checker infers the type of every expression where it can, and
+//! falls back to [`Ty::Unknown`] where it can't, so only errors that are
+//! certain to happen at runtime are reported.
+
+mod patterns;
+
+use crate::syntax::{
+    ast::{
+        BinaryOperation, Block, ClosureArg, ClosureBody, DefArgument, Expr, ExprKind, FieldInit,
+        FormatPart, Identifier, IntTy, Item, ItemKind, LetPattern, LetPatternKind, Literal,
+        MatchArm, Path, Stmt, StmtKind, StructField, Type, TypeKind, UnaryOperation, Variant,
+    },
+    visit::{self, ExprVisitor, ItemVisitor, StmtVisitor, Visitor},
+};
+use crate::{
+    diagnostic::{already_defined, Diagnostic, FileId, Label},
+    interpreter::{find_non_const, non_const_error, Builtin},
+    loader::Program,
+    Span, Spanned,
+};
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    /// The type of blocks without a value at the end and functions that don't return anything.
+    Unit,
+    /// An integer, which is `None` for literals whose type depends on where they're used.
+    Int(Option<IntTy>),
+    Float,
+    String,
+    Bool,
+    Array(Box<Ty>),
+    Tuple(Vec<Ty>),
+    /// A map from keys of the first type to values of the second.
+    Map(Box<Ty>, Box<Ty>),
+    /// A range of integers of the given type.
+    Range(Option<IntTy>),
+    /// A struct and the module it was declared in.
+    Struct(FileId, String),
+    /// An enum and the module it was declared in.
+    Enum(FileId, String),
+    /// A function or closure with the given return type.
+    Function(Box<Ty>),
+    /// The type couldn't be inferred.
+    Unknown,
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Unit => write!(f, "()"),
+            Ty::Int(Some(ty)) => write!(f, "{}", ty),
+            Ty::Int(None) => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::String => write!(f, "string"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Array(ty) => write!(f, "[{}]", ty),
+            Ty::Tuple(tys) => {
+                write!(f, "(")?;
+                for (idx, ty) in tys.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", ty)?;
+                }
+                if tys.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Ty::Map(key, val) => write!(f, "{{{}: {}}}", key, val),
+            Ty::Range(_) => write!(f, "range"),
+            Ty::Struct(_, name) | Ty::Enum(_, name) => write!(f, "{}", name),
+            Ty::Function(ret) => write!(f, "fn() -> {}", ret),
+            Ty::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StructInfo {
+    name: Identifier,
+    public: bool,
+    fields: Vec<(Identifier, Ty)>,
+}
+
+impl Ty {
+    /// Returns `false` if values of the two types can never be the same.
+    fn is_compatible(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::Array(a), Ty::Array(b)) => a.is_compatible(b),
+            (Ty::Tuple(a), Ty::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.is_compatible(b))
+            }
+            (Ty::Map(a_key, a_val), Ty::Map(b_key, b_val)) => {
+                a_key.is_compatible(b_key) && a_val.is_compatible(b_val)
+            }
+            (Ty::Int(None), Ty::Int(_)) | (Ty::Int(_), Ty::Int(None)) => true,
+            (Ty::Range(None), Ty::Range(_)) | (Ty::Range(_), Ty::Range(None)) => true,
+            // The arguments of functions aren't part of their type.
+            (Ty::Function(_), Ty::Function(_)) => true,
+            (a, b) => a == b,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EnumInfo {
+    name: Identifier,
+    public: bool,
+    variants: Vec<(Identifier, Vec<Ty>)>,
+}
+
+/// The variant of an enum that a path refers to, where `usize` is the index of
+/// the variant. It's `None` if the path is a variant that couldn't be found.
+type VariantRef = Option<(FileId, String, usize)>;
+
+#[derive(Debug)]
+struct FnSig {
+    name: Identifier,
+    public: bool,
+    args: Vec<Ty>,
+    ret: Ty,
+}
+
+#[derive(Debug)]
+struct ConstInfo {
+    name: Identifier,
+    public: bool,
+    ty: Ty,
+}
+
+/// The items that were declared in a single module.
+#[derive(Debug, Default)]
+struct Namespace {
+    structs: HashMap<String, StructInfo>,
+    enums: HashMap<String, EnumInfo>,
+    functions: HashMap<String, FnSig>,
+    consts: HashMap<String, ConstInfo>,
+    methods: HashMap<String, HashMap<String, FnSig>>,
+    imports: HashMap<String, FileId>,
+}
+
+#[derive(Debug)]
+pub struct TypeChecker {
+    /// The module that is currently checked.
+    file: FileId,
+    diagnostics: Vec<Diagnostic>,
+    modules: HashMap<FileId, Namespace>,
+    scopes: Vec<HashMap<String, Ty>>,
+    /// The return type of the function whose body is checked, which is unknown in
+    /// closures, because their return type is inferred.
+    ret: Ty,
+}
+
+impl TypeChecker {
+    pub fn new(file: FileId) -> Self {
+        Self {
+            file,
+            diagnostics: vec![],
+            modules: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            ret: Ty::Unknown,
+        }
+    }
+
+    /// Checks every module of the program and returns every error that was found.
+    pub fn check(&mut self, program: &Program) -> Vec<Diagnostic> {
+        for module in &program.modules {
+            self.file = module.file;
+            self.namespace().imports = module.imports.clone();
+            self.declare(&module.items);
+        }
+        for module in &program.modules {
+            self.file = module.file;
+            for item in &module.items {
+                self.visit_item(item);
+            }
+        }
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Returns the namespace of the module that is currently checked.
+    fn namespace(&mut self) -> &mut Namespace {
+        self.modules.entry(self.file).or_default()
+    }
+
+    fn struct_info(&self, file: FileId, name: &str) -> Option<&StructInfo> {
+        self.modules.get(&file)?.structs.get(name)
+    }
+
+    fn enum_info(&self, file: FileId, name: &str) -> Option<&EnumInfo> {
+        self.modules.get(&file)?.enums.get(name)
+    }
+
+    /// Returns the struct or enum named `name` that was declared in `file`.
+    fn named_type(&self, file: FileId, name: &str) -> Option<Ty> {
+        if self.struct_info(file, name).is_some() {
+            Some(Ty::Struct(file, name.to_string()))
+        } else if self.enum_info(file, name).is_some() {
+            Some(Ty::Enum(file, name.to_string()))
+        } else {
+            None
+        }
+    }
+
+    fn function(&self, name: &str) -> Option<&FnSig> {
+        self.modules.get(&self.file)?.functions.get(name)
+    }
+
+    fn const_info(&self, file: FileId, name: &str) -> Option<&ConstInfo> {
+        self.modules.get(&file)?.consts.get(name)
+    }
+
+    fn method(&self, file: FileId, ty: &str, name: &str) -> Option<&FnSig> {
+        self.modules.get(&file)?.methods.get(ty)?.get(name)
+    }
+
+    /// Returns the module that `path` refers to, or `None` if the module wasn't imported.
+    fn resolve(&mut self, path: &Path) -> Option<FileId> {
+        let module = match &path.module {
+            Some(module) => module,
+            None => return Some(self.file),
+        };
+        let file = self
+            .modules
+            .get(&self.file)
+            .and_then(|namespace| namespace.imports.get(module.data()))
+            .copied();
+        if file.is_none() {
+            let label = self.new_label("not found in this scope", module.span());
+            let diagnostic =
+                self.new_error(format!("cannot find module `{}`", module.data()), label);
+            self.diagnostics.push(diagnostic);
+        }
+        file
+    }
+
+    /// Returns the variant that `path` refers to, or `None` if it doesn't refer to a variant.
+    fn resolve_variant(&mut self, path: &Path) -> Option<VariantRef> {
+        let (file, name, variant) = match (&path.module, &path.variant) {
+            (Some(_), Some(variant)) => match self.resolve(path) {
+                Some(file) => (file, &path.name, variant),
+                None => return Some(None),
+            },
+            (Some(module), None) if !self.namespace().imports.contains_key(module.data()) => {
+                (self.file, module, &path.name)
+            }
+            _ => return None,
+        };
+        let info = self
+            .enum_info(file, name.data())
+            .map(|info| (info.name.clone(), info.public));
+        let (def, public) = match info {
+            Some(info) => info,
+            // `a::b` may also refer to a missing module, which is reported by `resolve`.
+            None if path.variant.is_none() => return None,
+            None => {
+                let label = self.new_label("not found in this module", name.span());
+                let diagnostic =
+                    self.new_error(format!("cannot find enum `{}`", name.data()), label);
+                self.diagnostics.push(diagnostic);
+                return Some(None);
+            }
+        };
+        self.check_visibility("enum", path.span(), file, &def, public);
+
+        let idx = self.enum_info(file, def.data()).and_then(|info| {
+            info.variants
+                .iter()
+                .position(|(name, _)| name.data() == variant.data())
+        });
+        if idx.is_none() {
+            let label = self.new_label("variant not found", variant.span());
+            let diagnostic = self.new_error(
+                format!(
+                    "no variant named `{}` in enum `{}`",
+                    variant.data(),
+                    def.data()
+                ),
+                label,
+            );
+            self.diagnostics.push(diagnostic);
+        }
+        Some(idx.map(|idx| (file, def.data().clone(), idx)))
+    }
+
+    /// Checks the arguments that are passed to the constructor of a variant
+    /// and returns the type of the enum.
+    fn check_variant_args(&mut self, span: Span, variant: VariantRef, args: &[Expr]) -> Ty {
+        let args = args
+            .iter()
+            .map(|arg| (arg.span(), self.visit_expr(arg)))
+            .collect::<Vec<_>>();
+        let (file, name, idx) = match variant {
+            Some(variant) => variant,
+            None => return Ty::Unknown,
+        };
+        let fields = self
+            .enum_info(file, &name)
+            .map(|info| info.variants[idx].1.clone())
+            .unwrap_or_default();
+
+        if fields.len() != args.len() {
+            let diagnostic = self.arity_error(span, fields.len(), args.len());
+            self.diagnostics.push(diagnostic);
+        } else {
+            for (field, (span, arg)) in fields.iter().zip(args) {
+                if !field.is_compatible(&arg) {
+                    let label =
+                        self.new_label(format!("expected `{}`, found `{}`", field, arg), span);
+                    let diagnostic = self.new_error("mismatched types", label);
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        Ty::Enum(file, name)
+    }
+
+    /// Collects the signatures of all items, so they can be used before they are declared.
+    fn declare(&mut self, items: &[Item]) {
+        // Type names have to be known first, because fields and arguments may refer to them.
+        for item in items {
+            match item.data() {
+                ItemKind::Struct { name, .. } | ItemKind::Enum { name, .. }
+                    if self.check_type_name(name) => {}
+                ItemKind::Struct { name, public, .. } => {
+                    let info = StructInfo {
+                        name: name.clone(),
+                        public: *public,
+                        fields: vec![],
+                    };
+                    self.namespace().structs.insert(name.data().clone(), info);
+                }
+                ItemKind::Enum { name, public, .. } => {
+                    let info = EnumInfo {
+                        name: name.clone(),
+                        public: *public,
+                        variants: vec![],
+                    };
+                    self.namespace().enums.insert(name.data().clone(), info);
+                }
+                _ => {}
+            }
+        }
+
+        for item in items {
+            match item.data() {
+                ItemKind::Struct { name, fields, .. } => {
+                    let fields = self.struct_fields(fields);
+                    if let Some(info) = self.namespace().structs.get_mut(name.data()) {
+                        // Duplicates were reported above, and keep the first declaration.
+                        if info.name.span() == name.span() {
+                            info.fields = fields;
+                        }
+                    }
+                }
+                ItemKind::Enum { name, variants, .. } => {
+                    let variants = self.enum_variants(variants);
+                    if let Some(info) = self.namespace().enums.get_mut(name.data()) {
+                        if info.name.span() == name.span() {
+                            info.variants = variants;
+                        }
+                    }
+                }
+                ItemKind::Def {
+                    name,
+                    public,
+                    args,
+                    ret,
+                    ..
+                } => {
+                    if let Some(prev) = self.function(name.data()) {
+                        let diagnostic = already_defined(self.file, name, &prev.name);
+                        self.diagnostics.push(diagnostic);
+                        continue;
+                    }
+                    let sig = self.signature(name, *public, args, ret.as_ref());
+                    self.namespace().functions.insert(name.data().clone(), sig);
+                }
+                ItemKind::Impl { ty, methods } => {
+                    if self.named_type(self.file, ty.data()).is_none() {
+                        self.unknown_type(ty);
+                    }
+                    for method in methods {
+                        if let ItemKind::Def {
+                            name,
+                            public,
+                            args,
+                            ret,
+                            ..
+                        } = method.data()
+                        {
+                            if let Some(prev) = self.method(self.file, ty.data(), name.data()) {
+                                let diagnostic = already_defined(self.file, name, &prev.name);
+                                self.diagnostics.push(diagnostic);
+                                continue;
+                            }
+                            let sig = self.signature(name, *public, args, ret.as_ref());
+                            self.namespace()
+                                .methods
+                                .entry(ty.data().clone())
+                                .or_default()
+                                .insert(name.data().clone(), sig);
+                        }
+                    }
+                }
+                ItemKind::Const {
+                    name, public, ty, ..
+                } => {
+                    if let Some(prev) = self.const_info(self.file, name.data()) {
+                        let diagnostic = already_defined(self.file, name, &prev.name);
+                        self.diagnostics.push(diagnostic);
+                        continue;
+                    }
+                    let info = ConstInfo {
+                        name: name.clone(),
+                        public: *public,
+                        ty: self.lower_type(ty),
+                    };
+                    self.namespace().consts.insert(name.data().clone(), info);
+                }
+                ItemKind::Import { .. } | ItemKind::Error => {}
+            }
+        }
+    }
+
+    /// Reports a struct or enum whose name is already used by another type in the
+    /// module, and returns whether it was reported.
+    fn check_type_name(&mut self, name: &Identifier) -> bool {
+        let namespace = self.namespace();
+        let prev = match namespace.structs.get(name.data()) {
+            Some(info) => info.name.clone(),
+            None => match namespace.enums.get(name.data()) {
+                Some(info) => info.name.clone(),
+                None => return false,
+            },
+        };
+        let diagnostic = already_defined(self.file, name, &prev);
+        self.diagnostics.push(diagnostic);
+        true
+    }
+
+    /// Reports arguments that have the same name as an earlier argument.
+    fn check_arg_names<'a>(&mut self, names: impl Iterator<Item = &'a Identifier>) {
+        let mut seen: Vec<&Identifier> = vec![];
+        for name in names {
+            match seen.iter().find(|prev| prev.data() == name.data()) {
+                Some(prev) => {
+                    let diagnostic = already_defined(self.file, name, prev);
+                    self.diagnostics.push(diagnostic);
+                }
+                None => seen.push(name),
+            }
+        }
+    }
+
+    /// Returns whether `expr` is a variable or path that refers to a constant.
+    fn is_const(&self, expr: &Expr) -> bool {
+        let (file, name) = match expr.data() {
+            ExprKind::Variable(name) => (Some(self.file), name),
+            ExprKind::Path(Path {
+                module: Some(module),
+                name,
+                variant: None,
+            }) => (
+                self.modules
+                    .get(&self.file)
+                    .and_then(|namespace| namespace.imports.get(module.data()))
+                    .copied(),
+                name,
+            ),
+            _ => return false,
+        };
+        file.and_then(|file| self.const_info(file, name.data()))
+            .is_some()
+    }
+
+    fn signature(
+        &mut self,
+        name: &Identifier,
+        public: bool,
+        args: &[DefArgument],
+        ret: Option<&Type>,
+    ) -> FnSig {
+        self.check_arg_names(args.iter().map(|arg| &arg.name));
+        FnSig {
+            name: name.clone(),
+            public,
+            args: args.iter().map(|arg| self.lower_type(&arg.ty)).collect(),
+            ret: match ret {
+                Some(ty) => self.lower_type(ty),
+                None => Ty::Unit,
+            },
+        }
+    }
+
+    /// Checks the body of the function `name` against its signature, where `tys` are the
+    /// types of the arguments, `ret` is the return type and `self_ty` is the type of
+    /// `self` for methods.
+    fn check_def(
+        &mut self,
+        name: &Identifier,
+        (tys, ret): (Vec<Ty>, Ty),
+        args: &[DefArgument],
+        body: &Block,
+        self_ty: Option<Ty>,
+    ) {
+        let outer = std::mem::replace(&mut self.ret, ret.clone());
+        let ty = self.scoped(|this| {
+            if let Some(ty) = self_ty {
+                if let Some(scope) = this.scopes.last_mut() {
+                    scope.insert("self".to_string(), ty);
+                }
+            }
+            for (arg, ty) in args.iter().zip(tys) {
+                this.define(&arg.name, ty);
+            }
+            this.check_block(body)
+        });
+        self.ret = outer;
+
+        match &body.expr {
+            Some(expr) => self.expect_value(expr, &ty, &ret),
+            None if !ty.is_compatible(&ret) => {
+                let label = self.new_label(
+                    format!("expected `{}`, but the body can end without a value", ret),
+                    name.span(),
+                );
+                let diagnostic = self
+                    .new_error(format!("function `{}` may not return a value", name), label)
+                    .with_notes(vec![
+                        "help: add a `return` or an expression without `;` at the end".to_string(),
+                    ]);
+                self.diagnostics.push(diagnostic);
+            }
+            None => {}
+        }
+    }
+
+    fn struct_fields(&mut self, fields: &[StructField]) -> Vec<(Identifier, Ty)> {
+        let mut result: Vec<(Identifier, Ty)> = vec![];
+        for field in fields {
+            if let Some((prev, _)) = result
+                .iter()
+                .find(|(name, _)| name.data() == field.name.data())
+            {
+                let diagnostic = Diagnostic::error()
+                    .with_message(format!("field `{}` is already declared", field.name.data()))
+                    .with_labels(vec![
+                        self.new_label("field already declared", field.name.span()),
+                        Label::secondary(self.file, prev.span())
+                            .with_message(format!("`{}` first declared here", prev.data())),
+                    ]);
+                self.diagnostics.push(diagnostic);
+                continue;
+            }
+            let ty = self.lower_type(&field.ty);
+            result.push((field.name.clone(), ty));
+        }
+        result
+    }
+
+    fn enum_variants(&mut self, variants: &[Variant]) -> Vec<(Identifier, Vec<Ty>)> {
+        let mut result: Vec<(Identifier, Vec<Ty>)> = vec![];
+        for variant in variants {
+            if let Some((prev, _)) = result
+                .iter()
+                .find(|(name, _)| name.data() == variant.name.data())
+            {
+                let diagnostic = Diagnostic::error()
+                    .with_message(format!(
+                        "variant `{}` is already declared",
+                        variant.name.data()
+                    ))
+                    .with_labels(vec![
+                        self.new_label("variant already declared", variant.name.span()),
+                        Label::secondary(self.file, prev.span())
+                            .with_message(format!("`{}` first declared here", prev.data())),
+                    ]);
+                self.diagnostics.push(diagnostic);
+                continue;
+            }
+            let fields = variant
+                .fields
+                .iter()
+                .map(|ty| self.lower_type(ty))
+                .collect();
+            result.push((variant.name.clone(), fields));
+        }
+        result
+    }
+
+    fn lower_type(&mut self, ty: &Type) -> Ty {
+        match ty.data() {
+            TypeKind::Unit => Ty::Unit,
+            TypeKind::Int(ty) => Ty::Int(Some(*ty)),
+            TypeKind::F64 => Ty::Float,
+            TypeKind::String => Ty::String,
+            TypeKind::Bool => Ty::Bool,
+            TypeKind::Array(ty) => Ty::Array(Box::new(self.lower_type(ty))),
+            TypeKind::Tuple(tys) => Ty::Tuple(tys.iter().map(|ty| self.lower_type(ty)).collect()),
+            TypeKind::Map(key, val) => {
+                let key_ty = self.lower_type(key);
+                self.check_key_type(key, &key_ty);
+                Ty::Map(Box::new(key_ty), Box::new(self.lower_type(val)))
+            }
+            TypeKind::Function { args, ret } => {
+                for arg in args {
+                    self.lower_type(arg);
+                }
+                let ret = match ret {
+                    Some(ret) => self.lower_type(ret),
+                    None => Ty::Unit,
+                };
+                Ty::Function(Box::new(ret))
+            }
+            TypeKind::Named(path) => match self.resolve(path) {
+                Some(file) => match self.named_type(file, path.name.data()) {
+                    Some(ty) => {
+                        self.check_type_visibility(file, path);
+                        ty
+                    }
+                    None => {
+                        self.unknown_type(&path.span().span(path.to_string()));
+                        Ty::Unknown
+                    }
+                },
+                None => Ty::Unknown,
+            },
+        }
+    }
+
+    fn scoped<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Checks the statements of the block and returns the type of the expression
+    /// at the end of it, which is unit if there is none.
+    fn check_block(&mut self, block: &Block) -> Ty {
+        self.scoped(|this| {
+            for stmt in &block.stmts {
+                this.visit_stmt(stmt);
+            }
+            match (&block.expr, block.stmts.last().map(Spanned::data)) {
+                (Some(expr), _) => this.visit_expr(expr),
+                // A block that always jumps somewhere else never has a value,
+                // so it fits wherever a value of any type is expected.
+                (None, Some(StmtKind::Return(_)))
+                | (None, Some(StmtKind::Break))
+                | (None, Some(StmtKind::Continue)) => Ty::Unknown,
+                (None, Some(StmtKind::Loop(body))) if !breaks(body) => Ty::Unknown,
+                (None, _) => Ty::Unit,
+            }
+        })
+    }
+
+    /// Reports an error if a value of type `ty` is used where a value of type `expected` is required.
+    fn expect_type(&mut self, expr: &Expr, ty: &Ty, expected: Ty) {
+        if !ty.is_compatible(&expected) {
+            let label = self.new_label(
+                format!("expected `{}`, found `{}`", expected, ty),
+                expr.span(),
+            );
+            let diagnostic = self.new_error("mismatched types", label);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Like [`expect_type`](Self::expect_type), but also reports integer literals
+    /// that don't fit into the expected integer type.
+    fn expect_value(&mut self, expr: &Expr, ty: &Ty, expected: &Ty) {
+        if let (Ty::Int(None), Ty::Int(Some(int_ty))) = (ty, expected) {
+            self.check_literal(expr, *int_ty);
+        }
+        self.expect_type(expr, ty, expected.clone());
+    }
+
+    fn check_literal(&mut self, expr: &Expr, ty: IntTy) {
+        match expr.int_literal() {
+            Some(val) if !ty.contains(val) => {
+                let label = self.new_label(
+                    format!(
+                        "the literal `{}` doesn't fit into `{}`, whose range is `{}..={}`",
+                        val,
+                        ty,
+                        ty.min(),
+                        ty.max()
+                    ),
+                    expr.span(),
+                );
+                let diagnostic =
+                    self.new_error(format!("literal out of range for `{}`", ty), label);
+                self.diagnostics.push(diagnostic);
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks that both operands are integers of the same type and returns that type.
+    /// Operands without a type take the type of the other one.
+    fn unify_operands(&mut self, left: &Expr, right: &Expr) -> Option<IntTy> {
+        let left_ty = self.visit_expr(left);
+        let right_ty = self.visit_expr(right);
+        self.unify_int_types(left, &left_ty, right, &right_ty)
+    }
+
+    /// Checks that `left` and `right` are integers of the same type,
+    /// where `left_ty` and `right_ty` are their types.
+    fn unify_int_types(
+        &mut self,
+        left: &Expr,
+        left_ty: &Ty,
+        right: &Expr,
+        right_ty: &Ty,
+    ) -> Option<IntTy> {
+        match (left_ty, right_ty) {
+            (Ty::Int(Some(ty)), _) => {
+                self.expect_value(right, right_ty, left_ty);
+                Some(*ty)
+            }
+            (_, Ty::Int(Some(ty))) => {
+                self.expect_value(left, left_ty, right_ty);
+                Some(*ty)
+            }
+            _ => {
+                self.expect_type(left, left_ty, Ty::Int(None));
+                self.expect_type(right, right_ty, Ty::Int(None));
+                None
+            }
+        }
+    }
+
+    /// Reports an error if values of type `ty` can't be used as the keys of a map.
+    fn check_key_type<T>(&mut self, node: &Spanned<T>, ty: &Ty) {
+        match ty {
+            Ty::Int(_) | Ty::String | Ty::Bool | Ty::Unknown => {}
+            ty => {
+                let label = self.new_label(format!("`{}` can't be used as a key", ty), node.span());
+                let diagnostic = self.new_error("invalid map key", label).with_notes(vec![
+                    "only integers, strings and booleans can be keys".to_string(),
+                ]);
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    /// Checks the arguments of the call at `span` against the types of the parameters.
+    fn check_args(&mut self, span: Span, args: &[Expr], tys: &[Ty], expected: &[Ty]) {
+        if args.len() != expected.len() {
+            let diagnostic = self.arity_error(span, expected.len(), args.len());
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        for ((arg, ty), expected) in args.iter().zip(tys).zip(expected) {
+            self.expect_value(arg, ty, expected);
+        }
+    }
+
+    fn lookup(&self, name: &Identifier) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name.data()))
+            .cloned()
+    }
+
+    fn define(&mut self, name: &Identifier, ty: Ty) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.data().clone(), ty);
+        }
+    }
+
+    /// Defines the variables of a `let`, whose value has type `ty`.
+    fn define_pattern(&mut self, pattern: &LetPattern, ty: Ty) {
+        let patterns = match (pattern.data(), ty) {
+            (LetPatternKind::Wildcard, _) => return,
+            (LetPatternKind::Name(name), ty) => return self.define(name, ty),
+            (LetPatternKind::Tuple(patterns), Ty::Tuple(tys)) if patterns.len() == tys.len() => {
+                for (pattern, ty) in patterns.iter().zip(tys) {
+                    self.define_pattern(pattern, ty);
+                }
+                return;
+            }
+            (LetPatternKind::Tuple(patterns), Ty::Unknown) => patterns,
+            (LetPatternKind::Tuple(patterns), ty) => {
+                let plural = if patterns.len() == 1 { "" } else { "s" };
+                let label = self.new_label(
+                    format!(
+                        "expected a tuple with {} element{}, found `{}`",
+                        patterns.len(),
+                        plural,
+                        ty
+                    ),
+                    pattern.span(),
+                );
+                let diagnostic = self.new_error("mismatched types", label);
+                self.diagnostics.push(diagnostic);
+                patterns
+            }
+        };
+        // The types of the variables are unknown if the type of the tuple is.
+        for pattern in patterns {
+            self.define_pattern(pattern, Ty::Unknown);
+        }
+    }
+
+    // Error utilities
+
+    fn new_error(&self, msg: impl Into<String>, primary_label: Label) -> Diagnostic {
+        Diagnostic::error()
+            .with_message(msg)
+            .with_labels(vec![primary_label])
+    }
+
+    fn new_label(&self, msg: impl Into<String>, span: Span) -> Label {
+        Label::primary(self.file, span).with_message(msg)
+    }
+
+    fn arity_error(&self, span: Span, expected: usize, found: usize) -> Diagnostic {
+        let label = self.new_label(
+            format!(
+                "expected {} argument{}, found {}",
+                expected,
+                if expected == 1 { "" } else { "s" },
+                found
+            ),
+            span,
+        );
+        self.new_error("wrong number of arguments", label)
+    }
+
+    /// Reports an error if the item `def`, that was declared in `file`, is private
+    /// and used at `span` outside of its module.
+    fn check_visibility(
+        &mut self,
+        kind: &str,
+        span: Span,
+        file: FileId,
+        def: &Identifier,
+        public: bool,
+    ) {
+        if public || file == self.file {
+            return;
+        }
+        let keyword = match kind {
+            "struct" | "enum" => kind,
+            "constant" => "const",
+            _ => "def",
+        };
+        let diagnostic = Diagnostic::error()
+            .with_message(format!("{} `{}` is private", kind, def.data()))
+            .with_labels(vec![
+                self.new_label(format!("private {}", kind), span),
+                Label::secondary(file, def.span())
+                    .with_message(format!("`{}` is declared here", def.data())),
+            ])
+            .with_notes(vec![format!(
+                "help: consider making it public: `pub {} {}`",
+                keyword,
+                def.data()
+            )]);
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn check_type_visibility(&mut self, file: FileId, path: &Path) {
+        let name = path.name.data();
+        let info = match self.struct_info(file, name) {
+            Some(info) => Some(("struct", info.name.clone(), info.public)),
+            None => self
+                .enum_info(file, name)
+                .map(|info| ("enum", info.name.clone(), info.public)),
+        };
+        if let Some((kind, def, public)) = info {
+            self.check_visibility(kind, path.span(), file, &def, public);
+        }
+    }
+
+    fn unknown_type(&mut self, name: &Identifier) {
+        let label = self.new_label("not found in this scope", name.span());
+        let diagnostic = self.new_error(format!("cannot find type `{}`", name.data()), label);
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn duplicate_field(&self, name: &Identifier, prev: &Identifier) -> Diagnostic {
+        Diagnostic::error()
+            .with_message(format!("field `{}` specified more than once", name.data()))
+            .with_labels(vec![
+                self.new_label("used more than once", name.span()),
+                Label::secondary(self.file, prev.span()).with_message("first use of the field"),
+            ])
+    }
+
+    fn no_such_field(&self, ty: &Identifier, name: &Identifier) -> Diagnostic {
+        let label = self.new_label("unknown field", name.span());
+        self.new_error(
+            format!(
+                "struct `{}` has no field named `{}`",
+                ty.data(),
+                name.data()
+            ),
+            label,
+        )
+    }
+
+    fn unknown_method(&mut self, ty: &Ty, name: &Identifier) {
+        let label = self.new_label("method not found", name.span());
+        let diagnostic = self.new_error(
+            format!("no method named `{}` found for `{}`", name.data(), ty),
+            label,
+        );
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn unknown_field(&mut self, ty: &Ty, name: &Identifier) {
+        let label = self.new_label("unknown field", name.span());
+        let mut diagnostic = self.new_error(
+            format!("no field `{}` on type `{}`", name.data(), ty),
+            label,
+        );
+        if let Some(info) = match ty {
+            Ty::Struct(file, s) => self.struct_info(*file, s),
+            _ => None,
+        } {
+            let fields = info
+                .fields
+                .iter()
+                .map(|(name, _)| format!("`{}`", name.data()))
+                .collect::<Vec<_>>();
+            if !fields.is_empty() {
+                diagnostic = diagnostic
+                    .with_notes(vec![format!("available fields are: {}", fields.join(", "))]);
+            }
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+impl ExprVisitor for TypeChecker {
+    type Output = Ty;
+
+    fn visit_variable(&mut self, _expr: &Expr, name: &Identifier) -> Ty {
+        if let Some(ty) = self.lookup(name) {
+            return ty;
+        }
+        if let Some(sig) = self.function(name.data()) {
+            return Ty::Function(Box::new(sig.ret.clone()));
+        }
+        match self.const_info(self.file, name.data()) {
+            Some(info) => info.ty.clone(),
+            None if Builtin::from_name(name).is_some() => Ty::Function(Box::new(Ty::Unknown)),
+            None => Ty::Unknown,
+        }
+    }
+
+    fn visit_path(&mut self, expr: &Expr, path: &Path) -> Ty {
+        if let Some(variant) = self.resolve_variant(path) {
+            return self.check_variant_args(expr.span(), variant, &[]);
+        }
+        let file = match self.resolve(path) {
+            Some(file) => file,
+            None => return Ty::Unknown,
+        };
+        let sig = self
+            .modules
+            .get(&file)
+            .and_then(|namespace| namespace.functions.get(path.name.data()));
+        match sig {
+            Some(sig) => {
+                let ty = Ty::Function(Box::new(sig.ret.clone()));
+                let (name, public) = (sig.name.clone(), sig.public);
+                self.check_visibility("function", path.span(), file, &name, public);
+                ty
+            }
+            None => {
+                let info = self
+                    .const_info(file, path.name.data())
+                    .map(|info| (info.name.clone(), info.public, info.ty.clone()));
+                if let Some((name, public, ty)) = info {
+                    self.check_visibility("constant", path.span(), file, &name, public);
+                    return ty;
+                }
+                let label = self.new_label("not found in this module", path.name.span());
+                let diagnostic = self.new_error(format!("cannot find `{}`", path), label);
+                self.diagnostics.push(diagnostic);
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn visit_literal(&mut self, _expr: &Expr, literal: &Literal) -> Ty {
+        match literal {
+            Literal::Int(_) => Ty::Int(None),
+            Literal::String(_) => Ty::String,
+            Literal::Bool(_) => Ty::Bool,
+        }
+    }
+
+    fn visit_call(&mut self, expr: &Expr, callee: &Expr, args: &[Expr]) -> Ty {
+        if let ExprKind::Path(path) = callee.data() {
+            if let Some(variant) = self.resolve_variant(path) {
+                return self.check_variant_args(expr.span(), variant, args);
+            }
+        }
+
+        let callee_ty = self.visit_expr(callee);
+        let arg_tys = args
+            .iter()
+            .map(|arg| self.visit_expr(arg))
+            .collect::<Vec<_>>();
+
+        // Builtins are generic, so their return type depends on the arguments.
+        if let ExprKind::Variable(name) = callee.data() {
+            if self.lookup(name).is_none() {
+                if let Some(sig) = self.function(name.data()) {
+                    let expected = sig.args.clone();
+                    self.check_args(expr.span(), args, &arg_tys, &expected);
+                }
+            }
+            if self.lookup(name).is_none() && self.function(name.data()).is_none() {
+                match (Builtin::from_name(name), arg_tys.first()) {
+                    (Some(Builtin::Len), _) => return Ty::Int(None),
+                    (Some(Builtin::Print | Builtin::Push | Builtin::Insert), _) => return Ty::Unit,
+                    (Some(Builtin::Pop), Some(Ty::Array(ty))) => return *ty.clone(),
+                    (Some(Builtin::Contains), _) => return Ty::Bool,
+                    (Some(Builtin::Remove), Some(Ty::Map(_, val))) => return *val.clone(),
+                    (Some(Builtin::Keys), Some(Ty::Map(key, _))) => return Ty::Array(key.clone()),
+                    _ => {}
+                }
+            }
+        }
+
+        match callee_ty {
+            Ty::Function(ret) => *ret,
+            Ty::Unknown => Ty::Unknown,
+            ty => {
+                let label = self.new_label(format!("found `{}`", ty), callee.span());
+                let diagnostic =
+                    self.new_error(format!("expected function, found `{}`", ty), label);
+                self.diagnostics.push(diagnostic);
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn visit_closure(&mut self, _expr: &Expr, args: &[ClosureArg], body: &ClosureBody) -> Ty {
+        self.check_arg_names(args.iter().map(|arg| &arg.name));
+        let outer = std::mem::replace(&mut self.ret, Ty::Unknown);
+        let ty = self.scoped(|this| {
+            for arg in args {
+                let ty = match &arg.ty {
+                    Some(ty) => this.lower_type(ty),
+                    None => Ty::Unknown,
+                };
+                this.define(&arg.name, ty);
+            }
+            match body {
+                ClosureBody::Expr(expr) => Ty::Function(Box::new(this.visit_expr(expr))),
+                ClosureBody::Block(block) => Ty::Function(Box::new(this.check_block(block))),
+            }
+        });
+        self.ret = outer;
+        ty
+    }
+
+    fn visit_array(&mut self, _expr: &Expr, elems: &[Expr]) -> Ty {
+        let ty = elems
+            .iter()
+            .map(|elem| self.visit_expr(elem))
+            .fold(Ty::Unknown, |ty, elem| match ty {
+                Ty::Unknown => elem,
+                ty => ty,
+            });
+        Ty::Array(Box::new(ty))
+    }
+
+    fn visit_format_string(&mut self, _expr: &Expr, parts: &[FormatPart]) -> Ty {
+        for part in parts {
+            if let FormatPart::Expr(expr) = part {
+                self.visit_expr(expr);
+            }
+        }
+        Ty::String
+    }
+
+    fn visit_tuple(&mut self, _expr: &Expr, elems: &[Expr]) -> Ty {
+        if elems.is_empty() {
+            return Ty::Unit;
+        }
+        Ty::Tuple(elems.iter().map(|elem| self.visit_expr(elem)).collect())
+    }
+
+    fn visit_map(&mut self, _expr: &Expr, entries: &[(Expr, Expr)]) -> Ty {
+        let mut key_ty = Ty::Unknown;
+        let mut val_ty = Ty::Unknown;
+        for (key, val) in entries {
+            let ty = self.visit_expr(key);
+            self.check_key_type(key, &ty);
+            if key_ty == Ty::Unknown {
+                key_ty = ty;
+            }
+            let ty = self.visit_expr(val);
+            if val_ty == Ty::Unknown {
+                val_ty = ty;
+            }
+        }
+        Ty::Map(Box::new(key_ty), Box::new(val_ty))
+    }
+
+    fn visit_index(&mut self, _expr: &Expr, array: &Expr, index: &Expr) -> Ty {
+        let array = self.visit_expr(array);
+        let index_ty = self.visit_expr(index);
+        match array {
+            Ty::Array(ty) => *ty,
+            Ty::Map(key, val) => {
+                self.expect_value(index, &index_ty, &key);
+                *val
+            }
+            _ => Ty::Unknown,
+        }
+    }
+
+    fn visit_struct_literal(&mut self, expr: &Expr, name: &Path, fields: &[FieldInit]) -> Ty {
+        let tys = fields
+            .iter()
+            .map(|field| self.visit_expr(&field.val))
+            .collect::<Vec<_>>();
+
+        let file = match self.resolve(name) {
+            Some(file) => file,
+            None => return Ty::Unknown,
+        };
+        self.check_type_visibility(file, name);
+        let info = match self.struct_info(file, name.name.data()) {
+            Some(info) => info,
+            None => {
+                let label = self.new_label("not found in this scope", name.span());
+                let diagnostic = self.new_error(format!("cannot find struct `{}`", name), label);
+                self.diagnostics.push(diagnostic);
+                return Ty::Unknown;
+            }
+        };
+
+        let mut diagnostics = vec![];
+        // The declared types of the fields that are initialized, in the order of `fields`.
+        let mut expected = vec![];
+        for (idx, field) in fields.iter().enumerate() {
+            let declared = info
+                .fields
+                .iter()
+                .find(|(name, _)| name.data() == field.name.data());
+            if let Some(prev) = fields[..idx]
+                .iter()
+                .find(|prev| prev.name.data() == field.name.data())
+            {
+                diagnostics.push(self.duplicate_field(&field.name, &prev.name));
+            } else if let Some((_, ty)) = declared {
+                expected.push((&field.val, &tys[idx], ty.clone()));
+            } else {
+                diagnostics.push(self.no_such_field(&info.name, &field.name));
+            }
+        }
+
+        let missing = info
+            .fields
+            .iter()
+            .filter(|(name, _)| !fields.iter().any(|field| field.name.data() == name.data()))
+            .map(|(name, _)| format!("`{}`", name.data()))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            let plural = if missing.len() == 1 { "" } else { "s" };
+            let missing = missing.join(", ");
+            let label = self.new_label(format!("missing {}", missing), expr.span());
+            diagnostics.push(self.new_error(
+                format!(
+                    "missing field{} {} in initializer of `{}`",
+                    plural,
+                    missing,
+                    info.name.data()
+                ),
+                label,
+            ));
+        }
+
+        self.diagnostics.extend(diagnostics);
+        for (val, ty, expected) in expected {
+            self.expect_value(val, ty, &expected);
+        }
+        Ty::Struct(file, name.name.data().clone())
+    }
+
+    fn visit_field(&mut self, _expr: &Expr, target: &Expr, name: &Identifier) -> Ty {
+        let ty = self.visit_expr(target);
+        match &ty {
+            Ty::Unknown => Ty::Unknown,
+            Ty::Struct(file, s) => {
+                let field = self
+                    .struct_info(*file, s)
+                    .and_then(|info| {
+                        info.fields
+                            .iter()
+                            .find(|(field, _)| field.data() == name.data())
+                    })
+                    .map(|(_, ty)| ty.clone());
+                field.unwrap_or_else(|| {
+                    self.unknown_field(&ty, name);
+                    Ty::Unknown
+                })
+            }
+            _ => {
+                self.unknown_field(&ty, name);
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn visit_method_call(
+        &mut self,
+        expr: &Expr,
+        receiver: &Expr,
+        name: &Identifier,
+        args: &[Expr],
+    ) -> Ty {
+        let ty = self.visit_expr(receiver);
+        let arg_tys = args
+            .iter()
+            .map(|arg| self.visit_expr(arg))
+            .collect::<Vec<_>>();
+
+        let method = match &ty {
+            Ty::Unknown => return Ty::Unknown,
+            Ty::Struct(file, s) | Ty::Enum(file, s) => {
+                let sig = self.method(*file, s, name.data()).map(|sig| {
+                    (
+                        sig.args.clone(),
+                        sig.ret.clone(),
+                        sig.name.clone(),
+                        sig.public,
+                    )
+                });
+                sig.map(|(expected, ret, def, public)| {
+                    self.check_visibility("method", name.span(), *file, &def, public);
+                    self.check_args(expr.span(), args, &arg_tys, &expected);
+                    ret
+                })
+            }
+            Ty::String => match Builtin::from_name(name) {
+                Some(Builtin::Len) => Some(Ty::Int(None)),
+                _ => None,
+            },
+            Ty::Map(key, val) => match Builtin::from_name(name) {
+                Some(Builtin::Len) => Some(Ty::Int(None)),
+                Some(Builtin::Insert) => Some(Ty::Unit),
+                Some(Builtin::Remove) => Some(*val.clone()),
+                Some(Builtin::Contains) => Some(Ty::Bool),
+                Some(Builtin::Keys) => Some(Ty::Array(key.clone())),
+                _ => None,
+            },
+            Ty::Array(elem) => match Builtin::from_name(name) {
+                Some(Builtin::Len) => Some(Ty::Int(None)),
+                Some(Builtin::Pop) => Some(*elem.clone()),
+                Some(Builtin::Push) => Some(Ty::Unit),
+                _ => None,
+            },
+            _ => None,
+        };
+        method.unwrap_or_else(|| {
+            self.unknown_method(&ty, name);
+            Ty::Unknown
+        })
+    }
+
+    fn visit_range(&mut self, _expr: &Expr, start: &Expr, end: &Expr, _inclusive: bool) -> Ty {
+        Ty::Range(self.unify_operands(start, end))
+    }
+
+    fn visit_cast(&mut self, _expr: &Expr, val: &Expr, ty: &Type) -> Ty {
+        let found = self.visit_expr(val);
+        let target = self.lower_type(ty);
+        match (&found, &target) {
+            (Ty::Int(_), Ty::Int(_)) | (Ty::Bool, Ty::Int(_)) | (Ty::Unknown, Ty::Int(_)) => {}
+            (_, Ty::Unknown) => return Ty::Unknown,
+            _ => {
+                let label = self.new_label(
+                    format!("cannot cast `{}` to `{}`", found, target),
+                    val.span().merge(ty.span()),
+                );
+                let diagnostic = self.new_error("invalid cast", label).with_notes(vec![
+                    "only integers and booleans can be cast, and only to integers".to_string(),
+                ]);
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        target
+    }
+
+    fn visit_match(&mut self, _expr: &Expr, scrutinee: &Expr, arms: &[MatchArm]) -> Ty {
+        self.check_match(scrutinee, arms)
+    }
+
+    fn visit_if(
+        &mut self,
+        expr: &Expr,
+        cond: &Expr,
+        then: &Block,
+        otherwise: Option<&Block>,
+    ) -> Ty {
+        self.visit_expr(cond);
+        let then_ty = self.check_block(then);
+        let otherwise = match otherwise {
+            Some(otherwise) => otherwise,
+            None => {
+                // Without an `else`, the `if` has no value if the condition is false.
+                if !then_ty.is_compatible(&Ty::Unit) {
+                    let span = then.expr.as_ref().map_or(expr.span(), |expr| expr.span());
+                    let label = self.new_label(format!("expected `()`, found `{}`", then_ty), span);
+                    let diagnostic = self
+                        .new_error("`if` may be missing an `else` clause", label)
+                        .with_notes(vec![
+                            "`if` expressions without `else` evaluate to `()`".to_string()
+                        ]);
+                    self.diagnostics.push(diagnostic);
+                }
+                return Ty::Unit;
+            }
+        };
+        let otherwise_ty = self.check_block(otherwise);
+        if then_ty.is_compatible(&otherwise_ty) {
+            return match then_ty {
+                Ty::Unknown => otherwise_ty,
+                ty => ty,
+            };
+        }
+
+        // Blocks don't have a span, so a block without a value points at the whole `if`.
+        let span = otherwise
+            .expr
+            .as_ref()
+            .map_or(expr.span(), |expr| expr.span());
+        let mut labels = vec![self.new_label(
+            format!("expected `{}`, found `{}`", then_ty, otherwise_ty),
+            span,
+        )];
+        if let Some(then) = &then.expr {
+            labels.push(
+                Label::secondary(self.file, then.span()).with_message("expected because of this"),
+            );
+        }
+        let diagnostic = Diagnostic::error()
+            .with_message("`if` and `else` have incompatible types")
+            .with_labels(labels);
+        self.diagnostics.push(diagnostic);
+        Ty::Unknown
+    }
+
+    fn visit_block(&mut self, _expr: &Expr, block: &Block) -> Ty {
+        self.check_block(block)
+    }
+
+    fn visit_assign(&mut self, _expr: &Expr, target: &Expr, val: &Expr) -> Ty {
+        let target_ty = self.visit_expr(target);
+        let val_ty = self.visit_expr(val);
+        self.expect_value(val, &val_ty, &target_ty);
+        target_ty
+    }
+
+    fn visit_binary(
+        &mut self,
+        _expr: &Expr,
+        left: &Expr,
+        op: &BinaryOperation,
+        right: &Expr,
+    ) -> Ty {
+        match op {
+            BinaryOperation::And | BinaryOperation::Or => {
+                for operand in &[left, right] {
+                    let ty = self.visit_expr(operand);
+                    self.expect_type(operand, &ty, Ty::Bool);
+                }
+                Ty::Bool
+            }
+            // The bitwise operators work on booleans too, where `^` is a logical xor.
+            BinaryOperation::BitAnd | BinaryOperation::BitOr | BinaryOperation::BitXor => {
+                let left_ty = self.visit_expr(left);
+                let right_ty = self.visit_expr(right);
+                if left_ty == Ty::Bool {
+                    self.expect_type(right, &right_ty, Ty::Bool);
+                    return Ty::Bool;
+                }
+                Ty::Int(self.unify_int_types(left, &left_ty, right, &right_ty))
+            }
+            // The operands of every other operation have to be integers of the same type.
+            BinaryOperation::Plus
+            | BinaryOperation::Minus
+            | BinaryOperation::Mul
+            | BinaryOperation::Div
+            | BinaryOperation::Rem
+            | BinaryOperation::Pow
+            | BinaryOperation::Shl
+            | BinaryOperation::Shr => Ty::Int(self.unify_operands(left, right)),
+            BinaryOperation::NotEqual
+            | BinaryOperation::EqualEqual
+            | BinaryOperation::Less
+            | BinaryOperation::LessEqual
+            | BinaryOperation::Greater
+            | BinaryOperation::GreaterEqual => {
+                self.unify_operands(left, right);
+                Ty::Bool
+            }
+        }
+    }
+
+    fn visit_unary(&mut self, expr: &Expr, op: &UnaryOperation, right: &Expr) -> Ty {
+        let ty = self.visit_expr(right);
+        match (op, ty) {
+            (UnaryOperation::Negate, Ty::Int(Some(int_ty))) if !int_ty.is_signed() => {
+                let label = self.new_label("cannot be negated", expr.span());
+                let diagnostic = self.new_error(
+                    format!("cannot apply unary operator `-` to type `{}`", int_ty),
+                    label,
+                );
+                self.diagnostics.push(diagnostic);
+                Ty::Int(Some(int_ty))
+            }
+            (UnaryOperation::Negate, Ty::Int(int_ty)) => Ty::Int(int_ty),
+            (UnaryOperation::Negate, ty) => {
+                self.expect_type(right, &ty, Ty::Int(None));
+                Ty::Int(None)
+            }
+            (UnaryOperation::Not, ty) => {
+                self.expect_type(right, &ty, Ty::Bool);
+                Ty::Bool
+            }
+        }
+    }
+}
+
+impl StmtVisitor for TypeChecker {
+    type Output = ();
+
+    fn visit_expr_stmt(&mut self, _stmt: &Stmt, expr: &Expr) {
+        self.visit_expr(expr);
+    }
+
+    fn visit_loop(&mut self, _stmt: &Stmt, block: &Block) {
+        self.check_block(block);
+    }
+
+    fn visit_while(&mut self, _stmt: &Stmt, cond: &Expr, block: &Block) {
+        self.visit_expr(cond);
+        self.check_block(block);
+    }
+
+    fn visit_for(&mut self, _stmt: &Stmt, var: &Identifier, iter: &Expr, block: &Block) {
+        let elem = match self.visit_expr(iter) {
+            Ty::Range(ty) => Ty::Int(ty),
+            Ty::Array(elem) => *elem,
+            Ty::Map(key, _) => *key,
+            Ty::String => Ty::String,
+            Ty::Unknown => Ty::Unknown,
+            ty => {
+                let label = self.new_label("expected a range, array, map or string", iter.span());
+                let diagnostic = self.new_error(format!("`{}` is not iterable", ty), label);
+                self.diagnostics.push(diagnostic);
+                Ty::Unknown
+            }
+        };
+        self.scoped(|this| {
+            this.define(var, elem);
+            this.check_block(block);
+        });
+    }
+
+    fn visit_break(&mut self, _stmt: &Stmt) {}
+
+    fn visit_continue(&mut self, _stmt: &Stmt) {}
+
+    fn visit_error_stmt(&mut self, _stmt: &Stmt) {}
+
+    fn visit_return(&mut self, stmt: &Stmt, val: Option<&Expr>) {
+        let expected = self.ret.clone();
+        match val {
+            Some(val) => {
+                let ty = self.visit_expr(val);
+                self.expect_value(val, &ty, &expected);
+            }
+            None if !Ty::Unit.is_compatible(&expected) => {
+                let label =
+                    self.new_label(format!("expected `{}`, found `()`", expected), stmt.span());
+                let diagnostic = self.new_error("mismatched types", label);
+                self.diagnostics.push(diagnostic);
+            }
+            None => {}
+        }
+    }
+
+    fn visit_let(
+        &mut self,
+        _stmt: &Stmt,
+        pattern: &LetPattern,
+        _mutable: bool,
+        ty: Option<&Type>,
+        val: &Expr,
+    ) {
+        let val_ty = self.visit_expr(val);
+        let ty = match ty {
+            Some(ty) => {
+                let ty = self.lower_type(ty);
+                self.expect_value(val, &val_ty, &ty);
+                ty
+            }
+            None => val_ty,
+        };
+        self.define_pattern(pattern, ty);
+    }
+}
+
+impl ItemVisitor for TypeChecker {
+    type Output = ();
+
+    fn visit_def(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        public: bool,
+        args: &[DefArgument],
+        ret: Option<&Type>,
+        body: &Block,
+    ) {
+        let sig = match self.function(name.data()) {
+            Some(sig) if sig.name.span() == name.span() => (sig.args.clone(), sig.ret.clone()),
+            // A function with the name of another one, which `declare` reported and skipped.
+            _ => {
+                let sig = self.signature(name, public, args, ret);
+                (sig.args, sig.ret)
+            }
+        };
+        self.check_def(name, sig, args, body, None);
+    }
+
+    fn visit_struct(
+        &mut self,
+        _item: &Item,
+        _name: &Identifier,
+        _public: bool,
+        _fields: &[StructField],
+    ) {
+    }
+
+    fn visit_enum(
+        &mut self,
+        _item: &Item,
+        _name: &Identifier,
+        _public: bool,
+        _variants: &[Variant],
+    ) {
+    }
+
+    fn visit_const(
+        &mut self,
+        _item: &Item,
+        name: &Identifier,
+        _public: bool,
+        ty: &Type,
+        val: &Expr,
+    ) {
+        let expected = match self.const_info(self.file, name.data()) {
+            Some(info) if info.name.span() == name.span() => info.ty.clone(),
+            // A constant with the name of another one, which `declare` reported and skipped.
+            _ => self.lower_type(ty),
+        };
+        let ty = self.visit_expr(val);
+        self.expect_value(val, &ty, &expected);
+        if let Some(expr) = find_non_const(val, &mut |expr| self.is_const(expr)) {
+            self.diagnostics
+                .push(non_const_error(self.file, expr.span()));
+        }
+    }
+
+    fn visit_import(&mut self, _item: &Item, _name: &Identifier, _path: Option<&Spanned<String>>) {}
+
+    fn visit_error_item(&mut self, _item: &Item) {}
+
+    fn visit_impl(&mut self, _item: &Item, ty: &Identifier, methods: &[Item]) {
+        let self_ty = self.named_type(self.file, ty.data()).unwrap_or(Ty::Unknown);
+        for method in methods {
+            if let ItemKind::Def {
+                name,
+                public,
+                args,
+                ret,
+                body,
+            } = method.data()
+            {
+                let sig = match self.method(self.file, ty.data(), name.data()) {
+                    Some(sig) if sig.name.span() == name.span() => {
+                        (sig.args.clone(), sig.ret.clone())
+                    }
+                    _ => {
+                        let sig = self.signature(name, *public, args, ret.as_ref());
+                        (sig.args, sig.ret)
+                    }
+                };
+                self.check_def(name, sig, args, body, Some(self_ty.clone()));
+            }
+        }
+    }
+}
+
+/// Whether the body of a `loop` contains a `break` that leaves it, without which
+/// the loop never ends normally.
+fn breaks(body: &Block) -> bool {
+    struct FindBreak(bool);
+
+    impl Visitor for FindBreak {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            match stmt.data() {
+                StmtKind::Break => self.0 = true,
+                // A `break` in a nested loop leaves that loop.
+                StmtKind::Loop(_) | StmtKind::While { .. } | StmtKind::For { .. } => {}
+                _ => visit::walk_stmt(self, stmt),
+            }
+        }
+
+        // A closure is a function of its own.
+        fn visit_expr(&mut self, expr: &Expr) {
+            if !matches!(expr.data(), ExprKind::Closure { .. }) {
+                visit::walk_expr(self, expr)
+            }
+        }
+    }
+
+    let mut finder = FindBreak(false);
+    finder.visit_block(body);
+    finder.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Files, loader::Loader, syntax::Parser};
+
+    fn check(src: &str) -> Vec<String> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        TypeChecker::new(file)
+            .check(&Program::single(file, items))
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    /// Checks a program that consists of multiple files, where the root is `main.tre`.
+    fn check_modules(sources: &[(&str, &str)]) -> (Files, Vec<Diagnostic>) {
+        let sources = sources
+            .iter()
+            .map(|(path, src)| (std::path::PathBuf::from(path), src.to_string()))
+            .collect::<HashMap<_, _>>();
+        let mut files = Files::new();
+        let program = Loader::new(&mut files)
+            .with_reader(move |path| Ok(sources[path].clone()))
+            .load("main.tre")
+            .unwrap();
+        let diagnostics = TypeChecker::new(program.root).check(&program);
+        (files, diagnostics)
+    }
+
+    #[test]
+    fn test_struct_literals() {
+        let errors = check(
+            "struct Point { x: i64, y: i64 }
+            def main() {
+                let p = Point { x: 1, y: 2 };
+                let q = Point { x: 1, z: 2 };
+                let r = Point { x: 1, x: 2, y: 3 };
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "struct `Point` has no field named `z`",
+                "missing field `y` in initializer of `Point`",
+                "field `x` specified more than once",
+            ]
+        );
+
+        let errors = check(
+            "struct Pixel { r: u8, name: string }
+            def main() {
+                let a = Pixel { r: 255, name: \"a\" };
+                let b = Pixel { r: 5 as i64, name: \"b\" };
+                let c = Pixel { r: 256, name: 1 };
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "mismatched types",
+                "literal out of range for `u8`",
+                "mismatched types",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typed_let() {
+        let errors = check(
+            "def main() {
+                let a: u8 = 255;
+                let b: u8 = a + 1;
+                let c: u8 = 300;
+                let d: u8 = 5 as i64;
+                let e: string = 1;
+                let (f, g): (u8, string) = (1, \"g\");
+                let h: i64 = b;
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "literal out of range for `u8`",
+                "mismatched types",
+                "mismatched types",
+                "mismatched types",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_types() {
+        let errors = check(
+            "def a(): i64 { }
+            def b(): i64 { return \"s\"; }
+            def c(): string { 5 }
+            def d(x: bool): i64 { if (x) { return 1; } }
+            def e(): i64 { return; }
+            def f(): u8 { return 300; }
+            def ok(x: bool): i64 {
+                if (x) { return 1; }
+                let g = |y| { return y; };
+                if (x) { 2 } else { return 3; }
+            }
+            def forever(): i64 { loop { return 1; } }
+            def stops(): i64 { loop { break; } }
+            def unit() { return; }
+            def wide(): u8 { let x = 5 as i64; x }
+            def early(): u8 { return 5 as i64; }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "function `a` may not return a value",
+                "mismatched types",
+                "mismatched types",
+                "mismatched types",
+                "mismatched types",
+                "literal out of range for `u8`",
+                "function `stops` may not return a value",
+                "mismatched types",
+                "mismatched types",
+            ]
+        );
+    }
+
+    /// Returns the message of every diagnostic and the lines of its labels.
+    fn check_lines(src: &str) -> Vec<(String, Vec<usize>)> {
+        let mut files = Files::new();
+        let file = files.add("test", src.to_string());
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        let line = |offset: usize| src[..offset].lines().count();
+        TypeChecker::new(file)
+            .check(&Program::single(file, items))
+            .into_iter()
+            .map(|diagnostic| {
+                let lines = diagnostic
+                    .labels
+                    .iter()
+                    .map(|label| line(label.range.end))
+                    .collect();
+                (diagnostic.message, lines)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicate_definitions() {
+        let errors = check_lines(
+            "struct S { x: i64 }
+            struct S { y: i64 }
+            enum S { A }
+            def main() { let s = S { x: 1 }; }",
+        );
+        let defined = |name: &str| format!("the name `{}` is defined multiple times", name);
+        assert_eq!(
+            errors,
+            vec![(defined("S"), vec![2, 1]), (defined("S"), vec![3, 1])]
+        );
+
+        let errors = check_lines(
+            "struct S { x: i64 }
+            impl S { def m(): i64 { 1 } }
+            impl S { def m(): string { 2 } }
+            def f(): i64 { 1 }
+            def f(): i64 { \"s\" }
+            def g(a: i64, a: i64) {}
+            def main() { let h = |b, b| b; }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                (defined("m"), vec![3, 2]),
+                (defined("f"), vec![5, 4]),
+                (defined("a"), vec![6, 6]),
+                // The bodies of duplicates are still checked.
+                ("mismatched types".to_string(), vec![3]),
+                ("mismatched types".to_string(), vec![5]),
+                (defined("b"), vec![7, 7]),
+            ]
+        );
+
+        let errors = check_lines(
+            "const A: i64 = 1;
+            const A: string = 2;
+            def main() { print(A); }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                (defined("A"), vec![2, 1]),
+                ("mismatched types".to_string(), vec![2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_argument_count() {
+        let mut files = Files::new();
+        let src = "struct P { x: i64 }
+            impl P { def get(i: i64): i64 { self.x + i } }
+            def f(a: i64): i64 { a }
+            def main() {
+                f(1, 2);
+                f();
+                P { x: 1 }.get();
+                f(f(1));
+            }";
+        let file = files.add("test", src.to_string());
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        let diagnostics = TypeChecker::new(file).check(&Program::single(file, items));
+        let errors: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let label = &diagnostic.labels[0];
+                (
+                    diagnostic.message.as_str(),
+                    label.message.as_str(),
+                    &src[label.range.clone()],
+                )
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "wrong number of arguments",
+                    "expected 1 argument, found 2",
+                    "f(1, 2)"
+                ),
+                (
+                    "wrong number of arguments",
+                    "expected 1 argument, found 0",
+                    "f()"
+                ),
+                (
+                    "wrong number of arguments",
+                    "expected 1 argument, found 0",
+                    "P { x: 1 }.get()"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_access() {
+        let errors = check(
+            "struct Point { x: i64, y: i64 }
+            struct Line { from: Point, to: Point }
+            def len(line: Line): i64 {
+                line.from.z = line.to.x;
+                return line.to.y.x;
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec!["no field `z` on type `Point`", "no field `x` on type `i64`"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_types() {
+        let errors = check("struct A { b: B, a: A } def f(c: C) {}");
+        assert_eq!(errors, vec!["cannot find type `B`", "cannot find type `C`"]);
+    }
+
+    #[test]
+    fn test_methods() {
+        let errors = check(
+            "struct Point { x: i64, y: i64 }
+            impl Point {
+                def sum(): i64 {
+                    return self.x + self.y + self.z;
+                }
+            }
+            impl Line {}
+            def main() {
+                let p = Point { x: 1, y: 2 };
+                p.sum().foo();
+                p.product();
+                \"abc\".len();
+                [1].push(2);
+                \"abc\".push(1);
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "cannot find type `Line`",
+                "no field `z` on type `Point`",
+                "no method named `foo` found for `i64`",
+                "no method named `product` found for `Point`",
+                "no method named `push` found for `string`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let errors = check(
+            "def one(): i64 { return 1; }
+            def main() {
+                let f = |x: i64| x + 1;
+                f(1).foo();
+                one().bar();
+                let n = 1;
+                n();
+                let len = |x| x;
+                len(1);
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "no method named `foo` found for `i64`",
+                "no method named `bar` found for `i64`",
+                "expected function, found `int`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let errors = check(
+            "struct Point { x: i64, y: bool }
+            def main() {
+                let b = true;
+                match b { true => 1, false => 2 };
+                match b { true => 1 };
+                match b { _ => 1, false => 2 };
+                match 1 { 1 => 1, x => x.foo(), 2 => 3 };
+                match 1 { \"a\" => 1, _ => 2 };
+                let p = Point { x: 1, y: true };
+                match p { Point { y: true } => 1, Point { x: 0, y: false } => 2 };
+                match p { Point { z } => 1, _ => 2 };
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "non-exhaustive patterns: `false` not covered",
+                "unreachable pattern",
+                "no method named `foo` found for `int`",
+                "unreachable pattern",
+                "mismatched types",
+                "non-exhaustive patterns: `Point { x: _, y: false }` not covered",
+                "struct `Point` has no field named `z`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        let errors = check(
+            "enum Shape { Circle(i64), Rect(i64, i64), Empty, Empty }
+            impl Shape {
+                def area(): i64 { return 0; }
+            }
+            def describe(s: Shape): string {
+                return match s {
+                    Shape::Circle(r) => \"circle\",
+                    Shape::Rect(1, h) => \"rect\",
+                };
+            }
+            def main() {
+                let s = Shape::Rect(1);
+                Shape::Circle(true);
+                Shape::Square;
+                Shape::Empty.area().foo();
+                match s { Shape::Circle(r, x) => 1, _ => 2 };
+                match s { Shape::Circle(r) => r.foo(), _ => 2, Shape::Empty => 3 };
+                match 1 { Shape::Empty => 1, _ => 2 };
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "variant `Empty` is already declared",
+                "non-exhaustive patterns: `Shape::Empty` not covered",
+                "wrong number of arguments",
+                "mismatched types",
+                "no variant named `Square` in enum `Shape`",
+                "no method named `foo` found for `i64`",
+                "this pattern has 2 fields, but the variant `Shape::Circle` has 1",
+                "no method named `foo` found for `i64`",
+                "unreachable pattern",
+                "mismatched types",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_for() {
+        let errors = check(
+            "def main() {
+                for i in 0..10 { i.foo(); }
+                for s in [\"a\"] { s.bar(); }
+                for c in \"abc\" { c.len().baz(); }
+                for x in 1 {}
+                let r = 0..true;
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "no method named `foo` found for `int`",
+                "no method named `bar` found for `string`",
+                "no method named `baz` found for `int`",
+                "`int` is not iterable",
+                "mismatched types",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let errors = check(
+            "def main() {
+                let a = if (true) { 1 } else { \"one\" };
+                let b = if (true) { 1 } else if (false) { 2 } else { [3] };
+                let c = if (true) { 1 } else { 2 };
+                c.foo();
+                let d = { let x = \"d\"; x };
+                d.bar();
+                let e = if (true) { 1 } else { return; };
+                let f = if (true) { 1 };
+                if (true) { \"f\" }
+                if (true) { print(1) }
+                if (true) { return; }
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "`if` and `else` have incompatible types",
+                "`if` and `else` have incompatible types",
+                "no method named `foo` found for `int`",
+                "no method named `bar` found for `string`",
+                "`if` may be missing an `else` clause",
+                "`if` may be missing an `else` clause",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unit() {
+        let errors = check(
+            "def void() {}
+            def unit(): () { return; }
+            def main() {
+                let a = 1 + void();
+                let b = -unit();
+                let c = !1;
+                let d = if (true) { print(1) } else { 2 };
+                let e = if (true) { 1 } else {};
+                let f = if (true) { 1 };
+                f.foo();
+                [1].push(2).bar();
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "mismatched types",
+                "mismatched types",
+                "mismatched types",
+                "`if` and `else` have incompatible types",
+                "`if` and `else` have incompatible types",
+                "`if` may be missing an `else` clause",
+                "no method named `foo` found for `()`",
+                "no method named `bar` found for `()`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imports() {
+        let sources = [
+            (
+                "main.tre",
+                "import geo;
+                def f(a: geo::Point, b: geo::Line) {}
+                def main() {
+                    let p = geo::Point { x: 1 };
+                    p.z;
+                    geo::origin().y.foo();
+                    geo::nope();
+                    c::d();
+                }",
+            ),
+            (
+                "geo.tre",
+                "pub struct Point { x: i64, y: i64 }
+                pub def origin(): Point { return Point { x: 0, y: 0 }; }",
+            ),
+        ];
+        let (_, errors) = check_modules(&sources);
+        let errors = errors
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                "cannot find type `geo::Line`",
+                "missing field `y` in initializer of `Point`",
+                "no field `z` on type `Point`",
+                "no method named `foo` found for `i64`",
+                "cannot find `geo::nope`",
+                "cannot find module `c`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_visibility() {
+        let sources = [
+            (
+                "main.tre",
+                "import geo;
+                def f(p: geo::Point) {}
+                def main() {
+                    let p = geo::Point { x: 1 };
+                    geo::origin().sum();
+                    geo::origin().len();
+                    geo::helper();
+                }",
+            ),
+            (
+                "geo.tre",
+                "struct Point { x: i64 }
+                impl Point {
+                    pub def sum(): i64 { return self.len(); }
+                    def len(): i64 { return self.x; }
+                }
+                pub def origin(): Point { return Point { x: helper() }; }
+                def helper(): i64 { return 0; }",
+            ),
+        ];
+        let (files, errors) = check_modules(&sources);
+        let messages = errors
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "struct `Point` is private",
+                "struct `Point` is private",
+                "method `len` is private",
+                "function `helper` is private",
+            ]
+        );
+        assert_eq!(
+            errors[3].notes,
+            vec!["help: consider making it public: `pub def helper`"]
+        );
+        assert_eq!(files.name(errors[3].labels[1].file_id), "geo.tre");
+    }
+
+    #[test]
+    fn test_integer_types() {
+        let errors = check(
+            "def byte(x: u8): u8 { x }
+            def main() {
+                let a = byte(1) + 1;
+                let b = byte(1) + 256;
+                let c = byte(1) + (1 as i32);
+                byte(-1);
+                let d = -byte(1);
+                let e = 1 as i8 + 2 as i8;
+                let f = \"a\" as u8;
+                let g = (1 as i16)..(2 as u16);
+                a.foo();
+                for i in 0..byte(3) { i.bar(); }
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "literal out of range for `u8`",
+                "mismatched types",
+                "literal out of range for `u8`",
+                "cannot apply unary operator `-` to type `u8`",
+                "invalid cast",
+                "mismatched types",
+                "no method named `foo` found for `u8`",
+                "no method named `bar` found for `u8`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assignment_types() {
+        let errors = check(
+            "struct P { x: u8 }
+            def main() {
+                let mut x: u8 = 1;
+                x = 300;
+                x = 5 as i64;
+                x = 255;
+                let p = P { x: 1 };
+                p.x = 1000;
+                p.x = true;
+                let a = [1 as u8];
+                a[0] = 300;
+                a[0] = \"a\";
+                let m = { \"a\": 1 as u8 };
+                m[\"b\"] = 256;
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "literal out of range for `u8`",
+                "mismatched types",
+                "literal out of range for `u8`",
+                "mismatched types",
+                "literal out of range for `u8`",
+                "mismatched types",
+                "literal out of range for `u8`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let errors = check(
+            "def count(m: {string: u8}): u8 { m[\"a\"] }
+            def main() {
+                let m = { \"a\": 1 };
+                m[1];
+                m[\"a\"].foo();
+                m.keys().bar();
+                m.remove(\"a\").baz();
+                count({ \"a\": 1 });
+                count({ 1: 1 });
+                let bad = { [1]: 2 };
+                for k in m { k.qux(); }
+            }
+            def invalid(m: {[i64]: i64}) {}",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "invalid map key",
+                "mismatched types",
+                "no method named `foo` found for `int`",
+                "no method named `bar` found for `[string]`",
+                "no method named `baz` found for `int`",
+                "mismatched types",
+                "invalid map key",
+                "no method named `qux` found for `string`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuples() {
+        let errors = check(
+            "def divmod(a: i64, b: i64): (i64, i64) { (a / b, a - a / b * b) }
+            def first(pair: (string, bool)): string { let (s, _) = pair; s }
+            def main() {
+                let (q, r) = divmod(7, 2);
+                q.foo();
+                let (a, b, c) = divmod(7, 2);
+                let (x, y) = 1;
+                let (s, (t, _)) = (\"s\", (true, 1));
+                s.bar();
+                t.baz();
+                first((\"a\", false));
+                first((\"a\", 1));
+                let u = ();
+                u.qux();
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "no method named `foo` found for `i64`",
+                "mismatched types",
+                "mismatched types",
+                "no method named `bar` found for `string`",
+                "no method named `baz` found for `bool`",
+                "mismatched types",
+                "no method named `qux` found for `()`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_strings() {
+        let mut files = Files::new();
+        let src = "def main() { let x = 1; print(f\"x is {x.foo()}\"); }";
+        let file = files.add("test", src.to_string());
+        let items = Parser::new(&files, file).parse_items().unwrap();
+        let errors = TypeChecker::new(file).check(&Program::single(file, items));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "no method named `foo` found for `int`");
+        assert_eq!(&src[errors[0].labels[0].range.clone()], "foo");
+    }
+
+    #[test]
+    fn test_consts() {
+        let errors = check(
+            "const A: i64 = 10 * 1024;
+            const B: string = A;
+            const C: u8 = 300;
+            const D: i64 = f();
+            const E: i64 = A + C;
+            def f(): i64 { A }
+            def main() {
+                let x = A + 1;
+                print(B.len() + x);
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "mismatched types",
+                "literal out of range for `u8`",
+                "non-constant expression in a `const` item",
+                "mismatched types",
+            ]
+        );
+
+        let (_, diagnostics) = check_modules(&[
+            ("main.tre", "import a; def main() { print(a::X + a::Y); }"),
+            ("a.tre", "pub const X: i64 = 1; const Y: i64 = 2;"),
+        ]);
+        let errors = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec!["constant `Y` is private"]);
+    }
+
+    #[test]
+    fn test_operators() {
+        let errors = check(
+            "def byte(x: u8): u8 { x }
+            def main() {
+                let a = byte(1) ** 2 % 3 << 1;
+                let b = true && 1;
+                let c = 1 || false;
+                let d = (true ^ false) | true;
+                let e = true & 1;
+                let f = byte(1) & 256;
+                let g = 1 << \"a\";
+            }",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "mismatched types",
+                "mismatched types",
+                "mismatched types",
+                "literal out of range for `u8`",
+                "mismatched types",
+            ]
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Checks the patterns of `match` expressions.
+//!
+//! Patterns are lowered to [`Pat`]s, which only keep what matters for matching,
+//! and are then checked using the usefulness algorithm: an arm is unreachable if
+//! its pattern isn't useful after the arms above it, and a match is exhaustive if
+//! a wildcard isn't useful after all of its arms.
+
+use super::{Ty, TypeChecker, VariantRef};
+use crate::{
+    diagnostic::{Diagnostic, FileId},
+    syntax::{
+        ast::{Expr, FieldPattern, Literal, MatchArm, Path, Pattern, PatternKind},
+        visit::ExprVisitor,
+    },
+};
+use std::fmt;
+
+/// Something that constructs values, like a literal or a struct.
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Bool(bool),
+    Int(i128),
+    String(String),
+    /// A struct, whose fields are the arguments of the constructor.
+    Struct {
+        file: FileId,
+        name: String,
+        fields: Vec<String>,
+    },
+    /// The variant `idx` of an enum, whose fields are the arguments of the constructor.
+    Variant {
+        file: FileId,
+        name: String,
+        variant: String,
+        idx: usize,
+    },
+}
+
+/// A pattern that has been checked against the type of the value it matches.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    /// A constructor, with the patterns for its arguments in declaration order.
+    Ctor(Ctor, Vec<Pat>),
+}
+
+impl Pat {
+    fn ctor(&self) -> Option<&Ctor> {
+        match self {
+            Pat::Wild => None,
+            Pat::Ctor(ctor, _) => Some(ctor),
+        }
+    }
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Ctor(Ctor::Bool(x), _) => write!(f, "{}", x),
+            Pat::Ctor(Ctor::Int(x), _) => write!(f, "{}", x),
+            Pat::Ctor(Ctor::String(x), _) => write!(f, "{:?}", x),
+            Pat::Ctor(Ctor::Struct { name, fields, .. }, args) => {
+                write!(f, "{} {{", name)?;
+                for (idx, (field, arg)) in fields.iter().zip(args).enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {}: {}", field, arg)?;
+                }
+                write!(f, " }}")
+            }
+            Pat::Ctor(Ctor::Variant { name, variant, .. }, args) => {
+                write!(f, "{}::{}", name, variant)?;
+                if !args.is_empty() {
+                    write!(f, "(")?;
+                    for (idx, arg) in args.iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", arg)?;
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the rest of `row` if its first pattern matches the values constructed
+/// by `ctor`, with the arguments of the constructor in front of it.
+fn specialize(row: &[Pat], ctor: &Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let mut result = match &row[0] {
+        Pat::Wild => vec![Pat::Wild; arity],
+        Pat::Ctor(other, args) if other == ctor => args.clone(),
+        Pat::Ctor(..) => return None,
+    };
+    result.extend_from_slice(&row[1..]);
+    Some(result)
+}
+
+impl TypeChecker {
+    /// Checks the arms of a match on `scrutinee` and returns the type of the match.
+    pub(super) fn check_match(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Ty {
+        let ty = self.visit_expr(scrutinee);
+        let mut rows = vec![];
+        // Reachability and exhaustiveness are only checked if all patterns are valid.
+        let mut valid = true;
+        let mut result = Ty::Unknown;
+        for arm in arms {
+            let body = self.scoped(|this| {
+                match this.check_pattern(&arm.pattern, &ty) {
+                    Some(pat) if valid => {
+                        let row = vec![pat];
+                        if this
+                            .useful(&rows, &row, std::slice::from_ref(&ty))
+                            .is_none()
+                        {
+                            let label = this.new_label("unreachable pattern", arm.pattern.span());
+                            this.diagnostics.push(
+                                Diagnostic::warning()
+                                    .with_message("unreachable pattern")
+                                    .with_labels(vec![label]),
+                            );
+                        }
+                        rows.push(row);
+                    }
+                    Some(_) => {}
+                    None => valid = false,
+                }
+                this.visit_expr(&arm.body)
+            });
+            if result == Ty::Unknown {
+                result = body;
+            }
+        }
+
+        // Only types with a known set of values have to be matched exhaustively.
+        if valid && self.ctors(&ty).is_some() {
+            if let Some(witness) = self.useful(&rows, &[Pat::Wild], &[ty]) {
+                let label = self.new_label(
+                    format!("pattern `{}` not covered", witness[0]),
+                    scrutinee.span(),
+                );
+                let diagnostic = self
+                    .new_error(
+                        format!("non-exhaustive patterns: `{}` not covered", witness[0]),
+                        label,
+                    )
+                    .with_notes(vec![
+                        "help: add an arm for the missing pattern, or a wildcard pattern `_`"
+                            .to_string(),
+                    ]);
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        result
+    }
+
+    /// Checks that `pattern` can match values of type `ty` and defines its bindings
+    /// in the current scope. Returns `None` if the pattern is invalid.
+    fn check_pattern(&mut self, pattern: &Pattern, ty: &Ty) -> Option<Pat> {
+        match pattern.data() {
+            PatternKind::Wildcard => Some(Pat::Wild),
+            PatternKind::Binding(name) => {
+                self.define(name, ty.clone());
+                Some(Pat::Wild)
+            }
+            PatternKind::Literal(literal) => {
+                let (found, ctor) = match literal {
+                    Literal::Int(x) => (Ty::Int(None), Ctor::Int(*x)),
+                    Literal::String(x) => (Ty::String, Ctor::String(x.clone())),
+                    Literal::Bool(x) => (Ty::Bool, Ctor::Bool(*x)),
+                };
+                self.expect_pattern_type(pattern, ty, &found)
+                    .then(|| Pat::Ctor(ctor, vec![]))
+            }
+            PatternKind::Struct { name, fields } => {
+                self.check_struct_pattern(pattern, name, fields, ty)
+            }
+            PatternKind::Variant { name, args } => match self.resolve_variant(name) {
+                Some(variant) => self.check_variant_pattern(pattern, variant, args, ty),
+                None => {
+                    let label = self.new_label("not a variant", name.span());
+                    let diagnostic =
+                        self.new_error(format!("expected enum variant, found `{}`", name), label);
+                    self.diagnostics.push(diagnostic);
+                    None
+                }
+            },
+        }
+    }
+
+    fn check_variant_pattern(
+        &mut self,
+        pattern: &Pattern,
+        variant: VariantRef,
+        args: &[Pattern],
+        ty: &Ty,
+    ) -> Option<Pat> {
+        let (file, name, idx) = variant?;
+        let found = Ty::Enum(file, name.clone());
+        if !self.expect_pattern_type(pattern, ty, &found) {
+            return None;
+        }
+        let (variant, fields) = self
+            .enum_info(file, &name)
+            .map(|info| info.variants[idx].clone())?;
+        if fields.len() != args.len() {
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            let label = self.new_label(
+                format!(
+                    "expected {} field{}, found {}",
+                    fields.len(),
+                    plural(fields.len()),
+                    args.len()
+                ),
+                pattern.span(),
+            );
+            let diagnostic = self.new_error(
+                format!(
+                    "this pattern has {} field{}, but the variant `{}::{}` has {}",
+                    args.len(),
+                    plural(args.len()),
+                    name,
+                    variant.data(),
+                    fields.len()
+                ),
+                label,
+            );
+            self.diagnostics.push(diagnostic);
+            return None;
+        }
+
+        let mut pats = vec![];
+        for (arg, ty) in args.iter().zip(&fields) {
+            pats.push(self.check_pattern(arg, ty));
+        }
+        let ctor = Ctor::Variant {
+            file,
+            name,
+            variant: variant.data().clone(),
+            idx,
+        };
+        Some(Pat::Ctor(ctor, pats.into_iter().collect::<Option<_>>()?))
+    }
+
+    fn check_struct_pattern(
+        &mut self,
+        pattern: &Pattern,
+        name: &Path,
+        fields: &[FieldPattern],
+        ty: &Ty,
+    ) -> Option<Pat> {
+        let file = self.resolve(name)?;
+        self.check_type_visibility(file, name);
+        let (struct_name, struct_fields) = match self.struct_info(file, name.name.data()) {
+            Some(info) => (info.name.clone(), info.fields.clone()),
+            None => {
+                let label = self.new_label("not found in this scope", name.span());
+                let diagnostic = self.new_error(format!("cannot find struct `{}`", name), label);
+                self.diagnostics.push(diagnostic);
+                return None;
+            }
+        };
+        let found = Ty::Struct(file, struct_name.data().clone());
+        if !self.expect_pattern_type(pattern, ty, &found) {
+            return None;
+        }
+
+        // Fields that aren't mentioned in the pattern match anything.
+        let mut args = vec![Pat::Wild; struct_fields.len()];
+        let mut valid = true;
+        for (idx, field) in fields.iter().enumerate() {
+            if let Some(prev) = fields[..idx]
+                .iter()
+                .find(|prev| prev.name.data() == field.name.data())
+            {
+                let diagnostic = self.duplicate_field(&field.name, &prev.name);
+                self.diagnostics.push(diagnostic);
+                valid = false;
+                continue;
+            }
+            match struct_fields
+                .iter()
+                .position(|(name, _)| name.data() == field.name.data())
+            {
+                Some(pos) => match self.check_pattern(&field.pattern, &struct_fields[pos].1) {
+                    Some(pat) => args[pos] = pat,
+                    None => valid = false,
+                },
+                None => {
+                    let diagnostic = self.no_such_field(&struct_name, &field.name);
+                    self.diagnostics.push(diagnostic);
+                    valid = false;
+                }
+            }
+        }
+
+        let ctor = Ctor::Struct {
+            file,
+            name: struct_name.data().clone(),
+            fields: struct_fields
+                .iter()
+                .map(|(name, _)| name.data().clone())
+                .collect(),
+        };
+        valid.then_some(Pat::Ctor(ctor, args))
+    }
+
+    /// Reports an error if a pattern of type `found` is used to match values of type `expected`.
+    fn expect_pattern_type(&mut self, pattern: &Pattern, expected: &Ty, found: &Ty) -> bool {
+        if expected.is_compatible(found) {
+            return true;
+        }
+        let label = self.new_label(
+            format!("expected `{}`, found `{}`", expected, found),
+            pattern.span(),
+        );
+        let diagnostic = self.new_error("mismatched types", label);
+        self.diagnostics.push(diagnostic);
+        false
+    }
+
+    /// Returns every constructor of `ty`, or `None` if there are too many to list them.
+    fn ctors(&self, ty: &Ty) -> Option<Vec<Ctor>> {
+        match ty {
+            Ty::Bool => Some(vec![Ctor::Bool(true), Ctor::Bool(false)]),
+            Ty::Struct(file, name) => {
+                let info = self.struct_info(*file, name)?;
+                Some(vec![Ctor::Struct {
+                    file: *file,
+                    name: name.clone(),
+                    fields: info
+                        .fields
+                        .iter()
+                        .map(|(name, _)| name.data().clone())
+                        .collect(),
+                }])
+            }
+            Ty::Enum(file, name) => {
+                let info = self.enum_info(*file, name)?;
+                let ctors =
+                    info.variants
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, (variant, _))| Ctor::Variant {
+                            file: *file,
+                            name: name.clone(),
+                            variant: variant.data().clone(),
+                            idx,
+                        });
+                Some(ctors.collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the types of the arguments of `ctor`.
+    fn ctor_args(&self, ctor: &Ctor) -> Vec<Ty> {
+        match ctor {
+            Ctor::Struct { file, name, .. } => self
+                .struct_info(*file, name)
+                .map(|info| info.fields.iter().map(|(_, ty)| ty.clone()).collect())
+                .unwrap_or_default(),
+            Ctor::Variant {
+                file, name, idx, ..
+            } => self
+                .enum_info(*file, name)
+                .map(|info| info.variants[*idx].1.clone())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns a list of patterns that are matched by `row` but by none of the `rows`,
+    /// or `None` if there is no such value. `tys` are the types of the columns.
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], tys: &[Ty]) -> Option<Vec<Pat>> {
+        let (head, rest) = match row.split_first() {
+            Some(split) => split,
+            None if rows.is_empty() => return Some(vec![]),
+            None => return None,
+        };
+        if let Pat::Ctor(ctor, args) = head {
+            let mut row = args.clone();
+            row.extend_from_slice(rest);
+            return self.useful_ctor(rows, ctor, &row, tys);
+        }
+
+        let mut used: Vec<&Ctor> = vec![];
+        for ctor in rows.iter().filter_map(|row| row[0].ctor()) {
+            if !used.contains(&ctor) {
+                used.push(ctor);
+            }
+        }
+        let all = self.ctors(&tys[0]);
+        match all {
+            // Every constructor is matched by some row, so the wildcard is only useful
+            // if it's useful for one of the constructors.
+            Some(all) if all.iter().all(|ctor| used.contains(&ctor)) => {
+                all.iter().find_map(|ctor| {
+                    let mut row = vec![Pat::Wild; self.ctor_args(ctor).len()];
+                    row.extend_from_slice(rest);
+                    self.useful_ctor(rows, ctor, &row, tys)
+                })
+            }
+            // Otherwise, it's useful for the missing constructors if the rest of
+            // the row is useful for the rows that start with a wildcard.
+            all => {
+                let rows = rows
+                    .iter()
+                    .filter(|row| matches!(row[0], Pat::Wild))
+                    .map(|row| row[1..].to_vec())
+                    .collect::<Vec<_>>();
+                let mut witness = self.useful(&rows, rest, &tys[1..])?;
+                let missing = all
+                    .and_then(|all| all.into_iter().find(|ctor| !used.contains(&ctor)))
+                    .map(|ctor| {
+                        let args = vec![Pat::Wild; self.ctor_args(&ctor).len()];
+                        Pat::Ctor(ctor, args)
+                    });
+                witness.insert(0, missing.unwrap_or(Pat::Wild));
+                Some(witness)
+            }
+        }
+    }
+
+    /// Checks if `row`, which was specialized for `ctor` already, is useful for `rows`.
+    fn useful_ctor(
+        &self,
+        rows: &[Vec<Pat>],
+        ctor: &Ctor,
+        row: &[Pat],
+        tys: &[Ty],
+    ) -> Option<Vec<Pat>> {
+        let mut arg_tys = self.ctor_args(ctor);
+        let arity = arg_tys.len();
+        arg_tys.extend_from_slice(&tys[1..]);
+        let rows = rows
+            .iter()
+            .filter_map(|other| specialize(other, ctor, arity))
+            .collect::<Vec<_>>();
+
+        let mut witness = self.useful(&rows, row, &arg_tys)?;
+        let rest = witness.split_off(arity);
+        let mut result = vec![Pat::Ctor(ctor.clone(), witness)];
+        result.extend(rest);
+        Some(result)
+    }
+}